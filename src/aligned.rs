@@ -0,0 +1,390 @@
+/*
+ * Copyright 2025 Jason King
+ */
+
+use std::alloc::{self, Layout};
+use std::fmt;
+use std::ops::{Deref, DerefMut};
+use std::ptr::NonNull;
+
+/// The number of free buffers a [`BufPool`] keeps by default.
+const POOL_DEFAULT_MAX: usize = 8;
+
+/// A zero-initialized heap buffer whose start is aligned to (at least) a
+/// given power of two, for HBAs that reject or bounce unaligned data
+/// buffers.
+pub struct AlignedBuf {
+    ptr: NonNull<u8>,
+    len: usize,
+    cap: usize,
+    align: usize,
+}
+
+// SAFETY: AlignedBuf uniquely owns its allocation, like a Box<[u8]>.
+unsafe impl Send for AlignedBuf {}
+// SAFETY: Shared access only hands out &[u8].
+unsafe impl Sync for AlignedBuf {}
+
+impl AlignedBuf {
+    /// Allocate `len` zeroed bytes aligned to `align`.
+    ///
+    /// # Panics
+    ///
+    /// If `align` is not a power of two.
+    pub fn zeroed(len: usize, align: usize) -> Self {
+        let layout = Layout::from_size_align(len, align).expect("invalid buffer alignment");
+        let ptr = match len {
+            // A zero-sized allocation is not allowed; any aligned,
+            // non-null pointer will do.
+            0 => NonNull::new(align as *mut u8).expect("alignment is non-zero"),
+            // SAFETY: The layout has a non-zero size.
+            _ => match NonNull::new(unsafe { alloc::alloc_zeroed(layout) }) {
+                Some(ptr) => ptr,
+                None => alloc::handle_alloc_error(layout),
+            },
+        };
+        Self {
+            ptr,
+            len,
+            cap: len,
+            align,
+        }
+    }
+
+    /// Allocate `len` zeroed bytes aligned to the system page size.
+    pub fn page_aligned(len: usize) -> Self {
+        Self::zeroed(len, page_size())
+    }
+
+    /// Allocate `len` zeroed bytes suitably aligned for a device with
+    /// logical blocks of `block_size` bytes (see [`alignment_for`]).
+    pub fn for_block_size(len: usize, block_size: u32) -> Self {
+        Self::zeroed(len, alignment_for(block_size))
+    }
+
+    pub fn align(&self) -> usize {
+        self.align
+    }
+
+    /// The number of bytes allocated, which may exceed the length of a
+    /// buffer reused from a [`BufPool`].
+    pub fn capacity(&self) -> usize {
+        self.cap
+    }
+
+    /// Set the length to `len` (no more than the capacity) and zero the
+    /// contents.
+    fn reset(&mut self, len: usize) {
+        assert!(len <= self.cap);
+        self.len = len;
+        self.fill(0);
+    }
+}
+
+impl Deref for AlignedBuf {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        // SAFETY: ptr is valid for cap >= len initialized bytes for as long
+        // as self is borrowed.
+        unsafe { std::slice::from_raw_parts(self.ptr.as_ptr(), self.len) }
+    }
+}
+
+impl DerefMut for AlignedBuf {
+    fn deref_mut(&mut self) -> &mut [u8] {
+        // SAFETY: As above, and self is borrowed uniquely.
+        unsafe { std::slice::from_raw_parts_mut(self.ptr.as_ptr(), self.len) }
+    }
+}
+
+impl AsRef<[u8]> for AlignedBuf {
+    fn as_ref(&self) -> &[u8] {
+        self
+    }
+}
+
+impl AsMut<[u8]> for AlignedBuf {
+    fn as_mut(&mut self) -> &mut [u8] {
+        self
+    }
+}
+
+impl fmt::Debug for AlignedBuf {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("AlignedBuf")
+            .field("len", &self.len)
+            .field("cap", &self.cap)
+            .field("align", &self.align)
+            .finish()
+    }
+}
+
+impl Drop for AlignedBuf {
+    fn drop(&mut self) {
+        if self.cap != 0 {
+            // SAFETY: ptr was allocated in zeroed() with this layout.
+            unsafe {
+                alloc::dealloc(
+                    self.ptr.as_ptr(),
+                    Layout::from_size_align_unchecked(self.cap, self.align),
+                )
+            }
+        }
+    }
+}
+
+/// A small pool of [`AlignedBuf`]s, reused across commands to avoid an
+/// allocation per transfer.
+#[derive(Debug)]
+pub struct BufPool {
+    align: usize,
+    max_free: usize,
+    free: Vec<AlignedBuf>,
+}
+
+impl BufPool {
+    /// A pool of buffers aligned to `align`, which must be a power of two.
+    pub fn new(align: usize) -> Self {
+        assert!(align.is_power_of_two(), "invalid buffer alignment");
+        Self {
+            align,
+            max_free: POOL_DEFAULT_MAX,
+            free: Vec::new(),
+        }
+    }
+
+    /// A pool of buffers suitably aligned for a device with logical blocks
+    /// of `block_size` bytes.
+    pub fn for_block_size(block_size: u32) -> Self {
+        Self::new(alignment_for(block_size))
+    }
+
+    /// Keep at most `max_free` buffers for reuse.
+    pub fn with_max_free(mut self, max_free: usize) -> Self {
+        self.max_free = max_free;
+        self.free.truncate(max_free);
+        self
+    }
+
+    pub fn align(&self) -> usize {
+        self.align
+    }
+
+    /// A zeroed buffer of `len` bytes, reusing a free one if one is large
+    /// enough.
+    pub fn get(&mut self, len: usize) -> AlignedBuf {
+        // Prefer the smallest free buffer that fits.
+        let best = self
+            .free
+            .iter()
+            .enumerate()
+            .filter(|(_, buf)| buf.cap >= len)
+            .min_by_key(|(_, buf)| buf.cap)
+            .map(|(i, _)| i);
+
+        match best {
+            Some(i) => {
+                let mut buf = self.free.swap_remove(i);
+                buf.reset(len);
+                buf
+            }
+            None => AlignedBuf::zeroed(len, self.align),
+        }
+    }
+
+    /// Return `buf` to the pool. It is dropped if the pool is full or
+    /// `buf` is less strictly aligned than the pool's buffers.
+    pub fn put(&mut self, buf: AlignedBuf) {
+        if buf.align < self.align || buf.cap == 0 {
+            return;
+        }
+        if self.free.len() >= self.max_free {
+            // Keep the larger buffers, which can satisfy more requests.
+            match self.free.iter().enumerate().min_by_key(|(_, b)| b.cap) {
+                Some((i, smallest)) if smallest.cap < buf.cap => {
+                    self.free.swap_remove(i);
+                }
+                _ => return,
+            }
+        }
+        self.free.push(buf);
+    }
+
+    /// The number of free buffers held.
+    pub fn len(&self) -> usize {
+        self.free.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.free.is_empty()
+    }
+}
+
+impl Default for BufPool {
+    /// A pool of page-aligned buffers.
+    fn default() -> Self {
+        Self::new(page_size())
+    }
+}
+
+/// The alignment used for data buffers for a device with logical blocks of
+/// `block_size` bytes: the larger of the block size (if it is a power of
+/// two) and the page size.
+pub fn alignment_for(block_size: u32) -> usize {
+    let page = page_size();
+    match block_size.is_power_of_two() {
+        true => page.max(block_size as usize),
+        false => page,
+    }
+}
+
+/// The system page size, or 4096 if it cannot be determined.
+pub(crate) fn page_size() -> usize {
+    // SAFETY: sysconf has no preconditions.
+    match unsafe { libc::sysconf(libc::_SC_PAGESIZE) } {
+        n if n > 0 => n as usize,
+        _ => 4096,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn is_aligned(buf: &AlignedBuf, align: usize) -> bool {
+        (buf.as_ptr() as usize).is_multiple_of(align)
+    }
+
+    #[test]
+    fn alignment() {
+        for align in [512, 4096, page_size(), 1 << 16] {
+            for len in [1, 511, 512, 4097] {
+                let buf = AlignedBuf::zeroed(len, align);
+                assert!(is_aligned(&buf, align), "{len} bytes at {align}");
+                assert_eq!((buf.len(), buf.capacity(), buf.align()), (len, len, align));
+                assert!(buf.iter().all(|&b| b == 0));
+            }
+        }
+
+        let buf = AlignedBuf::page_aligned(100);
+        assert_eq!(buf.align(), page_size());
+        assert!(is_aligned(&buf, page_size()));
+
+        assert_eq!(alignment_for(512), page_size());
+        assert_eq!(alignment_for(520), page_size());
+        assert_eq!(alignment_for(1 << 20), 1 << 20);
+        let buf = AlignedBuf::for_block_size(4096, 1 << 20);
+        assert!(is_aligned(&buf, 1 << 20));
+    }
+
+    #[test]
+    #[should_panic(expected = "invalid buffer alignment")]
+    fn alignment_not_power_of_two() {
+        AlignedBuf::zeroed(16, 24);
+    }
+
+    #[test]
+    fn zero_length() {
+        for align in [1, 512, page_size()] {
+            let mut buf = AlignedBuf::zeroed(0, align);
+            assert!(buf.is_empty());
+            assert_eq!(buf.capacity(), 0);
+            assert!(is_aligned(&buf, align));
+            assert_eq!(buf.as_mut(), &mut [] as &mut [u8]);
+        }
+        assert_eq!(
+            format!("{:?}", AlignedBuf::zeroed(0, 512)),
+            "AlignedBuf { len: 0, cap: 0, align: 512 }"
+        );
+    }
+
+    #[test]
+    fn send_and_sync() {
+        fn check<T: Send + Sync>() {}
+        check::<AlignedBuf>();
+        check::<BufPool>();
+    }
+
+    #[test]
+    fn reuse() {
+        let mut pool = BufPool::new(4096);
+        assert!(pool.is_empty());
+
+        let mut buf = pool.get(4096);
+        buf.fill(0xaa);
+        let ptr = buf.as_ptr();
+        pool.put(buf);
+        assert_eq!(pool.len(), 1);
+
+        // A smaller request reuses the buffer, zeroed and cut to length.
+        let buf = pool.get(1000);
+        assert_eq!(buf.as_ptr(), ptr);
+        assert_eq!((buf.len(), buf.capacity()), (1000, 4096));
+        assert!(buf.iter().all(|&b| b == 0));
+        assert!(pool.is_empty());
+
+        // A larger one cannot.
+        pool.put(buf);
+        let big = pool.get(4097);
+        assert_ne!(big.as_ptr(), ptr);
+        assert!(is_aligned(&big, 4096));
+        assert_eq!(pool.len(), 1);
+    }
+
+    #[test]
+    fn best_fit() {
+        let mut pool = BufPool::new(512);
+        for cap in [8192, 4096, 16384] {
+            pool.put(AlignedBuf::zeroed(cap, 512));
+        }
+
+        assert_eq!(pool.get(4000).capacity(), 4096);
+        assert_eq!(pool.get(4000).capacity(), 8192);
+        assert_eq!(pool.get(0).capacity(), 16384);
+        assert!(pool.is_empty());
+    }
+
+    #[test]
+    fn rejected_buffers() {
+        let mut pool = BufPool::new(4096);
+        // Less strictly aligned, or empty, buffers are not kept.
+        pool.put(AlignedBuf::zeroed(8192, 512));
+        pool.put(AlignedBuf::zeroed(0, 4096));
+        assert!(pool.is_empty());
+
+        // More strictly aligned ones are.
+        pool.put(AlignedBuf::zeroed(8192, 8192));
+        assert_eq!(pool.len(), 1);
+
+        assert_eq!(BufPool::default().align(), page_size());
+        assert_eq!(BufPool::for_block_size(1 << 20).align(), 1 << 20);
+    }
+
+    #[test]
+    fn eviction() {
+        let mut pool = BufPool::new(512).with_max_free(2);
+        pool.put(AlignedBuf::zeroed(1024, 512));
+        pool.put(AlignedBuf::zeroed(2048, 512));
+        assert_eq!(pool.len(), 2);
+
+        // A full pool drops a buffer no larger than the ones it holds...
+        pool.put(AlignedBuf::zeroed(1024, 512));
+        assert_eq!(pool.len(), 2);
+        // ...and evicts its smallest for a larger one.
+        pool.put(AlignedBuf::zeroed(4096, 512));
+        assert_eq!(pool.len(), 2);
+
+        assert_eq!(pool.get(1).capacity(), 2048);
+        assert_eq!(pool.get(1).capacity(), 4096);
+        assert_eq!(pool.get(1).capacity(), 1);
+
+        // Shrinking the pool drops what no longer fits.
+        let mut pool = BufPool::new(512);
+        for _ in 0..POOL_DEFAULT_MAX + 2 {
+            pool.put(AlignedBuf::zeroed(512, 512));
+        }
+        assert_eq!(pool.len(), POOL_DEFAULT_MAX);
+        assert_eq!(pool.with_max_free(3).len(), 3);
+    }
+}
@@ -0,0 +1,418 @@
+/*
+ * Copyright 2025 Jason King
+ */
+
+use crate::aligned::alignment_for;
+use crate::cdb::{Cdb, Read, Write};
+use crate::vpd::{BlockLimits, BLOCK_LIMITS};
+use crate::{
+    AlignedBuf, BufPool, CommandResult, DataBuffer, Device, Flags, ScsiError, Timeout, Transport,
+};
+use std::fmt;
+
+/// The transfer size used when the transport cannot report its limit.
+const FALLBACK_MAX_XFER: usize = 64 * 1024;
+
+/// The largest transfer a [`Device`] will issue in a single command.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct TransferLimits {
+    /// The logical block length in bytes.
+    pub block_size: u32,
+    /// The maximum number of blocks per command: the smaller of the
+    /// transport's limit and the device's (from the block limits VPD page).
+    pub max_blocks: u32,
+}
+
+impl TransferLimits {
+    pub fn max_bytes(&self) -> usize {
+        self.max_blocks as usize * self.block_size as usize
+    }
+}
+
+/// A failed block transfer.
+#[derive(Debug)]
+pub struct BlockIoError {
+    /// The LBA that failed: the one reported in the sense data if the
+    /// device gave one, the first LBA not transferred if the command was
+    /// short, otherwise the first LBA of the failing command.
+    pub lba: u64,
+    /// The number of blocks transferred successfully before the failure.
+    pub blocks_done: u64,
+    pub error: ScsiError,
+}
+
+impl fmt::Display for BlockIoError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "LBA {:#x}: {}", self.lba, self.error)
+    }
+}
+
+impl std::error::Error for BlockIoError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        Some(&self.error)
+    }
+}
+
+impl From<BlockIoError> for std::io::Error {
+    /// The error kind is that of the underlying [`ScsiError`], with the
+    /// [`BlockIoError`] kept as the payload for its LBA.
+    fn from(e: BlockIoError) -> Self {
+        std::io::Error::new(e.error.io_kind(), e)
+    }
+}
+
+impl<T: Transport> Device<T> {
+    /// The transfer limits for block I/O. These are queried on first use
+    /// and cached; see [`invalidate_limits`](Self::invalidate_limits).
+    pub fn transfer_limits(&mut self) -> Result<TransferLimits, ScsiError> {
+        if let Some(limits) = self.limits {
+            return Ok(limits);
+        }
+
+        let block_size = self.capacity()?.block_size;
+        if block_size == 0 {
+            return Err(ScsiError::InvalidCommand(
+                "device reports a zero block size",
+            ));
+        }
+
+        let max_xfer = match self.max_xfer() {
+            Ok(max) => max,
+            Err(ScsiError::Os(e)) if e.kind() == std::io::ErrorKind::Unsupported => {
+                FALLBACK_MAX_XFER
+            }
+            Err(e) => return Err(e),
+        };
+        let mut max_blocks = (max_xfer / block_size as usize).min(u32::MAX as usize) as u32;
+
+        // The block limits page is optional; ignore devices that lack it.
+        if let Some(b0) = self
+            .vpd_page(BLOCK_LIMITS)
+            .ok()
+            .and_then(|page| BlockLimits::parse(&page))
+        {
+            if b0.maximum_transfer_length != 0 {
+                max_blocks = max_blocks.min(b0.maximum_transfer_length);
+            }
+        }
+
+        let limits = TransferLimits {
+            block_size,
+            max_blocks: max_blocks.max(1),
+        };
+        self.limits = Some(limits);
+        if self.pool.align() != alignment_for(block_size) {
+            self.pool = BufPool::for_block_size(block_size);
+        }
+        Ok(limits)
+    }
+
+    /// A zeroed buffer of `len` bytes, aligned for the device's block size
+    /// and taken from its buffer pool. Return it with
+    /// [`release_buf`](Self::release_buf) once done to have it reused.
+    pub fn alloc_buf(&mut self, len: usize) -> Result<AlignedBuf, ScsiError> {
+        self.transfer_limits()?;
+        Ok(self.pool.get(len))
+    }
+
+    /// A zeroed, aligned buffer of `blocks` logical blocks (see
+    /// [`alloc_buf`](Self::alloc_buf)).
+    pub fn alloc_blocks(&mut self, blocks: u32) -> Result<AlignedBuf, ScsiError> {
+        let limits = self.transfer_limits()?;
+        Ok(self.pool.get(blocks as usize * limits.block_size as usize))
+    }
+
+    /// Return a buffer to the device's buffer pool.
+    pub fn release_buf(&mut self, buf: AlignedBuf) {
+        self.pool.put(buf);
+    }
+
+    /// Discard the cached [`TransferLimits`] (e.g. after the device has
+    /// been reformatted).
+    pub fn invalidate_limits(&mut self) {
+        self.limits = None;
+    }
+
+    /// Read `buf.len()` bytes starting at `lba`, split into as many READ
+    /// commands as the transfer limits require. `buf` must be a multiple
+    /// of the block size, and may not run past LBA `u64::MAX`. A command
+    /// that transfers less than it was asked to stops the transfer with
+    /// [`ScsiError::ShortTransfer`], the error pointing at the first block
+    /// not transferred. The returned result carries the total retries of
+    /// all the commands.
+    pub fn read_blocks(&mut self, lba: u64, buf: &mut [u8]) -> Result<CommandResult, BlockIoError> {
+        self.blocks(lba, DataBuffer::In(buf))
+    }
+
+    /// Write `buf` starting at `lba`, split into as many WRITE commands as
+    /// the transfer limits require. `buf` must be a multiple of the block
+    /// size. Short writes fail as for [`read_blocks`](Self::read_blocks).
+    pub fn write_blocks(&mut self, lba: u64, buf: &[u8]) -> Result<CommandResult, BlockIoError> {
+        self.blocks(lba, DataBuffer::Out(buf))
+    }
+
+    fn blocks(&mut self, lba: u64, mut buf: DataBuffer<'_>) -> Result<CommandResult, BlockIoError> {
+        let fail = |lba, blocks_done, error| BlockIoError {
+            lba,
+            blocks_done,
+            error,
+        };
+
+        let limits = self.transfer_limits().map_err(|e| fail(lba, 0, e))?;
+        let bs = limits.block_size as usize;
+        let len = buf.len();
+        if !len.is_multiple_of(bs) {
+            return Err(fail(
+                lba,
+                0,
+                ScsiError::InvalidCommand("buffer is not a multiple of the block size"),
+            ));
+        }
+        if len != 0 && lba.checked_add((len / bs) as u64 - 1).is_none() {
+            return Err(fail(
+                lba,
+                0,
+                ScsiError::InvalidCommand("transfer extends past the largest LBA"),
+            ));
+        }
+
+        let mut total = CommandResult::default();
+        let mut done = 0u64;
+        let mut off = 0;
+        while off < len {
+            let n = (len - off).min(limits.max_bytes());
+            let start = lba + done;
+            let blocks = (n / bs) as u32;
+            let result = match &mut buf {
+                DataBuffer::Out(data) => {
+                    let cdb = Write::new(start, blocks, limits.block_size);
+                    self.write(
+                        &cdb.to_bytes(),
+                        &data[off..off + n],
+                        None,
+                        Flags::empty(),
+                        Timeout::DEFAULT,
+                    )
+                }
+                DataBuffer::In(data) => {
+                    let cdb = Read::new(start, blocks, limits.block_size);
+                    self.read(
+                        &cdb.to_bytes(),
+                        &mut data[off..off + n],
+                        None,
+                        Flags::empty(),
+                        Timeout::DEFAULT,
+                    )
+                }
+                _ => unreachable!("block transfers are data-in or data-out"),
+            };
+
+            let result = result.map_err(|e| {
+                let failed = e
+                    .sense()
+                    .filter(|s| !s.is_deferred())
+                    .and_then(|s| s.information())
+                    .filter(|info| (start..=start + (blocks as u64 - 1)).contains(info))
+                    .unwrap_or(start);
+                fail(failed, done, e)
+            })?;
+
+            if result.resid != 0 {
+                let actual = n.saturating_sub(result.resid);
+                let good = (actual / bs) as u64;
+                return Err(fail(
+                    start + good,
+                    done + good,
+                    ScsiError::ShortTransfer {
+                        expected: n,
+                        actual,
+                    },
+                ));
+            }
+
+            total.retries += result.retries;
+            done += blocks as u64;
+            off += n;
+        }
+
+        Ok(total)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Command, Emulator, Fault, FaultInjector, FaultRule, SenseKey};
+
+    const BS: u32 = 512;
+    const BLOCKS: u64 = 256;
+
+    /// A device over an emulator that takes at most 8 blocks per command,
+    /// with its transfer limits already queried.
+    fn device() -> Device<FaultInjector<Emulator>> {
+        let emu = Emulator::new(BS, BLOCKS).with_max_xfer(8 * BS as usize);
+        let mut dev = Device::new(FaultInjector::new(emu));
+        dev.transfer_limits().unwrap();
+        dev
+    }
+
+    fn pattern(blocks: usize) -> Vec<u8> {
+        (0..blocks * BS as usize).map(|i| (i % 253) as u8).collect()
+    }
+
+    /// The number of commands sent through the device's transport.
+    fn commands(dev: &Device<FaultInjector<Emulator>>) -> u64 {
+        dev.transport().commands()
+    }
+
+    /// An emulator reporting `max_xfer` as its transport limit instead of
+    /// its own (which it still reports in the block limits page), or
+    /// failing to report one at all.
+    struct MaxXfer(Emulator, Option<usize>);
+
+    impl Transport for MaxXfer {
+        fn submit(&mut self, cmd: &mut Command<'_>) -> Result<CommandResult, ScsiError> {
+            self.0.submit(cmd)
+        }
+
+        fn max_xfer(&mut self) -> Result<usize, ScsiError> {
+            match self.1 {
+                Some(max) => Ok(max),
+                None => Err(ScsiError::Os(std::io::ErrorKind::Unsupported.into())),
+            }
+        }
+    }
+
+    #[test]
+    fn chunking() {
+        let mut dev = device();
+        let limits = dev.transfer_limits().unwrap();
+        assert_eq!(
+            limits,
+            TransferLimits {
+                block_size: BS,
+                max_blocks: 8,
+            }
+        );
+        assert_eq!(limits.max_bytes(), 4096);
+
+        let data = pattern(20);
+        let before = commands(&dev);
+        dev.write_blocks(10, &data).unwrap();
+        assert_eq!(commands(&dev) - before, 3);
+        let start = 10 * BS as usize;
+        assert_eq!(
+            &dev.transport().inner().data()[start..start + data.len()],
+            &data[..]
+        );
+
+        let mut buf = vec![0u8; data.len()];
+        let before = commands(&dev);
+        dev.read_blocks(10, &mut buf).unwrap();
+        assert_eq!(commands(&dev) - before, 3);
+        assert_eq!(buf, data);
+
+        // An empty transfer sends nothing.
+        let before = commands(&dev);
+        dev.read_blocks(0, &mut []).unwrap();
+        assert_eq!(commands(&dev), before);
+    }
+
+    #[test]
+    fn device_limit() {
+        // The block limits page caps the transfer below the transport's.
+        let emu = Emulator::new(BS, BLOCKS).with_max_xfer(8 * BS as usize);
+        let mut dev = Device::new(MaxXfer(emu, Some(1 << 20)));
+        assert_eq!(dev.transfer_limits().unwrap().max_blocks, 8);
+
+        // Without the page, only the transport's limit applies.
+        let fault = Fault::check_condition(SenseKey::IllegalRequest, 0x24, 0);
+        let mut dev = Device::new(
+            FaultInjector::new(Emulator::new(BS, BLOCKS).with_max_xfer(16 * BS as usize))
+                .with_rule(FaultRule::new(fault).opcode(0x12)),
+        );
+        assert_eq!(dev.transfer_limits().unwrap().max_blocks, 16);
+    }
+
+    #[test]
+    fn fallback_max_xfer() {
+        let mut dev = Device::new(MaxXfer(Emulator::new(BS, BLOCKS), None));
+        let limits = dev.transfer_limits().unwrap();
+        assert_eq!(limits.max_bytes(), FALLBACK_MAX_XFER);
+
+        let data = pattern(200);
+        dev.write_blocks(0, &data).unwrap();
+        let mut buf = vec![0u8; data.len()];
+        dev.read_blocks(0, &mut buf).unwrap();
+        assert_eq!(buf, data);
+    }
+
+    #[test]
+    fn short_transfer_stops() {
+        let mut dev = device();
+        // Short the second command of the transfer by 1000 bytes: six of
+        // its eight blocks make it.
+        let nth = commands(&dev) + 2;
+        dev.transport_mut()
+            .add_rule(FaultRule::new(Fault::ShortTransfer { resid: 1000 }).nth(nth));
+
+        let mut buf = vec![0u8; 20 * BS as usize];
+        let e = dev.read_blocks(100, &mut buf).unwrap_err();
+        assert_eq!(e.lba, 114);
+        assert_eq!(e.blocks_done, 14);
+        assert!(matches!(
+            e.error,
+            ScsiError::ShortTransfer {
+                expected: 4096,
+                actual: 3096
+            }
+        ));
+        // The third command was never sent.
+        assert_eq!(commands(&dev), nth);
+        assert_eq!(
+            std::io::Error::from(e).kind(),
+            std::io::ErrorKind::UnexpectedEof
+        );
+    }
+
+    #[test]
+    fn failed_command() {
+        let mut dev = device();
+        let nth = commands(&dev) + 3;
+        dev.transport_mut().add_rule(
+            FaultRule::new(Fault::check_condition(SenseKey::MediumError, 0x11, 0)).nth(nth),
+        );
+
+        let e = dev.write_blocks(0, &pattern(24)).unwrap_err();
+        assert_eq!((e.lba, e.blocks_done), (16, 16));
+        assert_eq!(e.error.sense().unwrap().key(), SenseKey::MediumError);
+
+        // Past the end of the medium the emulator fails the first command.
+        let mut buf = vec![0u8; 4 * BS as usize];
+        let e = dev.read_blocks(BLOCKS - 2, &mut buf).unwrap_err();
+        assert_eq!((e.lba, e.blocks_done), (BLOCKS - 2, 0));
+        assert_eq!(e.error.sense().unwrap().asc(), 0x21);
+    }
+
+    #[test]
+    fn invalid_transfers() {
+        let mut dev = device();
+        let before = commands(&dev);
+
+        let e = dev.read_blocks(0, &mut [0; 100]).unwrap_err();
+        assert!(matches!(e.error, ScsiError::InvalidCommand(_)));
+
+        // The last block would lie past LBA u64::MAX.
+        let e = dev.write_blocks(u64::MAX - 1, &pattern(3)).unwrap_err();
+        assert!(matches!(e.error, ScsiError::InvalidCommand(_)));
+        assert_eq!((e.lba, e.blocks_done), (u64::MAX - 1, 0));
+        let e = dev.read_blocks(u64::MAX, &mut [0; 1024]).unwrap_err();
+        assert!(matches!(e.error, ScsiError::InvalidCommand(_)));
+        assert_eq!(commands(&dev), before);
+
+        // The very last LBA is addressable, though not on this device.
+        let e = dev.read_blocks(u64::MAX, &mut [0; 512]).unwrap_err();
+        assert!(matches!(e.error, ScsiError::Status { .. }));
+        assert_eq!(e.lba, u64::MAX);
+    }
+}
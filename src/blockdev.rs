@@ -0,0 +1,341 @@
+/*
+ * Copyright 2025 Jason King
+ */
+
+use crate::cdb::SynchronizeCache;
+use crate::{
+    AlignedBuf, BlockIoError, CommandResult, Device, NativeTransport, ScsiError, Timeout,
+    TransferLimits, Transport,
+};
+use std::io::{self, Read, Seek, SeekFrom, Write};
+
+/// A byte addressed view of a block device, implementing [`Read`],
+/// [`Write`] and [`Seek`].
+///
+/// Byte offsets are translated to LBAs; a transfer that does not start or
+/// end on a block boundary reads the partial block (and, for a write,
+/// writes it back with the new bytes merged in). [`flush`](Write::flush)
+/// issues SYNCHRONIZE CACHE.
+///
+/// Reads and writes transfer at most one command's worth of data (see
+/// [`TransferLimits`]) per call; use [`Read::read_exact`] and
+/// [`Write::write_all`] to transfer more.
+#[derive(Debug)]
+pub struct BlockDevice<T: Transport = NativeTransport> {
+    dev: Device<T>,
+    limits: TransferLimits,
+    size: u64,
+    pos: u64,
+    scratch: AlignedBuf,
+}
+
+impl<T: Transport> BlockDevice<T> {
+    /// Wrap `dev`, querying its capacity and transfer limits.
+    pub fn new(mut dev: Device<T>) -> Result<Self, ScsiError> {
+        let size = dev.capacity()?.bytes().min(u64::MAX as u128) as u64;
+        let limits = dev.transfer_limits()?;
+        let scratch = dev.alloc_blocks(1)?;
+        Ok(Self {
+            dev,
+            limits,
+            size,
+            pos: 0,
+            scratch,
+        })
+    }
+
+    /// The size of the device in bytes.
+    pub fn size(&self) -> u64 {
+        self.size
+    }
+
+    pub fn block_size(&self) -> u32 {
+        self.limits.block_size
+    }
+
+    pub fn device(&self) -> &Device<T> {
+        &self.dev
+    }
+
+    pub fn device_mut(&mut self) -> &mut Device<T> {
+        &mut self.dev
+    }
+
+    pub fn into_inner(mut self) -> Device<T> {
+        self.dev.release_buf(self.scratch);
+        self.dev
+    }
+
+    fn bs(&self) -> u64 {
+        self.limits.block_size as u64
+    }
+
+    /// Read the block at `lba` into the scratch buffer, failing unless all
+    /// of it was read.
+    fn read_scratch_block(&mut self, lba: u64) -> io::Result<()> {
+        let (n, bs) = (self.scratch.len(), self.bs());
+        let result = self.dev.read_blocks(lba, &mut self.scratch);
+        match transferred(result, n, bs, io::ErrorKind::UnexpectedEof)? {
+            done if done == n => Ok(()),
+            _ => Err(io::ErrorKind::UnexpectedEof.into()),
+        }
+    }
+}
+
+/// The number of bytes of an `n` byte block transfer that completed, given
+/// its outcome: a short transfer counts the whole blocks done before it.
+/// Fails with `none` if nothing was transferred.
+fn transferred(
+    result: Result<CommandResult, BlockIoError>,
+    n: usize,
+    bs: u64,
+    none: io::ErrorKind,
+) -> io::Result<usize> {
+    let done = match result {
+        Ok(r) => n.saturating_sub(r.resid),
+        Err(BlockIoError {
+            blocks_done,
+            error: ScsiError::ShortTransfer { .. },
+            ..
+        }) => (blocks_done * bs) as usize,
+        Err(e) => return Err(e.into()),
+    };
+    match done {
+        0 => Err(none.into()),
+        done => Ok(done),
+    }
+}
+
+impl<T: Transport> Read for BlockDevice<T> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let bs = self.bs();
+        let len = (buf.len() as u64).min(self.size.saturating_sub(self.pos));
+        if len == 0 {
+            return Ok(0);
+        }
+
+        let lba = self.pos / bs;
+        let off = self.pos % bs;
+        let n = if off == 0 && len >= bs {
+            let n = (len - len % bs).min(self.limits.max_bytes() as u64) as usize;
+            let result = self.dev.read_blocks(lba, &mut buf[..n]);
+            transferred(result, n, bs, io::ErrorKind::UnexpectedEof)?
+        } else {
+            let n = (bs - off).min(len) as usize;
+            self.read_scratch_block(lba)?;
+            buf[..n].copy_from_slice(&self.scratch[off as usize..off as usize + n]);
+            n
+        };
+
+        self.pos += n as u64;
+        Ok(n)
+    }
+}
+
+impl<T: Transport> Write for BlockDevice<T> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let bs = self.bs();
+        let len = (buf.len() as u64).min(self.size.saturating_sub(self.pos));
+        if len == 0 {
+            return match buf.is_empty() {
+                true => Ok(0),
+                false => Err(io::ErrorKind::StorageFull.into()),
+            };
+        }
+
+        let lba = self.pos / bs;
+        let off = self.pos % bs;
+        let n = if off == 0 && len >= bs {
+            let n = (len - len % bs).min(self.limits.max_bytes() as u64) as usize;
+            let result = self.dev.write_blocks(lba, &buf[..n]);
+            transferred(result, n, bs, io::ErrorKind::WriteZero)?
+        } else {
+            let n = (bs - off).min(len) as usize;
+            self.read_scratch_block(lba)?;
+            self.scratch[off as usize..off as usize + n].copy_from_slice(&buf[..n]);
+            let result = self.dev.write_blocks(lba, &self.scratch);
+            // The block is written whole or not at all.
+            match transferred(result, self.scratch.len(), bs, io::ErrorKind::WriteZero)? {
+                done if done == self.scratch.len() => n,
+                _ => return Err(io::ErrorKind::WriteZero.into()),
+            }
+        };
+
+        self.pos += n as u64;
+        Ok(n)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.dev
+            .execute(&SynchronizeCache::all(), &mut [], Timeout::DEFAULT)?;
+        Ok(())
+    }
+}
+
+impl<T: Transport> Seek for BlockDevice<T> {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        let new = match pos {
+            SeekFrom::Start(off) => Some(off),
+            SeekFrom::End(off) => self.size.checked_add_signed(off),
+            SeekFrom::Current(off) => self.pos.checked_add_signed(off),
+        };
+
+        match new {
+            Some(new) => {
+                self.pos = new;
+                Ok(new)
+            }
+            None => Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "invalid seek to a negative or overflowing position",
+            )),
+        }
+    }
+
+    fn stream_position(&mut self) -> io::Result<u64> {
+        Ok(self.pos)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Emulator, Fault, FaultInjector, FaultRule, SenseKey};
+
+    const BS: usize = 512;
+    const BLOCKS: u64 = 64;
+    const SIZE: u64 = BS as u64 * BLOCKS;
+
+    fn block_device() -> BlockDevice<FaultInjector<Emulator>> {
+        let emulator = Emulator::new(BS as u32, BLOCKS);
+        BlockDevice::new(Device::new(FaultInjector::new(emulator))).unwrap()
+    }
+
+    fn injector(dev: &mut BlockDevice<FaultInjector<Emulator>>) -> &mut FaultInjector<Emulator> {
+        dev.device_mut().transport_mut()
+    }
+
+    #[test]
+    fn geometry() {
+        let dev = block_device();
+        assert_eq!(dev.size(), SIZE);
+        assert_eq!(dev.block_size(), BS as u32);
+    }
+
+    #[test]
+    fn unaligned_write() {
+        let mut dev = block_device();
+        let data: Vec<u8> = (1..=40).collect();
+        dev.seek(SeekFrom::Start(BS as u64 - 10)).unwrap();
+        dev.write_all(&data).unwrap();
+        assert_eq!(dev.stream_position().unwrap(), BS as u64 + 30);
+
+        // Only the bytes written changed.
+        let mut blocks = vec![0xa5; 3 * BS];
+        dev.device_mut().read_blocks(0, &mut blocks).unwrap();
+        let mut expected = vec![0u8; 3 * BS];
+        expected[BS - 10..BS + 30].copy_from_slice(&data);
+        assert_eq!(blocks, expected);
+
+        let mut buf = [0u8; 60];
+        dev.seek(SeekFrom::Current(-50)).unwrap();
+        dev.read_exact(&mut buf).unwrap();
+        assert_eq!(buf, expected[BS - 20..BS + 40]);
+    }
+
+    #[test]
+    fn aligned_transfers() {
+        let mut dev = block_device();
+        let data: Vec<u8> = (0..4 * BS).map(|i| (i % 253) as u8).collect();
+        dev.seek(SeekFrom::Start(BS as u64)).unwrap();
+        assert_eq!(dev.write(&data).unwrap(), data.len());
+
+        let mut buf = vec![0u8; data.len()];
+        dev.seek(SeekFrom::Start(BS as u64)).unwrap();
+        assert_eq!(dev.read(&mut buf).unwrap(), data.len());
+        assert_eq!(buf, data);
+    }
+
+    #[test]
+    fn seek() {
+        let mut dev = block_device();
+        assert_eq!(dev.seek(SeekFrom::End(-512)).unwrap(), SIZE - 512);
+        assert_eq!(dev.seek(SeekFrom::Current(12)).unwrap(), SIZE - 500);
+        assert!(dev.seek(SeekFrom::Current(-(SIZE as i64))).is_err());
+        assert!(dev.seek(SeekFrom::End(i64::MIN)).is_err());
+        // A failed seek leaves the position alone.
+        assert_eq!(dev.stream_position().unwrap(), SIZE - 500);
+
+        // Past the end nothing can be read or written.
+        assert_eq!(dev.seek(SeekFrom::End(100)).unwrap(), SIZE + 100);
+        assert_eq!(dev.read(&mut [0; 512]).unwrap(), 0);
+        let e = dev.write(&[0; 512]).unwrap_err();
+        assert_eq!(e.kind(), io::ErrorKind::StorageFull);
+        assert_eq!(dev.write(&[]).unwrap(), 0);
+        assert_eq!(dev.stream_position().unwrap(), SIZE + 100);
+    }
+
+    #[test]
+    fn end_of_medium() {
+        let mut dev = block_device();
+        let mut buf = vec![0u8; 4 * BS];
+
+        // Unaligned and aligned reads stop at the end of the medium.
+        dev.seek(SeekFrom::End(-100)).unwrap();
+        assert_eq!(dev.read(&mut buf).unwrap(), 100);
+        assert_eq!(dev.read(&mut buf).unwrap(), 0);
+        dev.seek(SeekFrom::End(-2 * BS as i64)).unwrap();
+        assert_eq!(dev.read(&mut buf).unwrap(), 2 * BS);
+        assert_eq!(dev.stream_position().unwrap(), SIZE);
+        dev.seek(SeekFrom::End(-2 * BS as i64)).unwrap();
+        let e = dev.read_exact(&mut buf).unwrap_err();
+        assert_eq!(e.kind(), io::ErrorKind::UnexpectedEof);
+
+        // So do writes.
+        dev.seek(SeekFrom::End(-(BS as i64) - 10)).unwrap();
+        assert_eq!(dev.write(&buf).unwrap(), 10);
+        assert_eq!(dev.write(&buf).unwrap(), BS);
+        let e = dev.write(&buf).unwrap_err();
+        assert_eq!(e.kind(), io::ErrorKind::StorageFull);
+    }
+
+    #[test]
+    fn short_transfer() {
+        let mut dev = block_device();
+        let mut buf = vec![0u8; 4 * BS];
+
+        // Only whole blocks are counted.
+        for (resid, expected) in [(BS, 3 * BS), (100, 3 * BS), (4 * BS - 1, 0)] {
+            let rule = FaultRule::new(Fault::ShortTransfer { resid }).times(1);
+            injector(&mut dev).add_rule(rule);
+            dev.seek(SeekFrom::Start(0)).unwrap();
+            match expected {
+                0 => {
+                    let e = dev.read(&mut buf).unwrap_err();
+                    assert_eq!(e.kind(), io::ErrorKind::UnexpectedEof);
+                }
+                n => assert_eq!(dev.read(&mut buf).unwrap(), n, "resid {resid}"),
+            }
+            assert_eq!(dev.stream_position().unwrap(), expected as u64);
+        }
+
+        let rule = FaultRule::new(Fault::ShortTransfer { resid: BS }).times(1);
+        injector(&mut dev).add_rule(rule);
+        dev.seek(SeekFrom::Start(0)).unwrap();
+        assert_eq!(dev.write(&buf).unwrap(), 3 * BS);
+    }
+
+    #[test]
+    fn flush() {
+        let mut dev = block_device();
+        let commands = injector(&mut dev).commands();
+        dev.flush().unwrap();
+        assert_eq!(injector(&mut dev).commands(), commands + 1);
+
+        // The command is SYNCHRONIZE CACHE, and its failure is reported.
+        let fault = Fault::check_condition(SenseKey::MediumError, 0x0c, 0x00);
+        injector(&mut dev).add_rule(FaultRule::new(fault).opcode(0x35));
+        assert!(dev.flush().is_err());
+        assert_eq!(injector(&mut dev).injected(), 1);
+    }
+}
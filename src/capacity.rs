@@ -0,0 +1,176 @@
+/*
+ * Copyright 2025 Jason King
+ */
+
+/// The capacity and block geometry of a logical unit, as reported by
+/// READ CAPACITY.
+///
+/// When only READ CAPACITY(10) data is available, the fields that exist
+/// only in the READ CAPACITY(16) data are zero (or `None`).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Capacity {
+    /// The LBA of the last logical block.
+    pub last_lba: u64,
+    /// The logical block length in bytes.
+    pub block_size: u32,
+    /// The protection type (1, 2 or 3), if protection is enabled.
+    pub protection_type: Option<u8>,
+    /// Protection information intervals per logical block, as a power of
+    /// two.
+    pub p_i_exponent: u8,
+    /// Logical blocks per physical block, as a power of two.
+    pub physical_block_exponent: u8,
+    /// Logical block provisioning management enabled (the device is thin
+    /// provisioned).
+    pub lbpme: bool,
+    /// Unmapped blocks read back as zeros.
+    pub lbprz: bool,
+    /// The first LBA aligned to a physical block boundary.
+    pub lowest_aligned_lba: u16,
+}
+
+impl Capacity {
+    /// Decode READ CAPACITY(10) parameter data.
+    pub fn parse10(buf: &[u8]) -> Option<Self> {
+        if buf.len() < 8 {
+            return None;
+        }
+
+        Some(Self {
+            last_lba: be32(&buf[0..4]) as u64,
+            block_size: be32(&buf[4..8]),
+            protection_type: None,
+            p_i_exponent: 0,
+            physical_block_exponent: 0,
+            lbpme: false,
+            lbprz: false,
+            lowest_aligned_lba: 0,
+        })
+    }
+
+    /// Decode READ CAPACITY(16) parameter data. Only the first 12 bytes are
+    /// required; fields beyond the end of a shorter buffer are zero.
+    pub fn parse16(buf: &[u8]) -> Option<Self> {
+        if buf.len() < 12 {
+            return None;
+        }
+
+        let byte = |i: usize| buf.get(i).copied().unwrap_or(0);
+        let mut lba = [0u8; 8];
+        lba.copy_from_slice(&buf[0..8]);
+
+        Some(Self {
+            last_lba: u64::from_be_bytes(lba),
+            block_size: be32(&buf[8..12]),
+            protection_type: match byte(12) & 0x01 {
+                0 => None,
+                _ => Some(((byte(12) >> 1) & 0x07) + 1),
+            },
+            p_i_exponent: byte(13) >> 4,
+            physical_block_exponent: byte(13) & 0x0f,
+            lbpme: byte(14) & 0x80 != 0,
+            lbprz: byte(14) & 0x40 != 0,
+            lowest_aligned_lba: u16::from_be_bytes([byte(14) & 0x3f, byte(15)]),
+        })
+    }
+
+    /// The number of logical blocks, saturating at `u64::MAX` for a last
+    /// LBA of `u64::MAX`.
+    pub fn blocks(&self) -> u64 {
+        self.last_lba.saturating_add(1)
+    }
+
+    /// The capacity in bytes.
+    pub fn bytes(&self) -> u128 {
+        (self.last_lba as u128 + 1) * self.block_size as u128
+    }
+
+    /// The physical block length in bytes.
+    pub fn physical_block_size(&self) -> u64 {
+        (self.block_size as u64) << self.physical_block_exponent
+    }
+}
+
+fn be32(b: &[u8]) -> u32 {
+    u32::from_be_bytes([b[0], b[1], b[2], b[3]])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse10() {
+        let cap = Capacity::parse10(&[0x00, 0x1d, 0x1c, 0x5f, 0x00, 0x00, 0x02, 0x00]).unwrap();
+        assert_eq!(cap.last_lba, 0x1d_1c5f);
+        assert_eq!(cap.block_size, 512);
+        assert_eq!(cap.blocks(), 0x1d_1c60);
+        assert_eq!(cap.bytes(), 0x1d_1c60 * 512);
+        assert_eq!(cap.protection_type, None);
+        assert_eq!(cap.physical_block_size(), 512);
+        assert!(!cap.lbpme && !cap.lbprz);
+
+        assert_eq!(Capacity::parse10(&[0; 7]), None);
+    }
+
+    #[test]
+    fn parse16() {
+        let mut buf = [0u8; 32];
+        buf[0..8].copy_from_slice(&0x1_d1c0_beafu64.to_be_bytes());
+        buf[8..12].copy_from_slice(&512u32.to_be_bytes());
+        // P_TYPE 1 (type 2 protection) and PROT_EN.
+        buf[12] = 0x03;
+        // P_I_EXPONENT 1, 2^3 logical blocks per physical block.
+        buf[13] = 0x13;
+        // LBPME, LBPRZ and a lowest aligned LBA of 0x107.
+        buf[14] = 0xc1;
+        buf[15] = 0x07;
+        let cap = Capacity::parse16(&buf).unwrap();
+        assert_eq!(
+            cap,
+            Capacity {
+                last_lba: 0x1_d1c0_beaf,
+                block_size: 512,
+                protection_type: Some(2),
+                p_i_exponent: 1,
+                physical_block_exponent: 3,
+                lbpme: true,
+                lbprz: true,
+                lowest_aligned_lba: 0x107,
+            }
+        );
+        assert_eq!(cap.physical_block_size(), 4096);
+        assert_eq!(cap.blocks(), 0x1_d1c0_beb0);
+
+        // Protection types 1 and 3; and none when PROT_EN is clear.
+        let mut b = buf;
+        b[12] = 0x01;
+        assert_eq!(Capacity::parse16(&b).unwrap().protection_type, Some(1));
+        b[12] = 0x05;
+        assert_eq!(Capacity::parse16(&b).unwrap().protection_type, Some(3));
+        b[12] = 0x04;
+        assert_eq!(Capacity::parse16(&b).unwrap().protection_type, None);
+
+        // Only the LBA and block size are required.
+        let cap = Capacity::parse16(&buf[..12]).unwrap();
+        assert_eq!((cap.last_lba, cap.block_size), (0x1_d1c0_beaf, 512));
+        assert_eq!(cap.protection_type, None);
+        assert_eq!((cap.lbpme, cap.lowest_aligned_lba), (false, 0));
+        let cap = Capacity::parse16(&buf[..14]).unwrap();
+        assert_eq!(cap.physical_block_exponent, 3);
+        assert!(!cap.lbpme);
+        assert_eq!(Capacity::parse16(&buf[..11]), None);
+    }
+
+    #[test]
+    fn largest() {
+        let mut buf = [0xffu8; 32];
+        buf[8..12].copy_from_slice(&4096u32.to_be_bytes());
+        let cap = Capacity::parse16(&buf).unwrap();
+        assert_eq!(cap.last_lba, u64::MAX);
+        assert_eq!(cap.blocks(), u64::MAX);
+        assert_eq!(cap.bytes(), (u64::MAX as u128 + 1) * 4096);
+        assert_eq!(cap.physical_block_exponent, 15);
+        assert_eq!(cap.physical_block_size(), 4096 << 15);
+    }
+}
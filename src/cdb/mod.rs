@@ -0,0 +1,364 @@
+/*
+ * Copyright 2025 Jason King
+ */
+
+//! Typed builders for command descriptor blocks.
+//!
+//! Each builder encodes to a correctly formatted CDB via [`Cdb::to_bytes`],
+//! knows the direction and expected length of its data transfer, and can be
+//! decoded back from raw CDB bytes (e.g. by an emulator or tracer).
+
+use crate::{DataDirection, ScsiError};
+use std::fmt;
+use std::ops::Deref;
+
+mod sbc;
+mod spc;
+
+pub use sbc::{
+    CdbSize, CompareAndWrite, GetLbaStatus, PreFetch, ProtectionTags, Read, ReadCapacity10,
+    ReadCapacity16, SbcCdb, SynchronizeCache, Unmap, UnmapDescriptor, Verify, Write,
+    WriteAndVerify, WriteSame,
+};
+pub use spc::{
+    Inquiry, LogSelect, LogSense, ModeSelect10, ModeSelect6, ModeSense10, ModeSense6, ReadBuffer,
+    ReportLuns, ReportSupportedOpcodes, ReportSupportedTmfs, RequestSense, SpcCdb, TestUnitReady,
+    WriteBuffer,
+};
+
+/// The longest CDB supported (a 32-byte variable length CDB).
+pub const MAX_CDB_LEN: usize = 32;
+
+/// The CDB length implied by the group code (top three bits) of `opcode`,
+/// or `None` for the groups without a fixed length: group 3 (variable
+/// length and extended CDBs) and the vendor specific groups 6 and 7.
+pub fn group_len(opcode: u8) -> Option<usize> {
+    match opcode >> 5 {
+        0 => Some(6),
+        1 | 2 => Some(10),
+        4 => Some(16),
+        5 => Some(12),
+        _ => None,
+    }
+}
+
+/// Check that the length of `cdb` agrees with its opcode's group code.
+///
+/// A variable length CDB (opcode 0x7f) must be 8 bytes plus its additional
+/// CDB length; other CDBs without a fixed length may be up to 255 bytes,
+/// the most a transport can express.
+pub fn validate(cdb: &[u8]) -> Result<(), ScsiError> {
+    let Some(&opcode) = cdb.first() else {
+        return Err(ScsiError::InvalidCommand("empty CDB"));
+    };
+
+    let ok = match (opcode, group_len(opcode)) {
+        (_, Some(len)) => cdb.len() == len,
+        (0x7f, None) => cdb.len() >= 8 && cdb.len() == 8 + cdb[7] as usize,
+        (_, None) => cdb.len() <= u8::MAX as usize,
+    };
+    match ok {
+        true => Ok(()),
+        false => Err(ScsiError::InvalidCommand(
+            "CDB length does not match its opcode's group code",
+        )),
+    }
+}
+
+/// The name of the command `cdb` encodes, taking the service action into
+/// account where the opcode has several.
+pub fn opcode_name(cdb: &[u8]) -> Option<&'static str> {
+    let opcode = *cdb.first()?;
+    let sa = cdb.get(1).map(|b| b & 0x1f);
+    let name = match opcode {
+        0x00 => "TEST UNIT READY",
+        0x03 => "REQUEST SENSE",
+        0x04 => "FORMAT UNIT",
+        0x08 => "READ(6)",
+        0x0a => "WRITE(6)",
+        0x12 => "INQUIRY",
+        0x15 => "MODE SELECT(6)",
+        0x1a => "MODE SENSE(6)",
+        0x1b => "START STOP UNIT",
+        0x1d => "SEND DIAGNOSTIC",
+        0x1e => "PREVENT ALLOW MEDIUM REMOVAL",
+        0x25 => "READ CAPACITY(10)",
+        0x28 => "READ(10)",
+        0x2a => "WRITE(10)",
+        0x2e => "WRITE AND VERIFY(10)",
+        0x2f => "VERIFY(10)",
+        0x34 => "PRE-FETCH(10)",
+        0x35 => "SYNCHRONIZE CACHE(10)",
+        0x3b => "WRITE BUFFER",
+        0x3c => "READ BUFFER",
+        0x41 => "WRITE SAME(10)",
+        0x42 => "UNMAP",
+        0x48 => "SANITIZE",
+        0x4c => "LOG SELECT",
+        0x4d => "LOG SENSE",
+        0x53 => "XDWRITEREAD(10)",
+        0x55 => "MODE SELECT(10)",
+        0x5a => "MODE SENSE(10)",
+        0x5e => "PERSISTENT RESERVE IN",
+        0x5f => "PERSISTENT RESERVE OUT",
+        0x7f => match cdb.get(8..10).map(be16) {
+            Some(0x0009) => "READ(32)",
+            Some(0x000a) => "VERIFY(32)",
+            Some(0x000b) => "WRITE(32)",
+            Some(0x000c) => "WRITE AND VERIFY(32)",
+            Some(0x000d) => "WRITE SAME(32)",
+            Some(0x0007) => "XDWRITEREAD(32)",
+            _ => "VARIABLE LENGTH",
+        },
+        0x83 => "THIRD-PARTY COPY OUT",
+        0x84 => "THIRD-PARTY COPY IN",
+        0x85 => "ATA PASS-THROUGH(16)",
+        0x88 => "READ(16)",
+        0x89 => "COMPARE AND WRITE",
+        0x8a => "WRITE(16)",
+        0x8e => "WRITE AND VERIFY(16)",
+        0x8f => "VERIFY(16)",
+        0x90 => "PRE-FETCH(16)",
+        0x91 => "SYNCHRONIZE CACHE(16)",
+        0x93 => "WRITE SAME(16)",
+        0x9e => match sa {
+            Some(0x10) => "READ CAPACITY(16)",
+            Some(0x12) => "GET LBA STATUS",
+            _ => "SERVICE ACTION IN(16)",
+        },
+        0xa0 => "REPORT LUNS",
+        0xa1 => "ATA PASS-THROUGH(12)",
+        0xa3 => match sa {
+            Some(0x0c) => "REPORT SUPPORTED OPERATION CODES",
+            Some(0x0d) => "REPORT SUPPORTED TASK MANAGEMENT FUNCTIONS",
+            _ => "MAINTENANCE IN",
+        },
+        0xa4 => "MAINTENANCE OUT",
+        0xa8 => "READ(12)",
+        0xaa => "WRITE(12)",
+        0xae => "WRITE AND VERIFY(12)",
+        0xaf => "VERIFY(12)",
+        _ => return None,
+    };
+    Some(name)
+}
+
+/// An encoded CDB.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub struct CdbBuf {
+    buf: [u8; MAX_CDB_LEN],
+    len: u8,
+}
+
+impl CdbBuf {
+    fn new(len: usize) -> Self {
+        assert!(len <= MAX_CDB_LEN);
+        Self {
+            buf: [0; MAX_CDB_LEN],
+            len: len as u8,
+        }
+    }
+
+    fn with_opcode(len: usize, opcode: u8) -> Self {
+        let mut cdb = Self::new(len);
+        cdb.buf[0] = opcode;
+        cdb
+    }
+
+    fn set(&mut self, off: usize, bytes: &[u8]) {
+        self.buf[off..off + bytes.len()].copy_from_slice(bytes);
+    }
+}
+
+impl Deref for CdbBuf {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        &self.buf[..self.len as usize]
+    }
+}
+
+impl AsRef<[u8]> for CdbBuf {
+    fn as_ref(&self) -> &[u8] {
+        self
+    }
+}
+
+impl fmt::Debug for CdbBuf {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:02x?}", &**self)
+    }
+}
+
+/// A command that can be encoded as a CDB.
+pub trait Cdb {
+    fn to_bytes(&self) -> CdbBuf;
+
+    fn direction(&self) -> DataDirection;
+
+    /// The number of bytes the command is expected to transfer (for data-in
+    /// commands, the allocation length).
+    fn transfer_length(&self) -> usize;
+}
+
+/// The PC field of MODE SENSE.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum PageControl {
+    #[default]
+    Current,
+    Changeable,
+    Default,
+    Saved,
+}
+
+impl From<u8> for PageControl {
+    fn from(val: u8) -> Self {
+        match val & 0x03 {
+            0 => PageControl::Current,
+            1 => PageControl::Changeable,
+            2 => PageControl::Default,
+            _ => PageControl::Saved,
+        }
+    }
+}
+
+/// Check `cdb` is at least `len` bytes and starts with `opcode` (and, if
+/// given, has `sa` in the service action field of byte 1).
+fn expect(cdb: &[u8], len: usize, opcode: u8, sa: Option<u8>) -> Option<()> {
+    if cdb.len() < len || cdb[0] != opcode {
+        return None;
+    }
+    match sa {
+        Some(sa) if cdb[1] & 0x1f != sa => None,
+        _ => Some(()),
+    }
+}
+
+fn be16(b: &[u8]) -> u16 {
+    u16::from_be_bytes([b[0], b[1]])
+}
+
+fn be24(b: &[u8]) -> u32 {
+    u32::from_be_bytes([0, b[0], b[1], b[2]])
+}
+
+fn be32(b: &[u8]) -> u32 {
+    u32::from_be_bytes([b[0], b[1], b[2], b[3]])
+}
+
+fn be64(b: &[u8]) -> u64 {
+    let mut v = [0u8; 8];
+    v.copy_from_slice(&b[..8]);
+    u64::from_be_bytes(v)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn group_lengths() {
+        let lens: Vec<_> = (0..8).map(|group| group_len(group << 5)).collect();
+        assert_eq!(
+            lens,
+            [
+                Some(6),
+                Some(10),
+                Some(10),
+                None,
+                Some(16),
+                Some(12),
+                None,
+                None
+            ]
+        );
+        assert_eq!(group_len(0x1f), Some(6));
+        assert_eq!(group_len(0x7e), None);
+    }
+
+    #[test]
+    fn validate_lengths() {
+        for (opcode, len) in [(0x00, 6), (0x28, 10), (0x5a, 10), (0x88, 16), (0xa8, 12)] {
+            let mut cdb = [0u8; 17];
+            cdb[0] = opcode;
+            assert!(validate(&cdb[..len]).is_ok(), "{opcode:#04x}");
+            assert!(validate(&cdb[..len - 1]).is_err(), "{opcode:#04x}");
+            assert!(validate(&cdb[..len + 1]).is_err(), "{opcode:#04x}");
+        }
+        assert!(validate(&[]).is_err());
+    }
+
+    #[test]
+    fn validate_variable_length() {
+        let mut cdb = [0u8; 40];
+        cdb[0] = 0x7f;
+        cdb[7] = 0x18;
+        assert!(validate(&cdb[..32]).is_ok());
+        assert!(validate(&cdb[..31]).is_err());
+        assert!(validate(&cdb[..33]).is_err());
+        cdb[7] = 0;
+        assert!(validate(&cdb[..8]).is_ok());
+        assert!(validate(&cdb[..7]).is_err());
+    }
+
+    #[test]
+    fn validate_reserved_groups() {
+        // Group 3 (other than 0x7f) and the vendor specific groups may be
+        // any length a transport can express.
+        for opcode in [0x7e, 0xc0, 0xff] {
+            let mut cdb = [0u8; 256];
+            cdb[0] = opcode;
+            assert!(validate(&cdb[..1]).is_ok(), "{opcode:#04x}");
+            assert!(validate(&cdb[..7]).is_ok(), "{opcode:#04x}");
+            assert!(validate(&cdb[..255]).is_ok(), "{opcode:#04x}");
+            assert!(validate(&cdb).is_err(), "{opcode:#04x}");
+        }
+    }
+
+    #[test]
+    fn opcode_names() {
+        assert_eq!(opcode_name(&[]), None);
+        assert_eq!(opcode_name(&[0x00; 6]), Some("TEST UNIT READY"));
+        assert_eq!(
+            opcode_name(&Read::new(0, 1, 512).to_bytes()),
+            Some("READ(6)")
+        );
+        let read = Read {
+            min_size: CdbSize::Sixteen,
+            ..Read::new(0, 1, 512)
+        };
+        assert_eq!(opcode_name(&read.to_bytes()), Some("READ(16)"));
+        assert_eq!(opcode_name(&[0xc0; 6]), None);
+
+        // Service actions.
+        let mut cdb = [0u8; 16];
+        cdb[0] = 0x9e;
+        cdb[1] = 0x10;
+        assert_eq!(opcode_name(&cdb), Some("READ CAPACITY(16)"));
+        cdb[1] = 0x12;
+        assert_eq!(opcode_name(&cdb), Some("GET LBA STATUS"));
+        cdb[1] = 0x1f;
+        assert_eq!(opcode_name(&cdb), Some("SERVICE ACTION IN(16)"));
+        assert_eq!(opcode_name(&[0x9e]), Some("SERVICE ACTION IN(16)"));
+
+        let mut cdb = [0u8; 12];
+        cdb[0] = 0xa3;
+        cdb[1] = 0x0c;
+        assert_eq!(opcode_name(&cdb), Some("REPORT SUPPORTED OPERATION CODES"));
+        cdb[1] = 0xea;
+        assert_eq!(opcode_name(&cdb), Some("MAINTENANCE IN"));
+
+        let mut cdb = [0u8; 32];
+        cdb[0] = 0x7f;
+        cdb[7] = 0x18;
+        for (sa, name) in [
+            (0x0007, "XDWRITEREAD(32)"),
+            (0x000b, "WRITE(32)"),
+            (0x0001, "VARIABLE LENGTH"),
+        ] {
+            cdb[8..10].copy_from_slice(&u16::to_be_bytes(sa));
+            assert_eq!(opcode_name(&cdb), Some(name));
+        }
+        assert_eq!(opcode_name(&cdb[..8]), Some("VARIABLE LENGTH"));
+    }
+}
@@ -0,0 +1,1525 @@
+/*
+ * Copyright 2025 Jason King
+ */
+
+use super::{be16, be32, be64, expect, Cdb, CdbBuf};
+use crate::DataDirection;
+
+/// The length of an encoded CDB.
+///
+/// Builders that come in several sizes take a minimum size and encode the
+/// smallest CDB at least that large which can hold their fields.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Default)]
+pub enum CdbSize {
+    #[default]
+    Six,
+    Ten,
+    Twelve,
+    Sixteen,
+    /// A variable length CDB, needed to pass expected protection tags.
+    ThirtyTwo,
+}
+
+impl CdbSize {
+    pub fn length(&self) -> usize {
+        match self {
+            CdbSize::Six => 6,
+            CdbSize::Ten => 10,
+            CdbSize::Twelve => 12,
+            CdbSize::Sixteen => 16,
+            CdbSize::ThirtyTwo => 32,
+        }
+    }
+}
+
+/// The expected protection information tags carried by the 32-byte forms
+/// of the read and write commands.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct ProtectionTags {
+    /// Expected initial logical block reference tag.
+    pub ref_tag: u32,
+    /// Expected logical block application tag.
+    pub app_tag: u16,
+    /// Logical block application tag mask.
+    pub app_tag_mask: u16,
+}
+
+const VARIABLE_LENGTH: u8 = 0x7f;
+
+/// The opcodes of a command that comes in 6, 10, 12, 16 and 32-byte forms.
+struct Opcodes {
+    six: Option<u8>,
+    ten: u8,
+    twelve: u8,
+    sixteen: u8,
+    /// Service action of the 32-byte variable length form.
+    sa32: u16,
+}
+
+/// The fields common to the READ, WRITE, VERIFY and WRITE AND VERIFY CDBs.
+/// `flags` is byte 1 of the 10, 12 and 16-byte forms (protection, DPO, FUA
+/// or BYTCHK).
+struct RwFields {
+    size: CdbSize,
+    flags: u8,
+    lba: u64,
+    blocks: u32,
+    group: u8,
+    tags: ProtectionTags,
+}
+
+impl Opcodes {
+    fn size(&self, min: CdbSize, lba: u64, blocks: u32, flags: u8, group: u8) -> CdbSize {
+        let six = self.six.is_some()
+            && flags == 0
+            && group == 0
+            && lba <= 0x1f_ffff
+            && (1..=256).contains(&blocks);
+
+        if min <= CdbSize::Six && six {
+            CdbSize::Six
+        } else if min <= CdbSize::Ten && lba <= u32::MAX as u64 && blocks <= u16::MAX as u32 {
+            CdbSize::Ten
+        } else if min <= CdbSize::Twelve && lba <= u32::MAX as u64 {
+            CdbSize::Twelve
+        } else if min <= CdbSize::Sixteen {
+            CdbSize::Sixteen
+        } else {
+            CdbSize::ThirtyTwo
+        }
+    }
+
+    fn encode(&self, f: &RwFields) -> CdbBuf {
+        let mut cdb = CdbBuf::new(f.size.length());
+        match f.size {
+            CdbSize::Six => {
+                cdb.buf[0] = self.six.unwrap_or_default();
+                cdb.set(1, &(f.lba as u32).to_be_bytes()[1..]);
+                cdb.buf[1] &= 0x1f;
+                // A transfer length of 0 means 256 blocks.
+                cdb.buf[4] = f.blocks as u8;
+            }
+            CdbSize::Ten => {
+                cdb.buf[0] = self.ten;
+                cdb.buf[1] = f.flags;
+                cdb.set(2, &(f.lba as u32).to_be_bytes());
+                cdb.buf[6] = f.group & 0x3f;
+                cdb.set(7, &(f.blocks as u16).to_be_bytes());
+            }
+            CdbSize::Twelve => {
+                cdb.buf[0] = self.twelve;
+                cdb.buf[1] = f.flags;
+                cdb.set(2, &(f.lba as u32).to_be_bytes());
+                cdb.set(6, &f.blocks.to_be_bytes());
+                cdb.buf[10] = f.group & 0x3f;
+            }
+            CdbSize::Sixteen => {
+                cdb.buf[0] = self.sixteen;
+                cdb.buf[1] = f.flags;
+                cdb.set(2, &f.lba.to_be_bytes());
+                cdb.set(10, &f.blocks.to_be_bytes());
+                cdb.buf[14] = f.group & 0x3f;
+            }
+            CdbSize::ThirtyTwo => {
+                cdb.buf[0] = VARIABLE_LENGTH;
+                cdb.buf[6] = f.group & 0x3f;
+                cdb.buf[7] = 0x18;
+                cdb.set(8, &self.sa32.to_be_bytes());
+                cdb.buf[10] = f.flags;
+                cdb.set(12, &f.lba.to_be_bytes());
+                cdb.set(20, &f.tags.ref_tag.to_be_bytes());
+                cdb.set(24, &f.tags.app_tag.to_be_bytes());
+                cdb.set(26, &f.tags.app_tag_mask.to_be_bytes());
+                cdb.set(28, &f.blocks.to_be_bytes());
+            }
+        }
+        cdb
+    }
+
+    fn decode(&self, cdb: &[u8]) -> Option<RwFields> {
+        let op = *cdb.first()?;
+        let mut f = RwFields {
+            size: CdbSize::Six,
+            flags: 0,
+            lba: 0,
+            blocks: 0,
+            group: 0,
+            tags: ProtectionTags::default(),
+        };
+
+        if Some(op) == self.six {
+            expect(cdb, 6, op, None)?;
+            f.lba = (be32(&cdb[0..4]) & 0x1f_ffff) as u64;
+            f.blocks = match cdb[4] {
+                0 => 256,
+                n => n as u32,
+            };
+        } else if op == self.ten {
+            expect(cdb, 10, op, None)?;
+            f.size = CdbSize::Ten;
+            f.flags = cdb[1];
+            f.lba = be32(&cdb[2..6]) as u64;
+            f.group = cdb[6] & 0x3f;
+            f.blocks = be16(&cdb[7..9]) as u32;
+        } else if op == self.twelve {
+            expect(cdb, 12, op, None)?;
+            f.size = CdbSize::Twelve;
+            f.flags = cdb[1];
+            f.lba = be32(&cdb[2..6]) as u64;
+            f.blocks = be32(&cdb[6..10]);
+            f.group = cdb[10] & 0x3f;
+        } else if op == self.sixteen {
+            expect(cdb, 16, op, None)?;
+            f.size = CdbSize::Sixteen;
+            f.flags = cdb[1];
+            f.lba = be64(&cdb[2..10]);
+            f.blocks = be32(&cdb[10..14]);
+            f.group = cdb[14] & 0x3f;
+        } else if op == VARIABLE_LENGTH {
+            expect(cdb, 32, op, None)?;
+            if be16(&cdb[8..10]) != self.sa32 {
+                return None;
+            }
+            f.size = CdbSize::ThirtyTwo;
+            f.group = cdb[6] & 0x3f;
+            f.flags = cdb[10];
+            f.lba = be64(&cdb[12..20]);
+            f.tags = ProtectionTags {
+                ref_tag: be32(&cdb[20..24]),
+                app_tag: be16(&cdb[24..26]),
+                app_tag_mask: be16(&cdb[26..28]),
+            };
+            f.blocks = be32(&cdb[28..32]);
+        } else {
+            return None;
+        }
+
+        Some(f)
+    }
+}
+
+const READ: Opcodes = Opcodes {
+    six: Some(0x08),
+    ten: 0x28,
+    twelve: 0xa8,
+    sixteen: 0x88,
+    sa32: 0x0009,
+};
+
+const WRITE: Opcodes = Opcodes {
+    six: Some(0x0a),
+    ten: 0x2a,
+    twelve: 0xaa,
+    sixteen: 0x8a,
+    sa32: 0x000b,
+};
+
+const VERIFY: Opcodes = Opcodes {
+    six: None,
+    ten: 0x2f,
+    twelve: 0xaf,
+    sixteen: 0x8f,
+    sa32: 0x000a,
+};
+
+const WRITE_AND_VERIFY: Opcodes = Opcodes {
+    six: None,
+    ten: 0x2e,
+    twelve: 0xae,
+    sixteen: 0x8e,
+    sa32: 0x000c,
+};
+
+fn bytes(blocks: u32, block_size: u32) -> usize {
+    blocks as usize * block_size as usize
+}
+
+/// READ(6/10/12/16/32).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Read {
+    pub lba: u64,
+    pub blocks: u32,
+    /// The logical block length, used to compute the transfer length.
+    pub block_size: u32,
+    /// Force unit access.
+    pub fua: bool,
+    /// Disable page out.
+    pub dpo: bool,
+    pub rdprotect: u8,
+    pub group: u8,
+    /// The smallest CDB to encode.
+    pub min_size: CdbSize,
+    /// Only encoded in the 32-byte form.
+    pub tags: ProtectionTags,
+}
+
+impl Read {
+    pub fn new(lba: u64, blocks: u32, block_size: u32) -> Self {
+        Self {
+            lba,
+            blocks,
+            block_size,
+            fua: false,
+            dpo: false,
+            rdprotect: 0,
+            group: 0,
+            min_size: CdbSize::Six,
+            tags: ProtectionTags::default(),
+        }
+    }
+
+    fn flags(&self) -> u8 {
+        (self.rdprotect & 0x07) << 5 | (self.dpo as u8) << 4 | (self.fua as u8) << 3
+    }
+
+    /// The size of CDB [`Cdb::to_bytes`] will produce.
+    pub fn size(&self) -> CdbSize {
+        READ.size(
+            self.min_size,
+            self.lba,
+            self.blocks,
+            self.flags(),
+            self.group,
+        )
+    }
+
+    pub fn decode(cdb: &[u8], block_size: u32) -> Option<Self> {
+        let f = READ.decode(cdb)?;
+        Some(Self {
+            lba: f.lba,
+            blocks: f.blocks,
+            block_size,
+            fua: f.flags & 0x08 != 0,
+            dpo: f.flags & 0x10 != 0,
+            rdprotect: f.flags >> 5,
+            group: f.group,
+            min_size: f.size,
+            tags: f.tags,
+        })
+    }
+}
+
+impl Cdb for Read {
+    fn to_bytes(&self) -> CdbBuf {
+        READ.encode(&RwFields {
+            size: self.size(),
+            flags: self.flags(),
+            lba: self.lba,
+            blocks: self.blocks,
+            group: self.group,
+            tags: self.tags,
+        })
+    }
+
+    fn direction(&self) -> DataDirection {
+        if self.blocks == 0 {
+            DataDirection::None
+        } else {
+            DataDirection::In
+        }
+    }
+
+    fn transfer_length(&self) -> usize {
+        bytes(self.blocks, self.block_size)
+    }
+}
+
+/// WRITE(6/10/12/16/32).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Write {
+    pub lba: u64,
+    pub blocks: u32,
+    /// The logical block length, used to compute the transfer length.
+    pub block_size: u32,
+    /// Force unit access.
+    pub fua: bool,
+    /// Disable page out.
+    pub dpo: bool,
+    pub wrprotect: u8,
+    pub group: u8,
+    /// The smallest CDB to encode.
+    pub min_size: CdbSize,
+    /// Only encoded in the 32-byte form.
+    pub tags: ProtectionTags,
+}
+
+impl Write {
+    pub fn new(lba: u64, blocks: u32, block_size: u32) -> Self {
+        Self {
+            lba,
+            blocks,
+            block_size,
+            fua: false,
+            dpo: false,
+            wrprotect: 0,
+            group: 0,
+            min_size: CdbSize::Six,
+            tags: ProtectionTags::default(),
+        }
+    }
+
+    fn flags(&self) -> u8 {
+        (self.wrprotect & 0x07) << 5 | (self.dpo as u8) << 4 | (self.fua as u8) << 3
+    }
+
+    /// The size of CDB [`Cdb::to_bytes`] will produce.
+    pub fn size(&self) -> CdbSize {
+        WRITE.size(
+            self.min_size,
+            self.lba,
+            self.blocks,
+            self.flags(),
+            self.group,
+        )
+    }
+
+    pub fn decode(cdb: &[u8], block_size: u32) -> Option<Self> {
+        let f = WRITE.decode(cdb)?;
+        Some(Self {
+            lba: f.lba,
+            blocks: f.blocks,
+            block_size,
+            fua: f.flags & 0x08 != 0,
+            dpo: f.flags & 0x10 != 0,
+            wrprotect: f.flags >> 5,
+            group: f.group,
+            min_size: f.size,
+            tags: f.tags,
+        })
+    }
+}
+
+impl Cdb for Write {
+    fn to_bytes(&self) -> CdbBuf {
+        WRITE.encode(&RwFields {
+            size: self.size(),
+            flags: self.flags(),
+            lba: self.lba,
+            blocks: self.blocks,
+            group: self.group,
+            tags: self.tags,
+        })
+    }
+
+    fn direction(&self) -> DataDirection {
+        if self.blocks == 0 {
+            DataDirection::None
+        } else {
+            DataDirection::Out
+        }
+    }
+
+    fn transfer_length(&self) -> usize {
+        bytes(self.blocks, self.block_size)
+    }
+}
+
+/// VERIFY(10/12/16/32).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Verify {
+    pub lba: u64,
+    pub blocks: u32,
+    /// The logical block length, used to compute the transfer length.
+    pub block_size: u32,
+    /// Disable page out.
+    pub dpo: bool,
+    pub vrprotect: u8,
+    /// 0: medium verification only, 1: compare against `blocks` blocks of
+    /// data-out, 3: compare each block against a single block of data-out.
+    pub bytchk: u8,
+    pub group: u8,
+    /// The smallest CDB to encode.
+    pub min_size: CdbSize,
+    /// Only encoded in the 32-byte form.
+    pub tags: ProtectionTags,
+}
+
+impl Verify {
+    pub fn new(lba: u64, blocks: u32, block_size: u32) -> Self {
+        Self {
+            lba,
+            blocks,
+            block_size,
+            dpo: false,
+            vrprotect: 0,
+            bytchk: 0,
+            group: 0,
+            min_size: CdbSize::Ten,
+            tags: ProtectionTags::default(),
+        }
+    }
+
+    fn flags(&self) -> u8 {
+        (self.vrprotect & 0x07) << 5 | (self.dpo as u8) << 4 | (self.bytchk & 0x03) << 1
+    }
+
+    /// The size of CDB [`Cdb::to_bytes`] will produce.
+    pub fn size(&self) -> CdbSize {
+        VERIFY.size(
+            self.min_size,
+            self.lba,
+            self.blocks,
+            self.flags(),
+            self.group,
+        )
+    }
+
+    pub fn decode(cdb: &[u8], block_size: u32) -> Option<Self> {
+        let f = VERIFY.decode(cdb)?;
+        Some(Self {
+            lba: f.lba,
+            blocks: f.blocks,
+            block_size,
+            dpo: f.flags & 0x10 != 0,
+            vrprotect: f.flags >> 5,
+            bytchk: (f.flags >> 1) & 0x03,
+            group: f.group,
+            min_size: f.size,
+            tags: f.tags,
+        })
+    }
+}
+
+impl Cdb for Verify {
+    fn to_bytes(&self) -> CdbBuf {
+        VERIFY.encode(&RwFields {
+            size: self.size(),
+            flags: self.flags(),
+            lba: self.lba,
+            blocks: self.blocks,
+            group: self.group,
+            tags: self.tags,
+        })
+    }
+
+    fn direction(&self) -> DataDirection {
+        if self.transfer_length() == 0 {
+            DataDirection::None
+        } else {
+            DataDirection::Out
+        }
+    }
+
+    fn transfer_length(&self) -> usize {
+        match self.bytchk {
+            0 => 0,
+            3 if self.blocks > 0 => self.block_size as usize,
+            _ => bytes(self.blocks, self.block_size),
+        }
+    }
+}
+
+/// WRITE AND VERIFY(10/12/16/32).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct WriteAndVerify {
+    pub lba: u64,
+    pub blocks: u32,
+    /// The logical block length, used to compute the transfer length.
+    pub block_size: u32,
+    /// Disable page out.
+    pub dpo: bool,
+    pub wrprotect: u8,
+    pub bytchk: u8,
+    pub group: u8,
+    /// The smallest CDB to encode.
+    pub min_size: CdbSize,
+    /// Only encoded in the 32-byte form.
+    pub tags: ProtectionTags,
+}
+
+impl WriteAndVerify {
+    pub fn new(lba: u64, blocks: u32, block_size: u32) -> Self {
+        Self {
+            lba,
+            blocks,
+            block_size,
+            dpo: false,
+            wrprotect: 0,
+            bytchk: 0,
+            group: 0,
+            min_size: CdbSize::Ten,
+            tags: ProtectionTags::default(),
+        }
+    }
+
+    fn flags(&self) -> u8 {
+        (self.wrprotect & 0x07) << 5 | (self.dpo as u8) << 4 | (self.bytchk & 0x03) << 1
+    }
+
+    /// The size of CDB [`Cdb::to_bytes`] will produce.
+    pub fn size(&self) -> CdbSize {
+        WRITE_AND_VERIFY.size(
+            self.min_size,
+            self.lba,
+            self.blocks,
+            self.flags(),
+            self.group,
+        )
+    }
+
+    pub fn decode(cdb: &[u8], block_size: u32) -> Option<Self> {
+        let f = WRITE_AND_VERIFY.decode(cdb)?;
+        Some(Self {
+            lba: f.lba,
+            blocks: f.blocks,
+            block_size,
+            dpo: f.flags & 0x10 != 0,
+            wrprotect: f.flags >> 5,
+            bytchk: (f.flags >> 1) & 0x03,
+            group: f.group,
+            min_size: f.size,
+            tags: f.tags,
+        })
+    }
+}
+
+impl Cdb for WriteAndVerify {
+    fn to_bytes(&self) -> CdbBuf {
+        WRITE_AND_VERIFY.encode(&RwFields {
+            size: self.size(),
+            flags: self.flags(),
+            lba: self.lba,
+            blocks: self.blocks,
+            group: self.group,
+            tags: self.tags,
+        })
+    }
+
+    fn direction(&self) -> DataDirection {
+        if self.blocks == 0 {
+            DataDirection::None
+        } else {
+            DataDirection::Out
+        }
+    }
+
+    fn transfer_length(&self) -> usize {
+        bytes(self.blocks, self.block_size)
+    }
+}
+
+/// Pick between the 10 and 16-byte forms of a command with a 32-bit LBA and
+/// 16-bit block count in its 10-byte form.
+fn size_10_16(min: CdbSize, lba: u64, blocks: u32) -> CdbSize {
+    if min <= CdbSize::Ten && lba <= u32::MAX as u64 && blocks <= u16::MAX as u32 {
+        CdbSize::Ten
+    } else {
+        CdbSize::Sixteen
+    }
+}
+
+/// Encode the LBA, block count and group number of a 10 or 16-byte CDB laid
+/// out like SYNCHRONIZE CACHE.
+fn encode_10_16(
+    size: CdbSize,
+    ops: (u8, u8),
+    byte1: u8,
+    lba: u64,
+    blocks: u32,
+    group: u8,
+) -> CdbBuf {
+    let mut cdb;
+    if size == CdbSize::Ten {
+        cdb = CdbBuf::with_opcode(10, ops.0);
+        cdb.set(2, &(lba as u32).to_be_bytes());
+        cdb.buf[6] = group & 0x3f;
+        cdb.set(7, &(blocks as u16).to_be_bytes());
+    } else {
+        cdb = CdbBuf::with_opcode(16, ops.1);
+        cdb.set(2, &lba.to_be_bytes());
+        cdb.set(10, &blocks.to_be_bytes());
+        cdb.buf[14] = group & 0x3f;
+    }
+    cdb.buf[1] = byte1;
+    cdb
+}
+
+/// The inverse of [`encode_10_16`]: returns the size, byte 1, LBA, block
+/// count and group number.
+fn decode_10_16(cdb: &[u8], ops: (u8, u8)) -> Option<(CdbSize, u8, u64, u32, u8)> {
+    let op = *cdb.first()?;
+    if op == ops.0 {
+        expect(cdb, 10, op, None)?;
+        Some((
+            CdbSize::Ten,
+            cdb[1],
+            be32(&cdb[2..6]) as u64,
+            be16(&cdb[7..9]) as u32,
+            cdb[6] & 0x3f,
+        ))
+    } else if op == ops.1 {
+        expect(cdb, 16, op, None)?;
+        Some((
+            CdbSize::Sixteen,
+            cdb[1],
+            be64(&cdb[2..10]),
+            be32(&cdb[10..14]),
+            cdb[14] & 0x3f,
+        ))
+    } else {
+        None
+    }
+}
+
+/// SYNCHRONIZE CACHE(10/16). A block count of 0 means through the last
+/// logical block.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct SynchronizeCache {
+    pub lba: u64,
+    pub blocks: u32,
+    /// Return as soon as the CDB has been validated.
+    pub immed: bool,
+    pub group: u8,
+    /// The smallest CDB to encode.
+    pub min_size: CdbSize,
+}
+
+impl SynchronizeCache {
+    const OPCODES: (u8, u8) = (0x35, 0x91);
+
+    /// Synchronize the entire cache.
+    pub fn all() -> Self {
+        Self::default()
+    }
+
+    pub fn range(lba: u64, blocks: u32) -> Self {
+        Self {
+            lba,
+            blocks,
+            ..Self::default()
+        }
+    }
+
+    pub fn size(&self) -> CdbSize {
+        size_10_16(self.min_size, self.lba, self.blocks)
+    }
+
+    pub fn decode(cdb: &[u8]) -> Option<Self> {
+        let (size, byte1, lba, blocks, group) = decode_10_16(cdb, Self::OPCODES)?;
+        Some(Self {
+            lba,
+            blocks,
+            immed: byte1 & 0x02 != 0,
+            group,
+            min_size: size,
+        })
+    }
+}
+
+impl Cdb for SynchronizeCache {
+    fn to_bytes(&self) -> CdbBuf {
+        encode_10_16(
+            self.size(),
+            Self::OPCODES,
+            (self.immed as u8) << 1,
+            self.lba,
+            self.blocks,
+            self.group,
+        )
+    }
+
+    fn direction(&self) -> DataDirection {
+        DataDirection::None
+    }
+
+    fn transfer_length(&self) -> usize {
+        0
+    }
+}
+
+/// PRE-FETCH(10/16). A block count of 0 means through the last logical
+/// block.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct PreFetch {
+    pub lba: u64,
+    pub blocks: u32,
+    /// Return as soon as the CDB has been validated.
+    pub immed: bool,
+    pub group: u8,
+    /// The smallest CDB to encode.
+    pub min_size: CdbSize,
+}
+
+impl PreFetch {
+    const OPCODES: (u8, u8) = (0x34, 0x90);
+
+    pub fn new(lba: u64, blocks: u32) -> Self {
+        Self {
+            lba,
+            blocks,
+            ..Self::default()
+        }
+    }
+
+    pub fn size(&self) -> CdbSize {
+        size_10_16(self.min_size, self.lba, self.blocks)
+    }
+
+    pub fn decode(cdb: &[u8]) -> Option<Self> {
+        let (size, byte1, lba, blocks, group) = decode_10_16(cdb, Self::OPCODES)?;
+        Some(Self {
+            lba,
+            blocks,
+            immed: byte1 & 0x02 != 0,
+            group,
+            min_size: size,
+        })
+    }
+}
+
+impl Cdb for PreFetch {
+    fn to_bytes(&self) -> CdbBuf {
+        encode_10_16(
+            self.size(),
+            Self::OPCODES,
+            (self.immed as u8) << 1,
+            self.lba,
+            self.blocks,
+            self.group,
+        )
+    }
+
+    fn direction(&self) -> DataDirection {
+        DataDirection::None
+    }
+
+    fn transfer_length(&self) -> usize {
+        0
+    }
+}
+
+/// WRITE SAME(10/16).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct WriteSame {
+    pub lba: u64,
+    pub blocks: u32,
+    /// The logical block length, used to compute the transfer length.
+    pub block_size: u32,
+    pub wrprotect: u8,
+    /// Request the blocks be unmapped (deallocated) rather than written.
+    pub unmap: bool,
+    /// Request the blocks be anchored.
+    pub anchor: bool,
+    /// No data-out buffer: write zeros. Requires the 16-byte form.
+    pub ndob: bool,
+    pub group: u8,
+    /// The smallest CDB to encode.
+    pub min_size: CdbSize,
+}
+
+impl WriteSame {
+    const OPCODES: (u8, u8) = (0x41, 0x93);
+
+    pub fn new(lba: u64, blocks: u32, block_size: u32) -> Self {
+        Self {
+            lba,
+            blocks,
+            block_size,
+            wrprotect: 0,
+            unmap: false,
+            anchor: false,
+            ndob: false,
+            group: 0,
+            min_size: CdbSize::Ten,
+        }
+    }
+
+    pub fn size(&self) -> CdbSize {
+        match self.ndob {
+            true => CdbSize::Sixteen,
+            false => size_10_16(self.min_size, self.lba, self.blocks),
+        }
+    }
+
+    pub fn decode(cdb: &[u8], block_size: u32) -> Option<Self> {
+        let (size, byte1, lba, blocks, group) = decode_10_16(cdb, Self::OPCODES)?;
+        Some(Self {
+            lba,
+            blocks,
+            block_size,
+            wrprotect: byte1 >> 5,
+            anchor: byte1 & 0x10 != 0,
+            unmap: byte1 & 0x08 != 0,
+            ndob: size == CdbSize::Sixteen && byte1 & 0x01 != 0,
+            group,
+            min_size: size,
+        })
+    }
+}
+
+impl Cdb for WriteSame {
+    fn to_bytes(&self) -> CdbBuf {
+        let byte1 = (self.wrprotect & 0x07) << 5
+            | (self.anchor as u8) << 4
+            | (self.unmap as u8) << 3
+            | self.ndob as u8;
+        encode_10_16(
+            self.size(),
+            Self::OPCODES,
+            byte1,
+            self.lba,
+            self.blocks,
+            self.group,
+        )
+    }
+
+    fn direction(&self) -> DataDirection {
+        match self.ndob {
+            true => DataDirection::None,
+            false => DataDirection::Out,
+        }
+    }
+
+    fn transfer_length(&self) -> usize {
+        match self.ndob {
+            true => 0,
+            false => self.block_size as usize,
+        }
+    }
+}
+
+/// A block range in the UNMAP parameter list.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct UnmapDescriptor {
+    pub lba: u64,
+    pub blocks: u32,
+}
+
+impl UnmapDescriptor {
+    /// Parse the block descriptors from an UNMAP parameter list.
+    pub fn parse_list(buf: &[u8]) -> Option<Vec<UnmapDescriptor>> {
+        if buf.len() < 8 {
+            return None;
+        }
+        let len = (be16(&buf[2..4]) as usize).min(buf.len() - 8);
+
+        Some(
+            buf[8..8 + len]
+                .chunks_exact(16)
+                .map(|d| UnmapDescriptor {
+                    lba: be64(&d[0..8]),
+                    blocks: be32(&d[8..12]),
+                })
+                .collect(),
+        )
+    }
+}
+
+/// UNMAP.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Unmap {
+    pub anchor: bool,
+    pub group: u8,
+    pub parameter_list_length: u16,
+}
+
+impl Unmap {
+    pub const OPCODE: u8 = 0x42;
+    /// The most block descriptors that fit in a parameter list, whose
+    /// length must fit the 16-bit parameter list length field.
+    pub const MAX_DESCRIPTORS: usize = (u16::MAX as usize - 8) / 16;
+
+    /// An UNMAP for the parameter list `list` (as built by
+    /// [`Unmap::parameter_list`]), or `None` if the list is too long.
+    pub fn new(list: &[u8]) -> Option<Self> {
+        Some(Self {
+            parameter_list_length: u16::try_from(list.len()).ok()?,
+            ..Self::default()
+        })
+    }
+
+    /// Build the parameter list for `descs`, or `None` if there are more
+    /// than [`MAX_DESCRIPTORS`](Self::MAX_DESCRIPTORS) of them.
+    pub fn parameter_list(descs: &[UnmapDescriptor]) -> Option<Vec<u8>> {
+        if descs.len() > Self::MAX_DESCRIPTORS {
+            return None;
+        }
+        let len = descs.len() * 16;
+        let mut buf = Vec::with_capacity(8 + len);
+        buf.extend_from_slice(&((len + 6) as u16).to_be_bytes());
+        buf.extend_from_slice(&(len as u16).to_be_bytes());
+        buf.extend_from_slice(&[0; 4]);
+        for d in descs {
+            buf.extend_from_slice(&d.lba.to_be_bytes());
+            buf.extend_from_slice(&d.blocks.to_be_bytes());
+            buf.extend_from_slice(&[0; 4]);
+        }
+        Some(buf)
+    }
+
+    pub fn decode(cdb: &[u8]) -> Option<Self> {
+        expect(cdb, 10, Self::OPCODE, None)?;
+        Some(Self {
+            anchor: cdb[1] & 0x01 != 0,
+            group: cdb[6] & 0x3f,
+            parameter_list_length: be16(&cdb[7..9]),
+        })
+    }
+}
+
+impl Cdb for Unmap {
+    fn to_bytes(&self) -> CdbBuf {
+        let mut cdb = CdbBuf::with_opcode(10, Self::OPCODE);
+        cdb.buf[1] = self.anchor as u8;
+        cdb.buf[6] = self.group & 0x3f;
+        cdb.set(7, &self.parameter_list_length.to_be_bytes());
+        cdb
+    }
+
+    fn direction(&self) -> DataDirection {
+        if self.parameter_list_length == 0 {
+            DataDirection::None
+        } else {
+            DataDirection::Out
+        }
+    }
+
+    fn transfer_length(&self) -> usize {
+        self.parameter_list_length as usize
+    }
+}
+
+/// READ CAPACITY(10).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct ReadCapacity10;
+
+impl ReadCapacity10 {
+    pub const OPCODE: u8 = 0x25;
+    /// The length of the returned parameter data.
+    pub const DATA_LEN: usize = 8;
+
+    pub fn decode(cdb: &[u8]) -> Option<Self> {
+        expect(cdb, 10, Self::OPCODE, None)?;
+        Some(Self)
+    }
+}
+
+impl Cdb for ReadCapacity10 {
+    fn to_bytes(&self) -> CdbBuf {
+        CdbBuf::with_opcode(10, Self::OPCODE)
+    }
+
+    fn direction(&self) -> DataDirection {
+        DataDirection::In
+    }
+
+    fn transfer_length(&self) -> usize {
+        Self::DATA_LEN
+    }
+}
+
+/// READ CAPACITY(16) (SERVICE ACTION IN(16)).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ReadCapacity16 {
+    pub allocation_length: u32,
+}
+
+impl ReadCapacity16 {
+    pub const OPCODE: u8 = 0x9e;
+    pub const SERVICE_ACTION: u8 = 0x10;
+    /// The length of the parameter data defined by SBC-4.
+    pub const DATA_LEN: usize = 32;
+
+    pub fn new(allocation_length: u32) -> Self {
+        Self { allocation_length }
+    }
+
+    pub fn decode(cdb: &[u8]) -> Option<Self> {
+        expect(cdb, 16, Self::OPCODE, Some(Self::SERVICE_ACTION))?;
+        Some(Self {
+            allocation_length: be32(&cdb[10..14]),
+        })
+    }
+}
+
+impl Default for ReadCapacity16 {
+    fn default() -> Self {
+        Self::new(Self::DATA_LEN as u32)
+    }
+}
+
+impl Cdb for ReadCapacity16 {
+    fn to_bytes(&self) -> CdbBuf {
+        let mut cdb = CdbBuf::with_opcode(16, Self::OPCODE);
+        cdb.buf[1] = Self::SERVICE_ACTION;
+        cdb.set(10, &self.allocation_length.to_be_bytes());
+        cdb
+    }
+
+    fn direction(&self) -> DataDirection {
+        DataDirection::In
+    }
+
+    fn transfer_length(&self) -> usize {
+        self.allocation_length as usize
+    }
+}
+
+/// GET LBA STATUS (SERVICE ACTION IN(16)).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct GetLbaStatus {
+    pub lba: u64,
+    pub allocation_length: u32,
+}
+
+impl GetLbaStatus {
+    pub const OPCODE: u8 = 0x9e;
+    pub const SERVICE_ACTION: u8 = 0x12;
+
+    pub fn new(lba: u64, allocation_length: u32) -> Self {
+        Self {
+            lba,
+            allocation_length,
+        }
+    }
+
+    pub fn decode(cdb: &[u8]) -> Option<Self> {
+        expect(cdb, 16, Self::OPCODE, Some(Self::SERVICE_ACTION))?;
+        Some(Self {
+            lba: be64(&cdb[2..10]),
+            allocation_length: be32(&cdb[10..14]),
+        })
+    }
+}
+
+impl Cdb for GetLbaStatus {
+    fn to_bytes(&self) -> CdbBuf {
+        let mut cdb = CdbBuf::with_opcode(16, Self::OPCODE);
+        cdb.buf[1] = Self::SERVICE_ACTION;
+        cdb.set(2, &self.lba.to_be_bytes());
+        cdb.set(10, &self.allocation_length.to_be_bytes());
+        cdb
+    }
+
+    fn direction(&self) -> DataDirection {
+        DataDirection::In
+    }
+
+    fn transfer_length(&self) -> usize {
+        self.allocation_length as usize
+    }
+}
+
+/// COMPARE AND WRITE. The data-out buffer holds `blocks` blocks of verify
+/// data followed by `blocks` blocks of write data.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CompareAndWrite {
+    pub lba: u64,
+    pub blocks: u8,
+    /// The logical block length, used to compute the transfer length.
+    pub block_size: u32,
+    /// Force unit access.
+    pub fua: bool,
+    /// Disable page out.
+    pub dpo: bool,
+    pub wrprotect: u8,
+    pub group: u8,
+}
+
+impl CompareAndWrite {
+    pub const OPCODE: u8 = 0x89;
+
+    pub fn new(lba: u64, blocks: u8, block_size: u32) -> Self {
+        Self {
+            lba,
+            blocks,
+            block_size,
+            fua: false,
+            dpo: false,
+            wrprotect: 0,
+            group: 0,
+        }
+    }
+
+    pub fn decode(cdb: &[u8], block_size: u32) -> Option<Self> {
+        expect(cdb, 16, Self::OPCODE, None)?;
+        Some(Self {
+            lba: be64(&cdb[2..10]),
+            blocks: cdb[13],
+            block_size,
+            fua: cdb[1] & 0x08 != 0,
+            dpo: cdb[1] & 0x10 != 0,
+            wrprotect: cdb[1] >> 5,
+            group: cdb[14] & 0x3f,
+        })
+    }
+}
+
+impl Cdb for CompareAndWrite {
+    fn to_bytes(&self) -> CdbBuf {
+        let mut cdb = CdbBuf::with_opcode(16, Self::OPCODE);
+        cdb.buf[1] = (self.wrprotect & 0x07) << 5 | (self.dpo as u8) << 4 | (self.fua as u8) << 3;
+        cdb.set(2, &self.lba.to_be_bytes());
+        cdb.buf[13] = self.blocks;
+        cdb.buf[14] = self.group & 0x3f;
+        cdb
+    }
+
+    fn direction(&self) -> DataDirection {
+        if self.blocks == 0 {
+            DataDirection::None
+        } else {
+            DataDirection::Out
+        }
+    }
+
+    fn transfer_length(&self) -> usize {
+        2 * bytes(self.blocks as u32, self.block_size)
+    }
+}
+
+/// Any of the SBC commands above, decoded from raw CDB bytes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SbcCdb {
+    Read(Read),
+    Write(Write),
+    Verify(Verify),
+    WriteAndVerify(WriteAndVerify),
+    SynchronizeCache(SynchronizeCache),
+    PreFetch(PreFetch),
+    WriteSame(WriteSame),
+    Unmap(Unmap),
+    ReadCapacity10(ReadCapacity10),
+    ReadCapacity16(ReadCapacity16),
+    GetLbaStatus(GetLbaStatus),
+    CompareAndWrite(CompareAndWrite),
+}
+
+impl SbcCdb {
+    /// Decode `cdb` for a device with `block_size` byte logical blocks,
+    /// returning `None` if it is not one of the commands above or is
+    /// malformed.
+    pub fn decode(cdb: &[u8], block_size: u32) -> Option<Self> {
+        let op = *cdb.first()?;
+        let is = |ops: &Opcodes| {
+            ops.six == Some(op)
+                || [ops.ten, ops.twelve, ops.sixteen].contains(&op)
+                || (op == VARIABLE_LENGTH && cdb.len() >= 10 && be16(&cdb[8..10]) == ops.sa32)
+        };
+
+        let cmd = if is(&READ) {
+            SbcCdb::Read(Read::decode(cdb, block_size)?)
+        } else if is(&WRITE) {
+            SbcCdb::Write(Write::decode(cdb, block_size)?)
+        } else if is(&VERIFY) {
+            SbcCdb::Verify(Verify::decode(cdb, block_size)?)
+        } else if is(&WRITE_AND_VERIFY) {
+            SbcCdb::WriteAndVerify(WriteAndVerify::decode(cdb, block_size)?)
+        } else {
+            match op {
+                0x35 | 0x91 => SbcCdb::SynchronizeCache(SynchronizeCache::decode(cdb)?),
+                0x34 | 0x90 => SbcCdb::PreFetch(PreFetch::decode(cdb)?),
+                0x41 | 0x93 => SbcCdb::WriteSame(WriteSame::decode(cdb, block_size)?),
+                Unmap::OPCODE => SbcCdb::Unmap(Unmap::decode(cdb)?),
+                ReadCapacity10::OPCODE => SbcCdb::ReadCapacity10(ReadCapacity10::decode(cdb)?),
+                // SERVICE ACTION IN(16)
+                0x9e => match cdb.get(1)? & 0x1f {
+                    ReadCapacity16::SERVICE_ACTION => {
+                        SbcCdb::ReadCapacity16(ReadCapacity16::decode(cdb)?)
+                    }
+                    GetLbaStatus::SERVICE_ACTION => {
+                        SbcCdb::GetLbaStatus(GetLbaStatus::decode(cdb)?)
+                    }
+                    _ => return None,
+                },
+                CompareAndWrite::OPCODE => {
+                    SbcCdb::CompareAndWrite(CompareAndWrite::decode(cdb, block_size)?)
+                }
+                _ => return None,
+            }
+        };
+
+        Some(cmd)
+    }
+
+    fn inner(&self) -> &dyn Cdb {
+        match self {
+            SbcCdb::Read(c) => c,
+            SbcCdb::Write(c) => c,
+            SbcCdb::Verify(c) => c,
+            SbcCdb::WriteAndVerify(c) => c,
+            SbcCdb::SynchronizeCache(c) => c,
+            SbcCdb::PreFetch(c) => c,
+            SbcCdb::WriteSame(c) => c,
+            SbcCdb::Unmap(c) => c,
+            SbcCdb::ReadCapacity10(c) => c,
+            SbcCdb::ReadCapacity16(c) => c,
+            SbcCdb::GetLbaStatus(c) => c,
+            SbcCdb::CompareAndWrite(c) => c,
+        }
+    }
+}
+
+impl Cdb for SbcCdb {
+    fn to_bytes(&self) -> CdbBuf {
+        self.inner().to_bytes()
+    }
+
+    fn direction(&self) -> DataDirection {
+        self.inner().direction()
+    }
+
+    fn transfer_length(&self) -> usize {
+        self.inner().transfer_length()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const BS: u32 = 512;
+
+    fn read(lba: u64, blocks: u32) -> Read {
+        Read::new(lba, blocks, BS)
+    }
+
+    /// Encode `cmd`, check the CDB is well formed, and decode it again both
+    /// directly and through [`SbcCdb`].
+    fn round_trip<C, D>(cmd: &C, decode: D) -> C
+    where
+        C: Cdb,
+        D: Fn(&[u8]) -> Option<C>,
+    {
+        let cdb = cmd.to_bytes();
+        crate::cdb::validate(&cdb).unwrap();
+        assert!(SbcCdb::decode(&cdb, BS).is_some(), "{cdb:?}");
+        decode(&cdb).unwrap()
+    }
+
+    #[test]
+    fn read_size_selection() {
+        // 21-bit LBA and 1 to 256 blocks fit the 6-byte form.
+        assert_eq!(read(0x1f_ffff, 256).size(), CdbSize::Six);
+        assert_eq!(read(0, 1).size(), CdbSize::Six);
+        assert_eq!(read(0x20_0000, 1).size(), CdbSize::Ten);
+        assert_eq!(read(0, 257).size(), CdbSize::Ten);
+        assert_eq!(read(0, 0).size(), CdbSize::Ten);
+
+        // 32-bit LBA and 16-bit count fit the 10-byte form.
+        assert_eq!(read(u32::MAX as u64, 0xffff).size(), CdbSize::Ten);
+        assert_eq!(read(u32::MAX as u64, 0x1_0000).size(), CdbSize::Twelve);
+        assert_eq!(read(u32::MAX as u64 + 1, 1).size(), CdbSize::Sixteen);
+        assert_eq!(read(u64::MAX, u32::MAX).size(), CdbSize::Sixteen);
+
+        // Fields the 6-byte form lacks force a larger one.
+        let mut r = read(0, 1);
+        r.fua = true;
+        assert_eq!(r.size(), CdbSize::Ten);
+        let mut r = read(0, 1);
+        r.group = 1;
+        assert_eq!(r.size(), CdbSize::Ten);
+
+        let mut r = read(0, 1);
+        r.min_size = CdbSize::ThirtyTwo;
+        assert_eq!(r.size(), CdbSize::ThirtyTwo);
+    }
+
+    #[test]
+    fn read_encoding() {
+        assert_eq!(
+            &*read(0x12_3456, 256).to_bytes(),
+            &[0x08, 0x12, 0x34, 0x56, 0, 0]
+        );
+        assert_eq!(
+            &*read(0x1234_5678, 0x9abc).to_bytes(),
+            &[0x28, 0, 0x12, 0x34, 0x56, 0x78, 0, 0x9a, 0xbc, 0]
+        );
+        assert_eq!(
+            &*read(0x1234_5678, 0x1_0000).to_bytes(),
+            &[0xa8, 0, 0x12, 0x34, 0x56, 0x78, 0, 1, 0, 0, 0, 0]
+        );
+        assert_eq!(
+            &*read(0x1_0000_0000, 1).to_bytes(),
+            &[0x88, 0, 0, 0, 0, 1, 0, 0, 0, 0, 0, 0, 0, 1, 0, 0]
+        );
+    }
+
+    #[test]
+    fn read_write_round_trip() {
+        let cases = [
+            (0, 1),
+            (0x1f_ffff, 256),
+            (0x20_0000, 8),
+            (u32::MAX as u64, 0xffff),
+            (u32::MAX as u64, 0x1_0000),
+            (u32::MAX as u64 + 1, 1),
+            (u64::MAX, u32::MAX),
+        ];
+        for (lba, blocks) in cases {
+            let mut r = read(lba, blocks);
+            r.min_size = r.size();
+            assert_eq!(round_trip(&r, |c| Read::decode(c, BS)), r);
+            assert_eq!(r.transfer_length(), blocks as usize * BS as usize);
+
+            let mut w = Write::new(lba, blocks, BS);
+            w.min_size = w.size();
+            assert_eq!(round_trip(&w, |c| Write::decode(c, BS)), w);
+            assert_eq!(w.direction(), DataDirection::Out);
+        }
+
+        let mut r = read(0x1234, 16);
+        r.fua = true;
+        r.dpo = true;
+        r.rdprotect = 3;
+        r.group = 0x15;
+        r.min_size = CdbSize::ThirtyTwo;
+        r.tags = ProtectionTags {
+            ref_tag: 0xdead_beef,
+            app_tag: 0x1234,
+            app_tag_mask: 0xffff,
+        };
+        assert_eq!(round_trip(&r, |c| Read::decode(c, BS)), r);
+
+        let mut w = Write::new(u64::MAX - 1, 2, BS);
+        w.fua = true;
+        w.wrprotect = 5;
+        w.min_size = CdbSize::ThirtyTwo;
+        assert_eq!(round_trip(&w, |c| Write::decode(c, BS)), w);
+    }
+
+    #[test]
+    fn verify_round_trip() {
+        for (lba, blocks, size) in [
+            (0, 1, CdbSize::Ten),
+            (u32::MAX as u64, 0x1_0000, CdbSize::Twelve),
+            (u32::MAX as u64 + 1, 1, CdbSize::Sixteen),
+        ] {
+            let mut v = Verify::new(lba, blocks, BS);
+            v.bytchk = 1;
+            assert_eq!(v.size(), size);
+            v.min_size = size;
+            assert_eq!(round_trip(&v, |c| Verify::decode(c, BS)), v);
+
+            let mut wv = WriteAndVerify::new(lba, blocks, BS);
+            wv.dpo = true;
+            assert_eq!(wv.size(), size);
+            wv.min_size = size;
+            assert_eq!(round_trip(&wv, |c| WriteAndVerify::decode(c, BS)), wv);
+        }
+
+        let mut v = Verify::new(0, 4, BS);
+        assert_eq!(v.direction(), DataDirection::None);
+        v.bytchk = 3;
+        assert_eq!(v.transfer_length(), BS as usize);
+        v.bytchk = 1;
+        assert_eq!(v.transfer_length(), 4 * BS as usize);
+    }
+
+    #[test]
+    fn synchronize_cache() {
+        let all = SynchronizeCache::all();
+        assert_eq!(&*all.to_bytes(), &[0x35, 0, 0, 0, 0, 0, 0, 0, 0, 0]);
+        assert_eq!(
+            round_trip(&all, SynchronizeCache::decode),
+            SynchronizeCache {
+                min_size: CdbSize::Ten,
+                ..all
+            }
+        );
+
+        for (lba, blocks, size) in [
+            (0, 0, CdbSize::Ten),
+            (u32::MAX as u64, 0xffff, CdbSize::Ten),
+            (u32::MAX as u64, 0x1_0000, CdbSize::Sixteen),
+            (u32::MAX as u64 + 1, 0, CdbSize::Sixteen),
+        ] {
+            let mut s = SynchronizeCache::range(lba, blocks);
+            s.immed = true;
+            assert_eq!(s.size(), size);
+            s.min_size = size;
+            assert_eq!(round_trip(&s, SynchronizeCache::decode), s);
+
+            let mut p = PreFetch::new(lba, blocks);
+            p.group = 3;
+            assert_eq!(p.size(), size);
+            p.min_size = size;
+            assert_eq!(round_trip(&p, PreFetch::decode), p);
+        }
+    }
+
+    #[test]
+    fn write_same() {
+        for (lba, blocks, size) in [
+            (0, 0, CdbSize::Ten),
+            (u32::MAX as u64, 0xffff, CdbSize::Ten),
+            (0, 0x1_0000, CdbSize::Sixteen),
+            (u32::MAX as u64 + 1, 0, CdbSize::Sixteen),
+        ] {
+            let mut ws = WriteSame::new(lba, blocks, BS);
+            ws.unmap = true;
+            ws.anchor = true;
+            assert_eq!(ws.size(), size);
+            ws.min_size = size;
+            assert_eq!(round_trip(&ws, |c| WriteSame::decode(c, BS)), ws);
+            assert_eq!(ws.transfer_length(), BS as usize);
+        }
+
+        let mut ws = WriteSame::new(0, 0, BS);
+        ws.ndob = true;
+        assert_eq!(ws.size(), CdbSize::Sixteen);
+        assert_eq!(ws.direction(), DataDirection::None);
+        ws.min_size = CdbSize::Sixteen;
+        assert_eq!(round_trip(&ws, |c| WriteSame::decode(c, BS)), ws);
+    }
+
+    #[test]
+    fn unmap() {
+        let descs = [
+            UnmapDescriptor { lba: 0, blocks: 8 },
+            UnmapDescriptor {
+                lba: u64::MAX - 8,
+                blocks: u32::MAX,
+            },
+        ];
+        let list = Unmap::parameter_list(&descs).unwrap();
+        assert_eq!(list.len(), 8 + 32);
+        assert_eq!(&list[..4], &[0, 38, 0, 32]);
+        assert_eq!(UnmapDescriptor::parse_list(&list).unwrap(), descs);
+
+        let mut u = Unmap::new(&list).unwrap();
+        u.anchor = true;
+        assert_eq!(u.transfer_length(), list.len());
+        assert_eq!(round_trip(&u, Unmap::decode), u);
+
+        let max = vec![descs[0]; Unmap::MAX_DESCRIPTORS];
+        let list = Unmap::parameter_list(&max).unwrap();
+        assert_eq!(UnmapDescriptor::parse_list(&list).unwrap().len(), max.len());
+        assert!(Unmap::new(&list).is_some());
+
+        let too_many = vec![descs[0]; Unmap::MAX_DESCRIPTORS + 1];
+        assert_eq!(Unmap::parameter_list(&too_many), None);
+        assert_eq!(Unmap::new(&[0; 0x1_0000]), None);
+    }
+
+    #[test]
+    fn service_action_in() {
+        let rc10 = ReadCapacity10;
+        assert_eq!(round_trip(&rc10, ReadCapacity10::decode), rc10);
+
+        let rc16 = ReadCapacity16::default();
+        assert_eq!(rc16.to_bytes()[1], 0x10);
+        assert_eq!(round_trip(&rc16, ReadCapacity16::decode), rc16);
+
+        let gls = GetLbaStatus::new(u64::MAX, 4096);
+        assert_eq!(round_trip(&gls, GetLbaStatus::decode), gls);
+        assert_eq!(ReadCapacity16::decode(&gls.to_bytes()), None);
+    }
+
+    #[test]
+    fn compare_and_write() {
+        let mut caw = CompareAndWrite::new(u64::MAX, 255, BS);
+        caw.fua = true;
+        caw.group = 7;
+        assert_eq!(round_trip(&caw, |c| CompareAndWrite::decode(c, BS)), caw);
+        assert_eq!(caw.transfer_length(), 2 * 255 * BS as usize);
+    }
+
+    #[test]
+    fn decode_rejects_short_cdbs() {
+        let cdb = read(u64::MAX, 1).to_bytes();
+        assert_eq!(Read::decode(&cdb[..15], BS), None);
+        assert_eq!(SbcCdb::decode(&cdb[..15], BS), None);
+        assert_eq!(Write::decode(&cdb, BS), None);
+    }
+}
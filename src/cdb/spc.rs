@@ -0,0 +1,1183 @@
+/*
+ * Copyright 2025 Jason King
+ */
+
+use super::{be16, be24, be32, expect, Cdb, CdbBuf, PageControl};
+use crate::DataDirection;
+
+/// TEST UNIT READY.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct TestUnitReady;
+
+impl TestUnitReady {
+    pub const OPCODE: u8 = 0x00;
+
+    pub fn decode(cdb: &[u8]) -> Option<Self> {
+        expect(cdb, 6, Self::OPCODE, None)?;
+        Some(Self)
+    }
+}
+
+impl Cdb for TestUnitReady {
+    fn to_bytes(&self) -> CdbBuf {
+        CdbBuf::with_opcode(6, Self::OPCODE)
+    }
+
+    fn direction(&self) -> DataDirection {
+        DataDirection::None
+    }
+
+    fn transfer_length(&self) -> usize {
+        0
+    }
+}
+
+/// REQUEST SENSE.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RequestSense {
+    /// Request descriptor format sense data.
+    pub desc: bool,
+    pub allocation_length: u8,
+}
+
+impl RequestSense {
+    pub const OPCODE: u8 = 0x03;
+
+    pub fn new(allocation_length: u8) -> Self {
+        Self {
+            desc: false,
+            allocation_length,
+        }
+    }
+
+    pub fn decode(cdb: &[u8]) -> Option<Self> {
+        expect(cdb, 6, Self::OPCODE, None)?;
+        Some(Self {
+            desc: cdb[1] & 0x01 != 0,
+            allocation_length: cdb[4],
+        })
+    }
+}
+
+impl Cdb for RequestSense {
+    fn to_bytes(&self) -> CdbBuf {
+        let mut cdb = CdbBuf::with_opcode(6, Self::OPCODE);
+        cdb.buf[1] = self.desc as u8;
+        cdb.buf[4] = self.allocation_length;
+        cdb
+    }
+
+    fn direction(&self) -> DataDirection {
+        DataDirection::In
+    }
+
+    fn transfer_length(&self) -> usize {
+        self.allocation_length as usize
+    }
+}
+
+/// INQUIRY, for either standard INQUIRY data or a VPD page.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Inquiry {
+    pub evpd: bool,
+    pub page_code: u8,
+    pub allocation_length: u16,
+}
+
+impl Inquiry {
+    pub const OPCODE: u8 = 0x12;
+
+    /// Request standard INQUIRY data.
+    pub fn standard(allocation_length: u16) -> Self {
+        Self {
+            evpd: false,
+            page_code: 0,
+            allocation_length,
+        }
+    }
+
+    /// Request a vital product data page.
+    pub fn vpd(page_code: u8, allocation_length: u16) -> Self {
+        Self {
+            evpd: true,
+            page_code,
+            allocation_length,
+        }
+    }
+
+    pub fn decode(cdb: &[u8]) -> Option<Self> {
+        expect(cdb, 6, Self::OPCODE, None)?;
+        Some(Self {
+            evpd: cdb[1] & 0x01 != 0,
+            page_code: cdb[2],
+            allocation_length: be16(&cdb[3..5]),
+        })
+    }
+}
+
+impl Cdb for Inquiry {
+    fn to_bytes(&self) -> CdbBuf {
+        let mut cdb = CdbBuf::with_opcode(6, Self::OPCODE);
+        cdb.buf[1] = self.evpd as u8;
+        cdb.buf[2] = self.page_code;
+        cdb.set(3, &self.allocation_length.to_be_bytes());
+        cdb
+    }
+
+    fn direction(&self) -> DataDirection {
+        DataDirection::In
+    }
+
+    fn transfer_length(&self) -> usize {
+        self.allocation_length as usize
+    }
+}
+
+/// MODE SENSE(6).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ModeSense6 {
+    /// Disable block descriptors.
+    pub dbd: bool,
+    pub pc: PageControl,
+    pub page_code: u8,
+    pub subpage_code: u8,
+    pub allocation_length: u8,
+}
+
+impl ModeSense6 {
+    pub const OPCODE: u8 = 0x1a;
+
+    pub fn new(page_code: u8, allocation_length: u8) -> Self {
+        Self {
+            dbd: false,
+            pc: PageControl::Current,
+            page_code,
+            subpage_code: 0,
+            allocation_length,
+        }
+    }
+
+    pub fn decode(cdb: &[u8]) -> Option<Self> {
+        expect(cdb, 6, Self::OPCODE, None)?;
+        Some(Self {
+            dbd: cdb[1] & 0x08 != 0,
+            pc: PageControl::from(cdb[2] >> 6),
+            page_code: cdb[2] & 0x3f,
+            subpage_code: cdb[3],
+            allocation_length: cdb[4],
+        })
+    }
+}
+
+impl Cdb for ModeSense6 {
+    fn to_bytes(&self) -> CdbBuf {
+        let mut cdb = CdbBuf::with_opcode(6, Self::OPCODE);
+        cdb.buf[1] = (self.dbd as u8) << 3;
+        cdb.buf[2] = (self.pc as u8) << 6 | self.page_code & 0x3f;
+        cdb.buf[3] = self.subpage_code;
+        cdb.buf[4] = self.allocation_length;
+        cdb
+    }
+
+    fn direction(&self) -> DataDirection {
+        DataDirection::In
+    }
+
+    fn transfer_length(&self) -> usize {
+        self.allocation_length as usize
+    }
+}
+
+/// MODE SENSE(10).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ModeSense10 {
+    /// Allow long LBA block descriptors.
+    pub llbaa: bool,
+    /// Disable block descriptors.
+    pub dbd: bool,
+    pub pc: PageControl,
+    pub page_code: u8,
+    pub subpage_code: u8,
+    pub allocation_length: u16,
+}
+
+impl ModeSense10 {
+    pub const OPCODE: u8 = 0x5a;
+
+    pub fn new(page_code: u8, allocation_length: u16) -> Self {
+        Self {
+            llbaa: false,
+            dbd: false,
+            pc: PageControl::Current,
+            page_code,
+            subpage_code: 0,
+            allocation_length,
+        }
+    }
+
+    pub fn decode(cdb: &[u8]) -> Option<Self> {
+        expect(cdb, 10, Self::OPCODE, None)?;
+        Some(Self {
+            llbaa: cdb[1] & 0x10 != 0,
+            dbd: cdb[1] & 0x08 != 0,
+            pc: PageControl::from(cdb[2] >> 6),
+            page_code: cdb[2] & 0x3f,
+            subpage_code: cdb[3],
+            allocation_length: be16(&cdb[7..9]),
+        })
+    }
+}
+
+impl Cdb for ModeSense10 {
+    fn to_bytes(&self) -> CdbBuf {
+        let mut cdb = CdbBuf::with_opcode(10, Self::OPCODE);
+        cdb.buf[1] = (self.llbaa as u8) << 4 | (self.dbd as u8) << 3;
+        cdb.buf[2] = (self.pc as u8) << 6 | self.page_code & 0x3f;
+        cdb.buf[3] = self.subpage_code;
+        cdb.set(7, &self.allocation_length.to_be_bytes());
+        cdb
+    }
+
+    fn direction(&self) -> DataDirection {
+        DataDirection::In
+    }
+
+    fn transfer_length(&self) -> usize {
+        self.allocation_length as usize
+    }
+}
+
+/// MODE SELECT(6).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ModeSelect6 {
+    /// Page format: the parameter list uses the standard page format.
+    pub pf: bool,
+    /// Save pages.
+    pub sp: bool,
+    pub parameter_list_length: u8,
+}
+
+impl ModeSelect6 {
+    pub const OPCODE: u8 = 0x15;
+
+    pub fn new(parameter_list_length: u8) -> Self {
+        Self {
+            pf: true,
+            sp: false,
+            parameter_list_length,
+        }
+    }
+
+    pub fn decode(cdb: &[u8]) -> Option<Self> {
+        expect(cdb, 6, Self::OPCODE, None)?;
+        Some(Self {
+            pf: cdb[1] & 0x10 != 0,
+            sp: cdb[1] & 0x01 != 0,
+            parameter_list_length: cdb[4],
+        })
+    }
+}
+
+impl Cdb for ModeSelect6 {
+    fn to_bytes(&self) -> CdbBuf {
+        let mut cdb = CdbBuf::with_opcode(6, Self::OPCODE);
+        cdb.buf[1] = (self.pf as u8) << 4 | self.sp as u8;
+        cdb.buf[4] = self.parameter_list_length;
+        cdb
+    }
+
+    fn direction(&self) -> DataDirection {
+        if self.parameter_list_length == 0 {
+            DataDirection::None
+        } else {
+            DataDirection::Out
+        }
+    }
+
+    fn transfer_length(&self) -> usize {
+        self.parameter_list_length as usize
+    }
+}
+
+/// MODE SELECT(10).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ModeSelect10 {
+    /// Page format: the parameter list uses the standard page format.
+    pub pf: bool,
+    /// Save pages.
+    pub sp: bool,
+    pub parameter_list_length: u16,
+}
+
+impl ModeSelect10 {
+    pub const OPCODE: u8 = 0x55;
+
+    pub fn new(parameter_list_length: u16) -> Self {
+        Self {
+            pf: true,
+            sp: false,
+            parameter_list_length,
+        }
+    }
+
+    pub fn decode(cdb: &[u8]) -> Option<Self> {
+        expect(cdb, 10, Self::OPCODE, None)?;
+        Some(Self {
+            pf: cdb[1] & 0x10 != 0,
+            sp: cdb[1] & 0x01 != 0,
+            parameter_list_length: be16(&cdb[7..9]),
+        })
+    }
+}
+
+impl Cdb for ModeSelect10 {
+    fn to_bytes(&self) -> CdbBuf {
+        let mut cdb = CdbBuf::with_opcode(10, Self::OPCODE);
+        cdb.buf[1] = (self.pf as u8) << 4 | self.sp as u8;
+        cdb.set(7, &self.parameter_list_length.to_be_bytes());
+        cdb
+    }
+
+    fn direction(&self) -> DataDirection {
+        if self.parameter_list_length == 0 {
+            DataDirection::None
+        } else {
+            DataDirection::Out
+        }
+    }
+
+    fn transfer_length(&self) -> usize {
+        self.parameter_list_length as usize
+    }
+}
+
+/// LOG SENSE.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LogSense {
+    /// Parameter pointer control.
+    pub ppc: bool,
+    /// Save parameters.
+    pub sp: bool,
+    /// Page control (0: current threshold, 1: current cumulative,
+    /// 2: default threshold, 3: default cumulative).
+    pub pc: u8,
+    pub page_code: u8,
+    pub subpage_code: u8,
+    pub parameter_pointer: u16,
+    pub allocation_length: u16,
+}
+
+impl LogSense {
+    pub const OPCODE: u8 = 0x4d;
+
+    /// Request the current cumulative values of a log page.
+    pub fn new(page_code: u8, allocation_length: u16) -> Self {
+        Self {
+            ppc: false,
+            sp: false,
+            pc: 1,
+            page_code,
+            subpage_code: 0,
+            parameter_pointer: 0,
+            allocation_length,
+        }
+    }
+
+    pub fn decode(cdb: &[u8]) -> Option<Self> {
+        expect(cdb, 10, Self::OPCODE, None)?;
+        Some(Self {
+            ppc: cdb[1] & 0x02 != 0,
+            sp: cdb[1] & 0x01 != 0,
+            pc: cdb[2] >> 6,
+            page_code: cdb[2] & 0x3f,
+            subpage_code: cdb[3],
+            parameter_pointer: be16(&cdb[5..7]),
+            allocation_length: be16(&cdb[7..9]),
+        })
+    }
+}
+
+impl Cdb for LogSense {
+    fn to_bytes(&self) -> CdbBuf {
+        let mut cdb = CdbBuf::with_opcode(10, Self::OPCODE);
+        cdb.buf[1] = (self.ppc as u8) << 1 | self.sp as u8;
+        cdb.buf[2] = (self.pc & 0x03) << 6 | self.page_code & 0x3f;
+        cdb.buf[3] = self.subpage_code;
+        cdb.set(5, &self.parameter_pointer.to_be_bytes());
+        cdb.set(7, &self.allocation_length.to_be_bytes());
+        cdb
+    }
+
+    fn direction(&self) -> DataDirection {
+        DataDirection::In
+    }
+
+    fn transfer_length(&self) -> usize {
+        self.allocation_length as usize
+    }
+}
+
+/// LOG SELECT.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LogSelect {
+    /// Parameter code reset.
+    pub pcr: bool,
+    /// Save parameters.
+    pub sp: bool,
+    /// Page control, as for [`LogSense::pc`].
+    pub pc: u8,
+    pub page_code: u8,
+    pub subpage_code: u8,
+    pub parameter_list_length: u16,
+}
+
+impl LogSelect {
+    pub const OPCODE: u8 = 0x4c;
+
+    pub fn new(page_code: u8, parameter_list_length: u16) -> Self {
+        Self {
+            pcr: false,
+            sp: false,
+            pc: 1,
+            page_code,
+            subpage_code: 0,
+            parameter_list_length,
+        }
+    }
+
+    pub fn decode(cdb: &[u8]) -> Option<Self> {
+        expect(cdb, 10, Self::OPCODE, None)?;
+        Some(Self {
+            pcr: cdb[1] & 0x02 != 0,
+            sp: cdb[1] & 0x01 != 0,
+            pc: cdb[2] >> 6,
+            page_code: cdb[2] & 0x3f,
+            subpage_code: cdb[3],
+            parameter_list_length: be16(&cdb[7..9]),
+        })
+    }
+}
+
+impl Cdb for LogSelect {
+    fn to_bytes(&self) -> CdbBuf {
+        let mut cdb = CdbBuf::with_opcode(10, Self::OPCODE);
+        cdb.buf[1] = (self.pcr as u8) << 1 | self.sp as u8;
+        cdb.buf[2] = (self.pc & 0x03) << 6 | self.page_code & 0x3f;
+        cdb.buf[3] = self.subpage_code;
+        cdb.set(7, &self.parameter_list_length.to_be_bytes());
+        cdb
+    }
+
+    fn direction(&self) -> DataDirection {
+        if self.parameter_list_length == 0 {
+            DataDirection::None
+        } else {
+            DataDirection::Out
+        }
+    }
+
+    fn transfer_length(&self) -> usize {
+        self.parameter_list_length as usize
+    }
+}
+
+/// REPORT LUNS.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ReportLuns {
+    pub select_report: u8,
+    pub allocation_length: u32,
+}
+
+impl ReportLuns {
+    pub const OPCODE: u8 = 0xa0;
+
+    pub fn new(allocation_length: u32) -> Self {
+        Self {
+            select_report: 0,
+            allocation_length,
+        }
+    }
+
+    pub fn decode(cdb: &[u8]) -> Option<Self> {
+        expect(cdb, 12, Self::OPCODE, None)?;
+        Some(Self {
+            select_report: cdb[2],
+            allocation_length: be32(&cdb[6..10]),
+        })
+    }
+}
+
+impl Cdb for ReportLuns {
+    fn to_bytes(&self) -> CdbBuf {
+        let mut cdb = CdbBuf::with_opcode(12, Self::OPCODE);
+        cdb.buf[2] = self.select_report;
+        cdb.set(6, &self.allocation_length.to_be_bytes());
+        cdb
+    }
+
+    fn direction(&self) -> DataDirection {
+        DataDirection::In
+    }
+
+    fn transfer_length(&self) -> usize {
+        self.allocation_length as usize
+    }
+}
+
+/// REPORT SUPPORTED OPERATION CODES (MAINTENANCE IN).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ReportSupportedOpcodes {
+    /// Return command timeouts descriptors.
+    pub rctd: bool,
+    pub reporting_options: u8,
+    pub requested_opcode: u8,
+    pub requested_service_action: u16,
+    pub allocation_length: u32,
+}
+
+impl ReportSupportedOpcodes {
+    pub const OPCODE: u8 = 0xa3;
+    pub const SERVICE_ACTION: u8 = 0x0c;
+
+    /// Request a list of all supported commands.
+    pub fn all(allocation_length: u32) -> Self {
+        Self {
+            rctd: false,
+            reporting_options: 0,
+            requested_opcode: 0,
+            requested_service_action: 0,
+            allocation_length,
+        }
+    }
+
+    pub fn decode(cdb: &[u8]) -> Option<Self> {
+        expect(cdb, 12, Self::OPCODE, Some(Self::SERVICE_ACTION))?;
+        Some(Self {
+            rctd: cdb[2] & 0x80 != 0,
+            reporting_options: cdb[2] & 0x07,
+            requested_opcode: cdb[3],
+            requested_service_action: be16(&cdb[4..6]),
+            allocation_length: be32(&cdb[6..10]),
+        })
+    }
+}
+
+impl Cdb for ReportSupportedOpcodes {
+    fn to_bytes(&self) -> CdbBuf {
+        let mut cdb = CdbBuf::with_opcode(12, Self::OPCODE);
+        cdb.buf[1] = Self::SERVICE_ACTION;
+        cdb.buf[2] = (self.rctd as u8) << 7 | self.reporting_options & 0x07;
+        cdb.buf[3] = self.requested_opcode;
+        cdb.set(4, &self.requested_service_action.to_be_bytes());
+        cdb.set(6, &self.allocation_length.to_be_bytes());
+        cdb
+    }
+
+    fn direction(&self) -> DataDirection {
+        DataDirection::In
+    }
+
+    fn transfer_length(&self) -> usize {
+        self.allocation_length as usize
+    }
+}
+
+/// REPORT SUPPORTED TASK MANAGEMENT FUNCTIONS (MAINTENANCE IN).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ReportSupportedTmfs {
+    /// Return extended parameter data.
+    pub repd: bool,
+    pub allocation_length: u32,
+}
+
+impl ReportSupportedTmfs {
+    pub const OPCODE: u8 = 0xa3;
+    pub const SERVICE_ACTION: u8 = 0x0d;
+
+    pub fn new(allocation_length: u32) -> Self {
+        Self {
+            repd: false,
+            allocation_length,
+        }
+    }
+
+    pub fn decode(cdb: &[u8]) -> Option<Self> {
+        expect(cdb, 12, Self::OPCODE, Some(Self::SERVICE_ACTION))?;
+        Some(Self {
+            repd: cdb[2] & 0x80 != 0,
+            allocation_length: be32(&cdb[6..10]),
+        })
+    }
+}
+
+impl Cdb for ReportSupportedTmfs {
+    fn to_bytes(&self) -> CdbBuf {
+        let mut cdb = CdbBuf::with_opcode(12, Self::OPCODE);
+        cdb.buf[1] = Self::SERVICE_ACTION;
+        cdb.buf[2] = (self.repd as u8) << 7;
+        cdb.set(6, &self.allocation_length.to_be_bytes());
+        cdb
+    }
+
+    fn direction(&self) -> DataDirection {
+        DataDirection::In
+    }
+
+    fn transfer_length(&self) -> usize {
+        self.allocation_length as usize
+    }
+}
+
+/// READ BUFFER(10).
+///
+/// The buffer offset and allocation length are 24-bit fields, so these are
+/// only constructed through [`ReadBuffer::new`], which rejects larger values.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ReadBuffer {
+    mode: u8,
+    buffer_id: u8,
+    buffer_offset: u32,
+    allocation_length: u32,
+}
+
+impl ReadBuffer {
+    pub const OPCODE: u8 = 0x3c;
+
+    pub fn new(
+        mode: u8,
+        buffer_id: u8,
+        buffer_offset: u32,
+        allocation_length: u32,
+    ) -> Option<Self> {
+        if mode > 0x1f || buffer_offset > 0xff_ffff || allocation_length > 0xff_ffff {
+            return None;
+        }
+
+        Some(Self {
+            mode,
+            buffer_id,
+            buffer_offset,
+            allocation_length,
+        })
+    }
+
+    pub fn mode(&self) -> u8 {
+        self.mode
+    }
+
+    pub fn buffer_id(&self) -> u8 {
+        self.buffer_id
+    }
+
+    pub fn buffer_offset(&self) -> u32 {
+        self.buffer_offset
+    }
+
+    pub fn allocation_length(&self) -> u32 {
+        self.allocation_length
+    }
+
+    pub fn decode(cdb: &[u8]) -> Option<Self> {
+        expect(cdb, 10, Self::OPCODE, None)?;
+        Self::new(cdb[1] & 0x1f, cdb[2], be24(&cdb[3..6]), be24(&cdb[6..9]))
+    }
+}
+
+impl Cdb for ReadBuffer {
+    fn to_bytes(&self) -> CdbBuf {
+        let mut cdb = CdbBuf::with_opcode(10, Self::OPCODE);
+        cdb.buf[1] = self.mode;
+        cdb.buf[2] = self.buffer_id;
+        cdb.set(3, &self.buffer_offset.to_be_bytes()[1..]);
+        cdb.set(6, &self.allocation_length.to_be_bytes()[1..]);
+        cdb
+    }
+
+    fn direction(&self) -> DataDirection {
+        DataDirection::In
+    }
+
+    fn transfer_length(&self) -> usize {
+        self.allocation_length as usize
+    }
+}
+
+/// WRITE BUFFER.
+///
+/// As with [`ReadBuffer`], the 24-bit fields are validated by
+/// [`WriteBuffer::new`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct WriteBuffer {
+    mode: u8,
+    mode_specific: u8,
+    buffer_id: u8,
+    buffer_offset: u32,
+    parameter_list_length: u32,
+}
+
+impl WriteBuffer {
+    pub const OPCODE: u8 = 0x3b;
+
+    pub fn new(
+        mode: u8,
+        mode_specific: u8,
+        buffer_id: u8,
+        buffer_offset: u32,
+        parameter_list_length: u32,
+    ) -> Option<Self> {
+        if mode > 0x1f
+            || mode_specific > 0x07
+            || buffer_offset > 0xff_ffff
+            || parameter_list_length > 0xff_ffff
+        {
+            return None;
+        }
+
+        Some(Self {
+            mode,
+            mode_specific,
+            buffer_id,
+            buffer_offset,
+            parameter_list_length,
+        })
+    }
+
+    pub fn mode(&self) -> u8 {
+        self.mode
+    }
+
+    pub fn mode_specific(&self) -> u8 {
+        self.mode_specific
+    }
+
+    pub fn buffer_id(&self) -> u8 {
+        self.buffer_id
+    }
+
+    pub fn buffer_offset(&self) -> u32 {
+        self.buffer_offset
+    }
+
+    pub fn parameter_list_length(&self) -> u32 {
+        self.parameter_list_length
+    }
+
+    pub fn decode(cdb: &[u8]) -> Option<Self> {
+        expect(cdb, 10, Self::OPCODE, None)?;
+        Self::new(
+            cdb[1] & 0x1f,
+            cdb[1] >> 5,
+            cdb[2],
+            be24(&cdb[3..6]),
+            be24(&cdb[6..9]),
+        )
+    }
+}
+
+impl Cdb for WriteBuffer {
+    fn to_bytes(&self) -> CdbBuf {
+        let mut cdb = CdbBuf::with_opcode(10, Self::OPCODE);
+        cdb.buf[1] = self.mode_specific << 5 | self.mode;
+        cdb.buf[2] = self.buffer_id;
+        cdb.set(3, &self.buffer_offset.to_be_bytes()[1..]);
+        cdb.set(6, &self.parameter_list_length.to_be_bytes()[1..]);
+        cdb
+    }
+
+    fn direction(&self) -> DataDirection {
+        if self.parameter_list_length == 0 {
+            DataDirection::None
+        } else {
+            DataDirection::Out
+        }
+    }
+
+    fn transfer_length(&self) -> usize {
+        self.parameter_list_length as usize
+    }
+}
+
+/// Any of the SPC commands above, decoded from raw CDB bytes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SpcCdb {
+    TestUnitReady(TestUnitReady),
+    RequestSense(RequestSense),
+    Inquiry(Inquiry),
+    ModeSense6(ModeSense6),
+    ModeSense10(ModeSense10),
+    ModeSelect6(ModeSelect6),
+    ModeSelect10(ModeSelect10),
+    LogSense(LogSense),
+    LogSelect(LogSelect),
+    ReportLuns(ReportLuns),
+    ReportSupportedOpcodes(ReportSupportedOpcodes),
+    ReportSupportedTmfs(ReportSupportedTmfs),
+    ReadBuffer(ReadBuffer),
+    WriteBuffer(WriteBuffer),
+}
+
+impl SpcCdb {
+    /// Decode `cdb`, returning `None` if it is not one of the commands
+    /// above or is malformed.
+    pub fn decode(cdb: &[u8]) -> Option<Self> {
+        let cmd = match *cdb.first()? {
+            TestUnitReady::OPCODE => SpcCdb::TestUnitReady(TestUnitReady::decode(cdb)?),
+            RequestSense::OPCODE => SpcCdb::RequestSense(RequestSense::decode(cdb)?),
+            Inquiry::OPCODE => SpcCdb::Inquiry(Inquiry::decode(cdb)?),
+            ModeSense6::OPCODE => SpcCdb::ModeSense6(ModeSense6::decode(cdb)?),
+            ModeSense10::OPCODE => SpcCdb::ModeSense10(ModeSense10::decode(cdb)?),
+            ModeSelect6::OPCODE => SpcCdb::ModeSelect6(ModeSelect6::decode(cdb)?),
+            ModeSelect10::OPCODE => SpcCdb::ModeSelect10(ModeSelect10::decode(cdb)?),
+            LogSense::OPCODE => SpcCdb::LogSense(LogSense::decode(cdb)?),
+            LogSelect::OPCODE => SpcCdb::LogSelect(LogSelect::decode(cdb)?),
+            ReportLuns::OPCODE => SpcCdb::ReportLuns(ReportLuns::decode(cdb)?),
+            ReadBuffer::OPCODE => SpcCdb::ReadBuffer(ReadBuffer::decode(cdb)?),
+            WriteBuffer::OPCODE => SpcCdb::WriteBuffer(WriteBuffer::decode(cdb)?),
+            0xa3 => match cdb.get(1)? & 0x1f {
+                ReportSupportedOpcodes::SERVICE_ACTION => {
+                    SpcCdb::ReportSupportedOpcodes(ReportSupportedOpcodes::decode(cdb)?)
+                }
+                ReportSupportedTmfs::SERVICE_ACTION => {
+                    SpcCdb::ReportSupportedTmfs(ReportSupportedTmfs::decode(cdb)?)
+                }
+                _ => return None,
+            },
+            _ => return None,
+        };
+
+        Some(cmd)
+    }
+
+    fn inner(&self) -> &dyn Cdb {
+        match self {
+            SpcCdb::TestUnitReady(c) => c,
+            SpcCdb::RequestSense(c) => c,
+            SpcCdb::Inquiry(c) => c,
+            SpcCdb::ModeSense6(c) => c,
+            SpcCdb::ModeSense10(c) => c,
+            SpcCdb::ModeSelect6(c) => c,
+            SpcCdb::ModeSelect10(c) => c,
+            SpcCdb::LogSense(c) => c,
+            SpcCdb::LogSelect(c) => c,
+            SpcCdb::ReportLuns(c) => c,
+            SpcCdb::ReportSupportedOpcodes(c) => c,
+            SpcCdb::ReportSupportedTmfs(c) => c,
+            SpcCdb::ReadBuffer(c) => c,
+            SpcCdb::WriteBuffer(c) => c,
+        }
+    }
+}
+
+impl Cdb for SpcCdb {
+    fn to_bytes(&self) -> CdbBuf {
+        self.inner().to_bytes()
+    }
+
+    fn direction(&self) -> DataDirection {
+        self.inner().direction()
+    }
+
+    fn transfer_length(&self) -> usize {
+        self.inner().transfer_length()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Encode `cmd`, check the CDB is well formed and matches `bytes`, and
+    /// decode it again both directly and through [`SpcCdb`].
+    fn round_trip<C, D>(cmd: C, bytes: &[u8], decode: D) -> SpcCdb
+    where
+        C: Cdb + Copy + PartialEq + std::fmt::Debug,
+        D: Fn(&[u8]) -> Option<C>,
+    {
+        let cdb = cmd.to_bytes();
+        crate::cdb::validate(&cdb).unwrap();
+        assert_eq!(&*cdb, bytes);
+        assert_eq!(decode(&cdb), Some(cmd));
+
+        // Anything shorter, or with another opcode, is rejected.
+        assert_eq!(decode(&cdb[..cdb.len() - 1]), None);
+        let mut other = cdb;
+        other.buf[0] ^= 0x01;
+        assert_eq!(decode(&other), None);
+
+        let spc = SpcCdb::decode(&cdb).unwrap();
+        assert_eq!(spc.to_bytes(), cdb);
+        assert_eq!(spc.direction(), cmd.direction());
+        assert_eq!(spc.transfer_length(), cmd.transfer_length());
+        assert_eq!(SpcCdb::decode(&cdb[..cdb.len() - 1]), None);
+        spc
+    }
+
+    #[test]
+    fn test_unit_ready() {
+        let spc = round_trip(TestUnitReady, &[0; 6], TestUnitReady::decode);
+        assert_eq!(spc, SpcCdb::TestUnitReady(TestUnitReady));
+        assert_eq!(TestUnitReady.direction(), DataDirection::None);
+        assert_eq!(TestUnitReady.transfer_length(), 0);
+    }
+
+    #[test]
+    fn request_sense() {
+        let cmd = RequestSense::new(252);
+        round_trip(cmd, &[0x03, 0, 0, 0, 252, 0], RequestSense::decode);
+        assert_eq!(cmd.direction(), DataDirection::In);
+        assert_eq!(cmd.transfer_length(), 252);
+
+        let cmd = RequestSense { desc: true, ..cmd };
+        round_trip(cmd, &[0x03, 1, 0, 0, 252, 0], RequestSense::decode);
+    }
+
+    #[test]
+    fn inquiry() {
+        let cmd = Inquiry::standard(0x1234);
+        round_trip(cmd, &[0x12, 0, 0, 0x12, 0x34, 0], Inquiry::decode);
+        assert_eq!(cmd.transfer_length(), 0x1234);
+
+        let cmd = Inquiry::vpd(0x83, 252);
+        let spc = round_trip(cmd, &[0x12, 1, 0x83, 0, 252, 0], Inquiry::decode);
+        assert_eq!(spc, SpcCdb::Inquiry(cmd));
+        assert_eq!(cmd.direction(), DataDirection::In);
+    }
+
+    #[test]
+    fn mode_sense() {
+        let cmd = ModeSense6 {
+            dbd: true,
+            pc: PageControl::Saved,
+            subpage_code: 0xff,
+            ..ModeSense6::new(0x3f, 255)
+        };
+        round_trip(cmd, &[0x1a, 0x08, 0xff, 0xff, 255, 0], ModeSense6::decode);
+        round_trip(
+            ModeSense6::new(0x08, 36),
+            &[0x1a, 0, 0x08, 0, 36, 0],
+            ModeSense6::decode,
+        );
+        assert_eq!(cmd.direction(), DataDirection::In);
+        assert_eq!(cmd.transfer_length(), 255);
+
+        let cmd = ModeSense10 {
+            llbaa: true,
+            pc: PageControl::Changeable,
+            ..ModeSense10::new(0x0a, 0x1000)
+        };
+        round_trip(
+            cmd,
+            &[0x5a, 0x10, 0x4a, 0, 0, 0, 0, 0x10, 0x00, 0],
+            ModeSense10::decode,
+        );
+        let cmd = ModeSense10 {
+            dbd: true,
+            pc: PageControl::Default,
+            subpage_code: 1,
+            ..ModeSense10::new(0x1c, 0xffff)
+        };
+        round_trip(
+            cmd,
+            &[0x5a, 0x08, 0x9c, 1, 0, 0, 0, 0xff, 0xff, 0],
+            ModeSense10::decode,
+        );
+        assert_eq!(cmd.transfer_length(), 0xffff);
+    }
+
+    #[test]
+    fn mode_select() {
+        let cmd = ModeSelect6::new(28);
+        round_trip(cmd, &[0x15, 0x10, 0, 0, 28, 0], ModeSelect6::decode);
+        assert_eq!(cmd.direction(), DataDirection::Out);
+        assert_eq!(cmd.transfer_length(), 28);
+
+        let cmd = ModeSelect6 {
+            pf: false,
+            sp: true,
+            parameter_list_length: 0,
+        };
+        round_trip(cmd, &[0x15, 0x01, 0, 0, 0, 0], ModeSelect6::decode);
+        assert_eq!(cmd.direction(), DataDirection::None);
+
+        let cmd = ModeSelect10 {
+            sp: true,
+            ..ModeSelect10::new(0x0123)
+        };
+        round_trip(
+            cmd,
+            &[0x55, 0x11, 0, 0, 0, 0, 0, 0x01, 0x23, 0],
+            ModeSelect10::decode,
+        );
+        assert_eq!(cmd.direction(), DataDirection::Out);
+        assert_eq!(ModeSelect10::new(0).direction(), DataDirection::None);
+    }
+
+    #[test]
+    fn log_sense() {
+        let cmd = LogSense::new(0x0d, 0x200);
+        round_trip(
+            cmd,
+            &[0x4d, 0, 0x4d, 0, 0, 0, 0, 0x02, 0x00, 0],
+            LogSense::decode,
+        );
+        assert_eq!(cmd.direction(), DataDirection::In);
+        assert_eq!(cmd.transfer_length(), 0x200);
+
+        let cmd = LogSense {
+            ppc: true,
+            sp: true,
+            pc: 3,
+            subpage_code: 0xff,
+            parameter_pointer: 0xabcd,
+            ..LogSense::new(0x3f, 0xffff)
+        };
+        round_trip(
+            cmd,
+            &[0x4d, 0x03, 0xff, 0xff, 0, 0xab, 0xcd, 0xff, 0xff, 0],
+            LogSense::decode,
+        );
+    }
+
+    #[test]
+    fn log_select() {
+        let cmd = LogSelect {
+            pcr: true,
+            pc: 3,
+            ..LogSelect::new(0, 0)
+        };
+        round_trip(
+            cmd,
+            &[0x4c, 0x02, 0xc0, 0, 0, 0, 0, 0, 0, 0],
+            LogSelect::decode,
+        );
+        assert_eq!(cmd.direction(), DataDirection::None);
+
+        let cmd = LogSelect {
+            sp: true,
+            subpage_code: 2,
+            ..LogSelect::new(0x18, 0x40)
+        };
+        round_trip(
+            cmd,
+            &[0x4c, 0x01, 0x58, 2, 0, 0, 0, 0, 0x40, 0],
+            LogSelect::decode,
+        );
+        assert_eq!(cmd.direction(), DataDirection::Out);
+        assert_eq!(cmd.transfer_length(), 0x40);
+    }
+
+    #[test]
+    fn report_luns() {
+        let cmd = ReportLuns {
+            select_report: 2,
+            ..ReportLuns::new(0x0102_0304)
+        };
+        round_trip(
+            cmd,
+            &[0xa0, 0, 2, 0, 0, 0, 0x01, 0x02, 0x03, 0x04, 0, 0],
+            ReportLuns::decode,
+        );
+        assert_eq!(cmd.direction(), DataDirection::In);
+        assert_eq!(cmd.transfer_length(), 0x0102_0304);
+    }
+
+    #[test]
+    fn maintenance_in() {
+        let opcodes = ReportSupportedOpcodes {
+            rctd: true,
+            reporting_options: 2,
+            requested_opcode: 0x9e,
+            requested_service_action: 0x0010,
+            ..ReportSupportedOpcodes::all(0x2000)
+        };
+        let spc = round_trip(
+            opcodes,
+            &[0xa3, 0x0c, 0x82, 0x9e, 0x00, 0x10, 0, 0, 0x20, 0x00, 0, 0],
+            ReportSupportedOpcodes::decode,
+        );
+        assert_eq!(spc, SpcCdb::ReportSupportedOpcodes(opcodes));
+
+        let tmfs = ReportSupportedTmfs {
+            repd: true,
+            ..ReportSupportedTmfs::new(16)
+        };
+        let spc = round_trip(
+            tmfs,
+            &[0xa3, 0x0d, 0x80, 0, 0, 0, 0, 0, 0, 16, 0, 0],
+            ReportSupportedTmfs::decode,
+        );
+        assert_eq!(spc, SpcCdb::ReportSupportedTmfs(tmfs));
+
+        // Each decoder only takes its own service action, and others are
+        // not SPC commands this module knows.
+        let cdb = opcodes.to_bytes();
+        assert_eq!(ReportSupportedTmfs::decode(&cdb), None);
+        assert_eq!(ReportSupportedOpcodes::decode(&tmfs.to_bytes()), None);
+        let mut other = cdb;
+        other.buf[1] = 0x0a;
+        assert_eq!(SpcCdb::decode(&other), None);
+        assert_eq!(SpcCdb::decode(&[0xa3]), None);
+    }
+
+    #[test]
+    fn read_buffer() {
+        let cmd = ReadBuffer::new(0x1c, 3, 0x12_3456, 0xff_ffff).unwrap();
+        round_trip(
+            cmd,
+            &[0x3c, 0x1c, 3, 0x12, 0x34, 0x56, 0xff, 0xff, 0xff, 0],
+            ReadBuffer::decode,
+        );
+        assert_eq!(cmd.direction(), DataDirection::In);
+        assert_eq!(cmd.transfer_length(), 0xff_ffff);
+        assert_eq!(
+            (cmd.mode(), cmd.buffer_id(), cmd.buffer_offset()),
+            (0x1c, 3, 0x12_3456)
+        );
+        assert_eq!(cmd.allocation_length(), 0xff_ffff);
+
+        assert_eq!(ReadBuffer::new(0x20, 0, 0, 0), None);
+        assert_eq!(ReadBuffer::new(0, 0, 0x100_0000, 0), None);
+        assert_eq!(ReadBuffer::new(0, 0, 0, 0x100_0000), None);
+    }
+
+    #[test]
+    fn write_buffer() {
+        let cmd = WriteBuffer::new(0x0e, 0x05, 1, 0x01_0000, 0x20).unwrap();
+        round_trip(
+            cmd,
+            &[0x3b, 0xae, 1, 0x01, 0x00, 0x00, 0x00, 0x00, 0x20, 0],
+            WriteBuffer::decode,
+        );
+        assert_eq!(cmd.direction(), DataDirection::Out);
+        assert_eq!(cmd.transfer_length(), 0x20);
+        assert_eq!(
+            (cmd.mode(), cmd.mode_specific(), cmd.buffer_id()),
+            (0x0e, 5, 1)
+        );
+        assert_eq!(
+            (cmd.buffer_offset(), cmd.parameter_list_length()),
+            (0x01_0000, 0x20)
+        );
+
+        let cmd = WriteBuffer::new(0x1f, 0, 0, 0, 0).unwrap();
+        round_trip(
+            cmd,
+            &[0x3b, 0x1f, 0, 0, 0, 0, 0, 0, 0, 0],
+            WriteBuffer::decode,
+        );
+        assert_eq!(cmd.direction(), DataDirection::None);
+
+        assert_eq!(WriteBuffer::new(0x20, 0, 0, 0, 0), None);
+        assert_eq!(WriteBuffer::new(0, 0x08, 0, 0, 0), None);
+        assert_eq!(WriteBuffer::new(0, 0, 0, 0x100_0000, 0), None);
+        assert_eq!(WriteBuffer::new(0, 0, 0, 0, 0x100_0000), None);
+    }
+
+    #[test]
+    fn decode_rejects_others() {
+        assert_eq!(SpcCdb::decode(&[]), None);
+        // READ(10) is an SBC command.
+        assert_eq!(SpcCdb::decode(&[0x28, 0, 0, 0, 0, 0, 0, 0, 1, 0]), None);
+        assert_eq!(SpcCdb::decode(&[0x12, 0, 0, 0]), None);
+    }
+}
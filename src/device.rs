@@ -0,0 +1,845 @@
+/*
+ * Copyright 2025 Jason King
+ */
+
+use crate::cdb::{Cdb, Inquiry, ReadCapacity10, ReadCapacity16};
+use crate::retry::RetryState;
+use crate::trace;
+use crate::{
+    BufPool, Capacity, Command, CommandResult, DataBuffer, DataDirection, Flags, IoVecMut,
+    NativeTransport, RetryPolicy, ScsiError, StandardInquiry, Timeout, TransferLimits, Transport,
+    INQUIRY_MIN_LEN,
+};
+use std::io::{IoSlice, IoSliceMut};
+use std::os::fd::{AsFd, AsRawFd, BorrowedFd, OwnedFd, RawFd};
+use std::path::Path;
+
+/// The size of the sense buffer used when the caller does not supply one.
+const SENSE_LEN: usize = 252;
+
+/// A handle to a SCSI device reached through some [`Transport`].
+///
+/// Unlike the free functions in the crate root, every method here is safe:
+/// the CDB, data and sense buffers are borrowed for the duration of the
+/// (synchronous) submission, so they cannot be freed or moved while the
+/// transport is using them.
+///
+/// Commands issued through [`read`](Self::read), [`write`](Self::write) and
+/// [`no_data`](Self::no_data) that complete with a non-GOOD status fail with
+/// [`ScsiError::Status`], carrying the parsed sense data (which is fetched
+/// into an internal buffer if the caller did not supply one).
+#[derive(Debug)]
+pub struct Device<T: Transport = NativeTransport> {
+    transport: T,
+    pub(crate) limits: Option<TransferLimits>,
+    pub(crate) pool: BufPool,
+    retry: RetryPolicy,
+    last_retries: u32,
+    path_instance: Option<u64>,
+}
+
+impl Device<NativeTransport> {
+    /// Open a device node using the host's [`NativeTransport`]: `SG_IO` on
+    /// Linux, `USCSICMD` elsewhere.
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self, std::io::Error> {
+        Ok(Self::new(NativeTransport::open(path)?))
+    }
+}
+
+impl<T: Transport> Device<T> {
+    pub fn new(transport: T) -> Self {
+        Self {
+            transport,
+            limits: None,
+            pool: BufPool::default(),
+            retry: RetryPolicy::none(),
+            last_retries: 0,
+            path_instance: None,
+        }
+    }
+
+    /// Apply `policy` to commands issued through this handle (other than
+    /// [`submit`](Self::submit)). The default is to never retry.
+    pub fn with_retry_policy(mut self, policy: RetryPolicy) -> Self {
+        self.retry = policy;
+        self
+    }
+
+    pub fn set_retry_policy(&mut self, policy: RetryPolicy) {
+        self.retry = policy;
+    }
+
+    pub fn retry_policy(&self) -> &RetryPolicy {
+        &self.retry
+    }
+
+    /// Send commands issued through this handle down a specific multipath
+    /// path (`USCSI_PATH_INSTANCE`), or clear the setting with `None`.
+    pub fn set_path_instance(&mut self, path_instance: Option<u64>) {
+        self.path_instance = path_instance;
+    }
+
+    pub fn path_instance(&self) -> Option<u64> {
+        self.path_instance
+    }
+
+    /// The number of retries the most recent command needed, whether or
+    /// not it eventually succeeded.
+    pub fn last_retries(&self) -> u32 {
+        self.last_retries
+    }
+
+    pub fn transport(&self) -> &T {
+        &self.transport
+    }
+
+    pub fn transport_mut(&mut self) -> &mut T {
+        &mut self.transport
+    }
+
+    pub fn into_transport(self) -> T {
+        self.transport
+    }
+
+    /// Submit an arbitrary command. Unlike the other methods, a non-GOOD
+    /// status is returned in the [`CommandResult`] rather than as an error.
+    pub fn submit(&mut self, cmd: &mut Command<'_>) -> Result<CommandResult, ScsiError> {
+        trace::submit(&mut self.transport, cmd)
+    }
+
+    fn issue(&mut self, cmd: Command<'_>) -> Result<CommandResult, ScsiError> {
+        let mut local = [0u8; SENSE_LEN];
+        let mut cmd = match cmd.sense {
+            Some(_) => cmd,
+            None => Command {
+                sense: Some(&mut local),
+                ..cmd
+            },
+        };
+
+        if cmd.path_instance.is_none() {
+            cmd.path_instance = self.path_instance;
+        }
+
+        let mut state = RetryState::new();
+        loop {
+            state.attempts += 1;
+            let err = match trace::submit(&mut self.transport, &mut cmd)
+                .and_then(|result| result.check(cmd.sense.as_deref()))
+            {
+                Ok(result) => {
+                    self.last_retries = state.retries();
+                    return Ok(CommandResult {
+                        retries: state.retries(),
+                        ..result
+                    });
+                }
+                Err(e) => e,
+            };
+
+            match self.retry.next(&mut state, cmd.cdb, &err) {
+                Some(delay) if delay.is_zero() => {}
+                Some(delay) => std::thread::sleep(delay),
+                None => {
+                    self.last_retries = state.retries();
+                    return Err(err);
+                }
+            }
+        }
+    }
+
+    /// Issue a data-in command, returning its status and residuals.
+    pub fn read(
+        &mut self,
+        cdb: &[u8],
+        data: &mut [u8],
+        sense: Option<&mut [u8]>,
+        flags: Flags,
+        timeout: Timeout,
+    ) -> Result<CommandResult, ScsiError> {
+        self.issue(Command {
+            cdb,
+            data: DataBuffer::In(data),
+            sense,
+            flags,
+            timeout,
+            path_instance: None,
+        })
+    }
+
+    /// Issue a data-out command, returning its status and residuals.
+    pub fn write(
+        &mut self,
+        cdb: &[u8],
+        data: &[u8],
+        sense: Option<&mut [u8]>,
+        flags: Flags,
+        timeout: Timeout,
+    ) -> Result<CommandResult, ScsiError> {
+        self.issue(Command {
+            cdb,
+            data: DataBuffer::Out(data),
+            sense,
+            flags,
+            timeout,
+            path_instance: None,
+        })
+    }
+
+    /// Issue a data-in command whose data is scattered across `bufs`, in
+    /// order.
+    pub fn read_vectored(
+        &mut self,
+        cdb: &[u8],
+        bufs: &mut [IoSliceMut<'_>],
+        sense: Option<&mut [u8]>,
+        flags: Flags,
+        timeout: Timeout,
+    ) -> Result<CommandResult, ScsiError> {
+        self.issue(Command {
+            cdb,
+            data: DataBuffer::InVectored(IoVecMut::new(bufs)),
+            sense,
+            flags,
+            timeout,
+            path_instance: None,
+        })
+    }
+
+    /// Issue a data-out command whose data is gathered from `bufs`, in
+    /// order, without first copying them into one buffer (unless the
+    /// transport requires it).
+    pub fn write_vectored(
+        &mut self,
+        cdb: &[u8],
+        bufs: &[IoSlice<'_>],
+        sense: Option<&mut [u8]>,
+        flags: Flags,
+        timeout: Timeout,
+    ) -> Result<CommandResult, ScsiError> {
+        self.issue(Command {
+            cdb,
+            data: DataBuffer::OutVectored(bufs),
+            sense,
+            flags,
+            timeout,
+            path_instance: None,
+        })
+    }
+
+    /// Issue a command that transfers no data (e.g. TEST UNIT READY),
+    /// returning its status and sense residual.
+    pub fn no_data(
+        &mut self,
+        cdb: &[u8],
+        sense: Option<&mut [u8]>,
+        flags: Flags,
+        timeout: Timeout,
+    ) -> Result<CommandResult, ScsiError> {
+        self.issue(Command {
+            cdb,
+            data: DataBuffer::None,
+            sense,
+            flags,
+            timeout,
+            path_instance: None,
+        })
+    }
+
+    /// Issue a command that sends `data_out` to the device and receives
+    /// `data_in` from it (e.g. XDWRITEREAD). Not every transport supports
+    /// this; those that do not fail with [`ScsiError::Unsupported`].
+    pub fn bidirectional(
+        &mut self,
+        cdb: &[u8],
+        data_out: &[u8],
+        data_in: &mut [u8],
+        sense: Option<&mut [u8]>,
+        flags: Flags,
+        timeout: Timeout,
+    ) -> Result<CommandResult, ScsiError> {
+        self.issue(Command {
+            cdb,
+            data: DataBuffer::Bidirectional { data_out, data_in },
+            sense,
+            flags,
+            timeout,
+            path_instance: None,
+        })
+    }
+
+    /// Issue a no-data or data-in command built by one of the
+    /// [`crate::cdb`] builders. The length of the transfer is taken from
+    /// `cdb`; `data` must be at least [`Cdb::transfer_length`] bytes long.
+    /// Data-out commands are issued with [`Device::execute_out`].
+    pub fn execute<C: Cdb + ?Sized>(
+        &mut self,
+        cdb: &C,
+        data: &mut [u8],
+        timeout: Timeout,
+    ) -> Result<CommandResult, ScsiError> {
+        let len = transfer_length(cdb, data.len())?;
+        let bytes = cdb.to_bytes();
+        match cdb.direction() {
+            DataDirection::None => self.no_data(&bytes, None, Flags::empty(), timeout),
+            DataDirection::In => self.read(&bytes, &mut data[..len], None, Flags::empty(), timeout),
+            DataDirection::Out => Err(ScsiError::InvalidCommand(
+                "data-out commands must be issued with Device::execute_out",
+            )),
+            DataDirection::Bidirectional => Err(ScsiError::InvalidCommand(
+                "bidirectional commands must be issued with Device::bidirectional",
+            )),
+        }
+    }
+
+    /// Issue a no-data or data-out command built by one of the
+    /// [`crate::cdb`] builders, sending the first
+    /// [`Cdb::transfer_length`] bytes of `data`.
+    pub fn execute_out<C: Cdb + ?Sized>(
+        &mut self,
+        cdb: &C,
+        data: &[u8],
+        timeout: Timeout,
+    ) -> Result<CommandResult, ScsiError> {
+        let len = transfer_length(cdb, data.len())?;
+        let bytes = cdb.to_bytes();
+        match cdb.direction() {
+            DataDirection::None => self.no_data(&bytes, None, Flags::empty(), timeout),
+            DataDirection::Out => self.write(&bytes, &data[..len], None, Flags::empty(), timeout),
+            DataDirection::In => Err(ScsiError::InvalidCommand(
+                "data-in commands must be issued with Device::execute",
+            )),
+            DataDirection::Bidirectional => Err(ScsiError::InvalidCommand(
+                "bidirectional commands must be issued with Device::bidirectional",
+            )),
+        }
+    }
+
+    /// Issue INQUIRY and decode the standard INQUIRY data. If the device
+    /// has more data than fit in the first buffer, the command is reissued
+    /// with a large enough allocation length.
+    pub fn inquiry(&mut self) -> Result<StandardInquiry, ScsiError> {
+        let mut len = 96;
+        loop {
+            let mut buf = vec![0u8; len];
+            let result =
+                self.execute(&Inquiry::standard(len as u16), &mut buf, Timeout::DEFAULT)?;
+            let actual = len.saturating_sub(result.resid);
+
+            let inq = StandardInquiry::parse(&buf[..actual]).ok_or(ScsiError::ShortTransfer {
+                expected: INQUIRY_MIN_LEN,
+                actual,
+            })?;
+            if inq.total_length() <= len || len > 96 {
+                return Ok(inq);
+            }
+            len = inq.total_length();
+        }
+    }
+
+    /// Fetch VPD page `page`, reissuing INQUIRY with a larger allocation
+    /// length if the page does not fit in the first buffer. Decode the
+    /// result with one of the parsers in [`crate::vpd`].
+    pub fn vpd_page(&mut self, page: u8) -> Result<Vec<u8>, ScsiError> {
+        let mut len = 256;
+        loop {
+            let mut buf = vec![0u8; len];
+            let result =
+                self.execute(&Inquiry::vpd(page, len as u16), &mut buf, Timeout::DEFAULT)?;
+            let actual = len.saturating_sub(result.resid);
+            if actual < 4 {
+                return Err(ScsiError::ShortTransfer {
+                    expected: 4,
+                    actual,
+                });
+            }
+
+            let total = 4 + u16::from_be_bytes([buf[2], buf[3]]) as usize;
+            if total <= len || len > 256 {
+                buf.truncate(actual.min(total));
+                return Ok(buf);
+            }
+            len = total.min(u16::MAX as usize);
+        }
+    }
+
+    /// Query the capacity of the logical unit.
+    ///
+    /// READ CAPACITY(10) is issued first. If it reports a last LBA of
+    /// 0xFFFFFFFF the device is too large for it, and READ CAPACITY(16) is
+    /// required. Otherwise READ CAPACITY(16) is still attempted for the
+    /// provisioning and protection details, but a device that rejects it
+    /// gets the READ CAPACITY(10) values.
+    pub fn capacity(&mut self) -> Result<Capacity, ScsiError> {
+        let mut buf = [0u8; ReadCapacity10::DATA_LEN];
+        let result = self.execute(&ReadCapacity10, &mut buf, Timeout::DEFAULT)?;
+        let short = |result: CommandResult, expected: usize| ScsiError::ShortTransfer {
+            expected,
+            actual: expected.saturating_sub(result.resid),
+        };
+        let cap10 = Capacity::parse10(&buf[..buf.len().saturating_sub(result.resid)])
+            .ok_or_else(|| short(result, ReadCapacity10::DATA_LEN))?;
+
+        let mut buf = [0u8; ReadCapacity16::DATA_LEN];
+        let cap16 = self
+            .execute(&ReadCapacity16::default(), &mut buf, Timeout::DEFAULT)
+            .and_then(|result| {
+                Capacity::parse16(&buf[..buf.len().saturating_sub(result.resid)])
+                    .ok_or_else(|| short(result, ReadCapacity16::DATA_LEN))
+            });
+
+        match cap16 {
+            Ok(cap) => Ok(cap),
+            Err(e) if cap10.last_lba == u32::MAX as u64 => Err(e),
+            Err(ScsiError::Status { .. } | ScsiError::ShortTransfer { .. }) => Ok(cap10),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Reset the target.
+    pub fn reset(&mut self) -> Result<(), ScsiError> {
+        self.transport.reset()
+    }
+
+    /// The maximum transfer size (in bytes) supported for a single command.
+    pub fn max_xfer(&mut self) -> Result<usize, ScsiError> {
+        self.transport.max_xfer()
+    }
+}
+
+/// The transfer length of `cdb`, checking that a buffer of `available`
+/// bytes can hold it.
+fn transfer_length<C: Cdb + ?Sized>(cdb: &C, available: usize) -> Result<usize, ScsiError> {
+    let len = cdb.transfer_length();
+    if available < len {
+        return Err(ScsiError::InvalidCommand(
+            "data buffer is shorter than the transfer length",
+        ));
+    }
+    Ok(len)
+}
+
+impl<T: Transport> From<T> for Device<T> {
+    fn from(transport: T) -> Self {
+        Self::new(transport)
+    }
+}
+
+impl From<OwnedFd> for Device<NativeTransport> {
+    fn from(fd: OwnedFd) -> Self {
+        Self::new(fd.into())
+    }
+}
+
+impl<T: Transport + AsFd> AsFd for Device<T> {
+    fn as_fd(&self) -> BorrowedFd<'_> {
+        self.transport.as_fd()
+    }
+}
+
+impl<T: Transport + AsRawFd> AsRawFd for Device<T> {
+    fn as_raw_fd(&self) -> RawFd {
+        self.transport.as_raw_fd()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cdb::{CdbBuf, Read, TestUnitReady, Write, WriteBuffer};
+    use crate::{Emulator, Fault, FaultInjector, FaultRule, ScsiStatus};
+    use std::time::Duration;
+
+    /// A transport that records the CDBs sent to it and completes each with
+    /// `handler`.
+    struct Mock<F> {
+        handler: F,
+        cdbs: Vec<Vec<u8>>,
+    }
+
+    fn mock<F>(handler: F) -> Device<Mock<F>>
+    where
+        F: FnMut(&mut Command<'_>) -> Result<CommandResult, ScsiError>,
+    {
+        Device::new(Mock {
+            handler,
+            cdbs: Vec::new(),
+        })
+    }
+
+    impl<F> Transport for Mock<F>
+    where
+        F: FnMut(&mut Command<'_>) -> Result<CommandResult, ScsiError>,
+    {
+        fn submit(&mut self, cmd: &mut Command<'_>) -> Result<CommandResult, ScsiError> {
+            self.cdbs.push(cmd.cdb.to_vec());
+            (self.handler)(cmd)
+        }
+    }
+
+    /// Complete `cmd` with GOOD status, returning as much of `data` as
+    /// fits.
+    fn serve(cmd: &mut Command<'_>, data: &[u8]) -> CommandResult {
+        let n = cmd.data.fill_input(data);
+        CommandResult {
+            resid: cmd.data.input_len() - n,
+            ..Default::default()
+        }
+    }
+
+    /// Standard INQUIRY data `len` bytes long, reporting `total` bytes
+    /// available.
+    fn inquiry_data(len: usize, total: usize) -> Vec<u8> {
+        let mut buf = vec![0u8; len];
+        buf[2] = 0x07;
+        buf[3] = 0x02;
+        buf[4] = (total - 5) as u8;
+        buf[8..36].copy_from_slice(b"ACME    Widget          1.0 ");
+        buf
+    }
+
+    fn allocation_lengths<F>(dev: &Device<Mock<F>>) -> Vec<u16>
+    where
+        F: FnMut(&mut Command<'_>) -> Result<CommandResult, ScsiError>,
+    {
+        dev.transport()
+            .cdbs
+            .iter()
+            .map(|cdb| {
+                assert_eq!(cdb[0], Inquiry::OPCODE);
+                u16::from_be_bytes([cdb[3], cdb[4]])
+            })
+            .collect()
+    }
+
+    #[test]
+    fn inquiry_reissues_for_long_data() {
+        let data = inquiry_data(200, 200);
+        let mut dev = mock(|cmd| Ok(serve(cmd, &data)));
+        let inq = dev.inquiry().unwrap();
+        assert_eq!(allocation_lengths(&dev), [96, 200]);
+        assert_eq!(inq.total_length(), 200);
+        assert_eq!(inq.vendor, "ACME");
+        assert_eq!(inq.product, "Widget");
+    }
+
+    #[test]
+    fn inquiry_short_data() {
+        // Everything fits in the first buffer.
+        let data = inquiry_data(96, 96);
+        let mut dev = mock(|cmd| Ok(serve(cmd, &data)));
+        assert_eq!(dev.inquiry().unwrap().total_length(), 96);
+        assert_eq!(allocation_lengths(&dev), [96]);
+
+        let data = inquiry_data(36, 36);
+        let mut dev = mock(|cmd| Ok(serve(cmd, &data)));
+        assert_eq!(dev.inquiry().unwrap().total_length(), 36);
+        assert_eq!(allocation_lengths(&dev), [96]);
+
+        // Too little data to decode at all.
+        let mut dev = mock(|cmd| Ok(serve(cmd, &[0; 20])));
+        assert!(matches!(
+            dev.inquiry(),
+            Err(ScsiError::ShortTransfer {
+                expected: INQUIRY_MIN_LEN,
+                actual: 20
+            })
+        ));
+    }
+
+    #[test]
+    fn inquiry_reissues_once() {
+        // A device whose additional length keeps growing is only asked
+        // twice.
+        let mut total = 150;
+        let mut dev = mock(|cmd| {
+            let data = inquiry_data(total, total);
+            total += 50;
+            Ok(serve(cmd, &data))
+        });
+        let inq = dev.inquiry().unwrap();
+        assert_eq!(allocation_lengths(&dev), [96, 150]);
+        assert_eq!(inq.total_length(), 200);
+    }
+
+    /// READ CAPACITY(10) data.
+    fn rc10_data(last_lba: u32) -> Vec<u8> {
+        [last_lba.to_be_bytes(), 512u32.to_be_bytes()].concat()
+    }
+
+    /// READ CAPACITY(16) data, `len` bytes of it.
+    fn rc16_data(last_lba: u64, len: usize) -> Vec<u8> {
+        let mut buf = [0u8; 32];
+        buf[0..8].copy_from_slice(&last_lba.to_be_bytes());
+        buf[8..12].copy_from_slice(&4096u32.to_be_bytes());
+        buf[13] = 0x01;
+        buf[..len].to_vec()
+    }
+
+    fn rejected() -> ScsiError {
+        ScsiError::Status {
+            status: ScsiStatus::CheckCondition,
+            sense: None,
+        }
+    }
+
+    /// A device answering READ CAPACITY(10) with `rc10` and READ
+    /// CAPACITY(16) with `rc16`.
+    fn capacity_device(
+        rc10: Vec<u8>,
+        rc16: impl Fn() -> Result<Vec<u8>, ScsiError>,
+    ) -> Result<Capacity, ScsiError> {
+        let mut dev = mock(|cmd| match cmd.cdb[0] {
+            0x25 => Ok(serve(cmd, &rc10)),
+            0x9e => rc16().map(|data| serve(cmd, &data)),
+            op => panic!("unexpected opcode {op:#04x}"),
+        });
+        let cap = dev.capacity();
+        let opcodes: Vec<u8> = dev.transport().cdbs.iter().map(|cdb| cdb[0]).collect();
+        assert_eq!(opcodes, [0x25, 0x9e]);
+        cap
+    }
+
+    #[test]
+    fn capacity_prefers_rc16() {
+        let cap = capacity_device(rc10_data(0xff), || Ok(rc16_data(0xff, 32))).unwrap();
+        assert_eq!(cap.last_lba, 0xff);
+        assert_eq!(cap.block_size, 4096);
+        assert_eq!(cap.physical_block_exponent, 1);
+
+        // Large devices need READ CAPACITY(16).
+        let cap =
+            capacity_device(rc10_data(u32::MAX), || Ok(rc16_data(0x1_0000_0000, 32))).unwrap();
+        assert_eq!(cap.last_lba, 0x1_0000_0000);
+        assert_eq!(cap.blocks(), 0x1_0000_0001);
+    }
+
+    #[test]
+    fn capacity_falls_back_to_rc10() {
+        let rc10 = Capacity::parse10(&rc10_data(0xff)).unwrap();
+        let cap = capacity_device(rc10_data(0xff), || Err(rejected())).unwrap();
+        assert_eq!(cap, rc10);
+        let cap = capacity_device(rc10_data(0xff), || Ok(rc16_data(0xff, 11))).unwrap();
+        assert_eq!(cap, rc10);
+
+        // Other failures are not hidden.
+        assert!(matches!(
+            capacity_device(rc10_data(0xff), || Err(ScsiError::Timeout)),
+            Err(ScsiError::Timeout)
+        ));
+    }
+
+    #[test]
+    fn capacity_requires_rc16_for_large_devices() {
+        assert!(matches!(
+            capacity_device(rc10_data(u32::MAX), || Err(rejected())),
+            Err(ScsiError::Status {
+                status: ScsiStatus::CheckCondition,
+                ..
+            })
+        ));
+        assert!(matches!(
+            capacity_device(rc10_data(u32::MAX), || Ok(rc16_data(0, 8))),
+            Err(ScsiError::ShortTransfer {
+                expected: 32,
+                actual: 8
+            })
+        ));
+    }
+
+    #[test]
+    fn capacity_short_rc10() {
+        let mut dev = mock(|cmd| Ok(serve(cmd, &[0; 6])));
+        assert!(matches!(
+            dev.capacity(),
+            Err(ScsiError::ShortTransfer {
+                expected: 8,
+                actual: 6
+            })
+        ));
+        assert_eq!(dev.transport().cdbs.len(), 1);
+    }
+
+    /// A READ that claims to transfer in both directions.
+    struct Bidirectional(Read);
+
+    impl Cdb for Bidirectional {
+        fn to_bytes(&self) -> CdbBuf {
+            self.0.to_bytes()
+        }
+
+        fn direction(&self) -> DataDirection {
+            DataDirection::Bidirectional
+        }
+
+        fn transfer_length(&self) -> usize {
+            self.0.transfer_length()
+        }
+    }
+
+    #[test]
+    fn execute() {
+        let mut dev = Device::new(Emulator::new(512, 64));
+        let write = Write::new(3, 2, 512);
+        let data: Vec<u8> = (0..1024).map(|i| i as u8).collect();
+        assert_eq!(
+            dev.execute_out(&write, &data, Timeout::DEFAULT)
+                .unwrap()
+                .resid,
+            0
+        );
+
+        // Only the transfer length of a larger buffer is used.
+        let mut buf = [0xa5; 1536];
+        let read = Read::new(3, 2, 512);
+        assert_eq!(
+            dev.execute(&read, &mut buf, Timeout::DEFAULT)
+                .unwrap()
+                .resid,
+            0
+        );
+        assert_eq!(buf[..1024], data);
+        assert_eq!(buf[1024..], [0xa5; 512]);
+
+        dev.execute(&TestUnitReady, &mut [], Timeout::DEFAULT)
+            .unwrap();
+        dev.execute_out(&TestUnitReady, &[], Timeout::DEFAULT)
+            .unwrap();
+    }
+
+    #[test]
+    fn execute_rejects() {
+        let mut dev = mock(|_| panic!("command sent"));
+        let read = Read::new(0, 2, 512);
+        let write = Write::new(0, 2, 512);
+        let invalid = |r| matches!(r, Err(ScsiError::InvalidCommand(_)));
+
+        // Buffers shorter than the transfer length.
+        assert!(invalid(dev.execute(
+            &read,
+            &mut [0; 1023],
+            Timeout::DEFAULT
+        )));
+        assert!(invalid(dev.execute_out(
+            &write,
+            &[0; 1023],
+            Timeout::DEFAULT
+        )));
+
+        // The wrong direction.
+        assert!(invalid(dev.execute(
+            &write,
+            &mut [0; 1024],
+            Timeout::DEFAULT
+        )));
+        assert!(invalid(dev.execute_out(
+            &read,
+            &[0; 1024],
+            Timeout::DEFAULT
+        )));
+        let bidi = Bidirectional(read);
+        assert!(invalid(dev.execute(
+            &bidi,
+            &mut [0; 1024],
+            Timeout::DEFAULT
+        )));
+        assert!(invalid(dev.execute_out(
+            &bidi,
+            &[0; 1024],
+            Timeout::DEFAULT
+        )));
+
+        assert!(dev.transport().cdbs.is_empty());
+    }
+
+    fn retrying<R>(rules: R) -> Device<FaultInjector<Emulator>>
+    where
+        R: IntoIterator<Item = FaultRule>,
+    {
+        let mut injector = FaultInjector::new(Emulator::new(512, 64));
+        for rule in rules {
+            injector.add_rule(rule);
+        }
+        let mut policy = RetryPolicy::default();
+        // Retry without sleeping.
+        policy.initial_backoff = Duration::ZERO;
+        Device::new(injector).with_retry_policy(policy)
+    }
+
+    #[test]
+    fn retries_busy() {
+        let busy = FaultRule::new(Fault::Status(ScsiStatus::Busy)).times(2);
+        let mut dev = retrying([busy.clone()]);
+        let cdb = Read::new(0, 1, 512).to_bytes();
+        let mut buf = [0u8; 512];
+        let result = dev
+            .read(&cdb, &mut buf, None, Flags::empty(), Timeout::DEFAULT)
+            .unwrap();
+        assert_eq!(result.status, ScsiStatus::Good);
+        assert_eq!(result.retries, 2);
+        assert_eq!(dev.last_retries(), 2);
+        assert_eq!(dev.transport().commands(), 3);
+        assert_eq!(dev.transport().injected(), 2);
+
+        // Block transfers report the retries of all their commands.
+        let mut dev = retrying([]);
+        dev.transfer_limits().unwrap();
+        dev.transport_mut().add_rule(busy);
+        let result = dev.write_blocks(0, &[0xa5; 2048]).unwrap();
+        assert_eq!(result.retries, 2);
+
+        // Retries stop at the rule's limit.
+        let mut dev = retrying([FaultRule::new(Fault::Status(ScsiStatus::Busy))]);
+        let e = dev
+            .read(&cdb, &mut buf, None, Flags::empty(), Timeout::DEFAULT)
+            .unwrap_err();
+        assert!(matches!(
+            e,
+            ScsiError::Status {
+                status: ScsiStatus::Busy,
+                ..
+            }
+        ));
+        assert_eq!(dev.last_retries(), 5);
+        assert_eq!(dev.transport().commands(), 6);
+    }
+
+    #[test]
+    fn no_retry_for_non_idempotent() {
+        let busy = FaultRule::new(Fault::Status(ScsiStatus::Busy)).times(2);
+        let mut dev = retrying([busy.clone()]);
+        let cdb = WriteBuffer::new(0x02, 0, 0, 0, 512).unwrap().to_bytes();
+        let e = dev
+            .write(&cdb, &[0; 512], None, Flags::empty(), Timeout::DEFAULT)
+            .unwrap_err();
+        assert!(matches!(
+            e,
+            ScsiError::Status {
+                status: ScsiStatus::Busy,
+                ..
+            }
+        ));
+        assert_eq!(dev.last_retries(), 0);
+        assert_eq!(dev.transport().commands(), 1);
+
+        // XPWRITE(32) neither.
+        let mut dev = retrying([busy.clone()]);
+        let mut xpwrite = [0u8; 32];
+        xpwrite[0] = 0x7f;
+        xpwrite[7] = 0x18;
+        xpwrite[9] = 0x06;
+        assert!(dev
+            .write(&xpwrite, &[0; 512], None, Flags::empty(), Timeout::DEFAULT)
+            .is_err());
+        assert_eq!(dev.transport().commands(), 1);
+
+        // Unless the policy allows it.
+        let mut dev = retrying([busy]);
+        let mut policy = dev.retry_policy().clone();
+        policy.allow_non_idempotent = true;
+        dev.set_retry_policy(policy);
+        let _ = dev.write(&cdb, &[0; 512], None, Flags::empty(), Timeout::DEFAULT);
+        assert_eq!(dev.last_retries(), 2);
+        assert_eq!(dev.transport().commands(), 3);
+    }
+}
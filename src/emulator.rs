@@ -0,0 +1,746 @@
+/*
+ * Copyright 2025 Jason King
+ */
+
+use crate::{Command, CommandResult, DataDirection, ScsiError, ScsiStatus, Transport};
+
+const KEY_NO_SENSE: u8 = 0x00;
+const KEY_ILLEGAL_REQUEST: u8 = 0x05;
+
+const FIXED_SENSE_LEN: usize = 18;
+
+const VENDOR: &[u8; 8] = b"USCSI   ";
+const PRODUCT: &[u8; 16] = b"EMULATED DISK   ";
+const REVISION: &[u8; 4] = b"0001";
+const SERIAL: &[u8] = b"EMU0000001";
+
+/// A fixed-format sense triple that will be reported with CHECK CONDITION.
+#[derive(Debug, Clone, Copy)]
+struct Check {
+    key: u8,
+    asc: u8,
+    ascq: u8,
+}
+
+const INVALID_OPCODE: Check = Check {
+    key: KEY_ILLEGAL_REQUEST,
+    asc: 0x20,
+    ascq: 0x00,
+};
+const LBA_OUT_OF_RANGE: Check = Check {
+    key: KEY_ILLEGAL_REQUEST,
+    asc: 0x21,
+    ascq: 0x00,
+};
+const INVALID_FIELD_IN_CDB: Check = Check {
+    key: KEY_ILLEGAL_REQUEST,
+    asc: 0x24,
+    ascq: 0x00,
+};
+
+impl Check {
+    fn fixed(&self) -> [u8; FIXED_SENSE_LEN] {
+        let mut buf = [0u8; FIXED_SENSE_LEN];
+        buf[0] = 0x70;
+        buf[2] = self.key;
+        buf[7] = (FIXED_SENSE_LEN - 8) as u8;
+        buf[12] = self.asc;
+        buf[13] = self.ascq;
+        buf
+    }
+}
+
+/// A RAM-backed emulated direct-access (disk) device, for exercising code
+/// written against [`Transport`] without hardware.
+///
+/// Supports TEST UNIT READY, REQUEST SENSE, INQUIRY (standard, and VPD
+/// pages 0x00, 0x80, 0x83, 0xB0 and 0xB1), MODE SENSE(6/10) (caching and control pages),
+/// READ CAPACITY(10/16), READ/WRITE(6/10/16) and SYNCHRONIZE CACHE(10/16).
+/// Errors are reported as CHECK CONDITION with fixed-format sense data,
+/// returned via autosense when the command has a sense buffer, or held for
+/// a subsequent REQUEST SENSE otherwise.
+#[derive(Debug, Clone)]
+pub struct Emulator {
+    block_size: u32,
+    blocks: u64,
+    store: Vec<u8>,
+    pending: Option<Check>,
+    max_xfer: usize,
+}
+
+impl Emulator {
+    /// Create a zero-filled device of `blocks` logical blocks of
+    /// `block_size` bytes each.
+    pub fn new(block_size: u32, blocks: u64) -> Self {
+        assert!(block_size > 0, "block size must be non-zero");
+
+        let len =
+            usize::try_from(blocks * u64::from(block_size)).expect("emulated device too large");
+
+        Self {
+            block_size,
+            blocks,
+            store: vec![0; len],
+            pending: None,
+            max_xfer: 1 << 20,
+        }
+    }
+
+    /// Set the value reported by [`Transport::max_xfer`].
+    pub fn with_max_xfer(mut self, max_xfer: usize) -> Self {
+        self.max_xfer = max_xfer;
+        self
+    }
+
+    pub fn block_size(&self) -> u32 {
+        self.block_size
+    }
+
+    pub fn blocks(&self) -> u64 {
+        self.blocks
+    }
+
+    /// The backing store.
+    pub fn data(&self) -> &[u8] {
+        &self.store
+    }
+
+    pub fn data_mut(&mut self) -> &mut [u8] {
+        &mut self.store
+    }
+
+    fn execute(&mut self, cmd: &mut Command<'_>) -> Result<usize, Check> {
+        let cdb = cmd.cdb;
+        let opcode = *cdb.first().ok_or(INVALID_OPCODE)?;
+
+        // Sense data only survives until the next command.
+        if opcode != 0x03 {
+            self.pending = None;
+        }
+
+        match opcode {
+            // TEST UNIT READY
+            0x00 => {
+                cdb_len(cdb, 6)?;
+                Ok(0)
+            }
+            // REQUEST SENSE
+            0x03 => {
+                cdb_len(cdb, 6)?;
+                let check = self.pending.take().unwrap_or(Check {
+                    key: KEY_NO_SENSE,
+                    asc: 0,
+                    ascq: 0,
+                });
+                Ok(copy_in(cmd, &check.fixed(), cdb[4] as usize))
+            }
+            // READ(6) / WRITE(6)
+            0x08 | 0x0a => {
+                cdb_len(cdb, 6)?;
+                let lba = u64::from(be24(&cdb[1..4]) & 0x1f_ffff);
+                let count = match cdb[4] {
+                    0 => 256,
+                    n => u64::from(n),
+                };
+                self.rw(cmd, opcode == 0x08, lba, count)
+            }
+            // INQUIRY
+            0x12 => {
+                cdb_len(cdb, 6)?;
+                let alloc = be16(&cdb[3..5]) as usize;
+                let page = self.inquiry(cdb[1] & 0x01 != 0, cdb[2])?;
+                Ok(copy_in(cmd, &page, alloc))
+            }
+            // MODE SENSE(6)
+            0x1a => {
+                cdb_len(cdb, 6)?;
+                let pages = self.mode_pages(cdb[2])?;
+                let bd = if cdb[1] & 0x08 == 0 {
+                    self.block_descriptor(false)
+                } else {
+                    Vec::new()
+                };
+                let mut buf = vec![0u8; 4];
+                buf[3] = bd.len() as u8;
+                buf.extend_from_slice(&bd);
+                buf.extend_from_slice(&pages);
+                buf[0] = (buf.len() - 1).min(0xff) as u8;
+                Ok(copy_in(cmd, &buf, cdb[4] as usize))
+            }
+            // READ CAPACITY(10)
+            0x25 => {
+                cdb_len(cdb, 10)?;
+                let last = u32::try_from(self.blocks.saturating_sub(1)).unwrap_or(u32::MAX);
+                let mut buf = [0u8; 8];
+                buf[0..4].copy_from_slice(&last.to_be_bytes());
+                buf[4..8].copy_from_slice(&self.block_size.to_be_bytes());
+                Ok(copy_in(cmd, &buf, buf.len()))
+            }
+            // READ(10) / WRITE(10)
+            0x28 | 0x2a => {
+                cdb_len(cdb, 10)?;
+                let lba = u64::from(be32(&cdb[2..6]));
+                let count = u64::from(be16(&cdb[7..9]));
+                self.rw(cmd, opcode == 0x28, lba, count)
+            }
+            // SYNCHRONIZE CACHE(10)
+            0x35 => {
+                cdb_len(cdb, 10)?;
+                let lba = u64::from(be32(&cdb[2..6]));
+                let count = u64::from(be16(&cdb[7..9]));
+                self.check_range(lba, count)?;
+                Ok(0)
+            }
+            // MODE SENSE(10)
+            0x5a => {
+                cdb_len(cdb, 10)?;
+                let pages = self.mode_pages(cdb[2])?;
+                let llbaa = cdb[1] & 0x10 != 0;
+                let bd = if cdb[1] & 0x08 == 0 {
+                    self.block_descriptor(llbaa)
+                } else {
+                    Vec::new()
+                };
+                let mut buf = vec![0u8; 8];
+                if llbaa && !bd.is_empty() {
+                    buf[4] = 0x01;
+                }
+                buf[6..8].copy_from_slice(&(bd.len() as u16).to_be_bytes());
+                buf.extend_from_slice(&bd);
+                buf.extend_from_slice(&pages);
+                let len = (buf.len() - 2).min(0xffff) as u16;
+                buf[0..2].copy_from_slice(&len.to_be_bytes());
+                Ok(copy_in(cmd, &buf, be16(&cdb[7..9]) as usize))
+            }
+            // READ(16) / WRITE(16)
+            0x88 | 0x8a => {
+                cdb_len(cdb, 16)?;
+                let lba = be64(&cdb[2..10]);
+                let count = u64::from(be32(&cdb[10..14]));
+                self.rw(cmd, opcode == 0x88, lba, count)
+            }
+            // SYNCHRONIZE CACHE(16)
+            0x91 => {
+                cdb_len(cdb, 16)?;
+                let lba = be64(&cdb[2..10]);
+                let count = u64::from(be32(&cdb[10..14]));
+                self.check_range(lba, count)?;
+                Ok(0)
+            }
+            // SERVICE ACTION IN(16)
+            0x9e => {
+                cdb_len(cdb, 16)?;
+                // READ CAPACITY(16)
+                if cdb[1] & 0x1f != 0x10 {
+                    return Err(INVALID_FIELD_IN_CDB);
+                }
+                let mut buf = [0u8; 32];
+                buf[0..8].copy_from_slice(&self.blocks.saturating_sub(1).to_be_bytes());
+                buf[8..12].copy_from_slice(&self.block_size.to_be_bytes());
+                Ok(copy_in(cmd, &buf, be32(&cdb[10..14]) as usize))
+            }
+            _ => Err(INVALID_OPCODE),
+        }
+    }
+
+    fn check_range(&self, lba: u64, count: u64) -> Result<(), Check> {
+        match lba.checked_add(count) {
+            Some(end) if end <= self.blocks => Ok(()),
+            _ => Err(LBA_OUT_OF_RANGE),
+        }
+    }
+
+    fn rw(
+        &mut self,
+        cmd: &mut Command<'_>,
+        read: bool,
+        lba: u64,
+        count: u64,
+    ) -> Result<usize, Check> {
+        self.check_range(lba, count)?;
+
+        let bs = self.block_size as usize;
+        let start = lba as usize * bs;
+        let len = (count as usize * bs).min(cmd.data.len());
+        let len = len - len % bs;
+
+        if read {
+            if let Some(data) = cmd.data.input() {
+                data[..len].copy_from_slice(&self.store[start..start + len]);
+            }
+        } else if let Some(data) = cmd.data.output() {
+            self.store[start..start + len].copy_from_slice(&data[..len]);
+        }
+
+        Ok(cmd.data.len() - len)
+    }
+
+    fn inquiry(&self, evpd: bool, page: u8) -> Result<Vec<u8>, Check> {
+        if !evpd {
+            if page != 0 {
+                return Err(INVALID_FIELD_IN_CDB);
+            }
+
+            let mut buf = vec![0u8; 74];
+            buf[2] = 0x06;
+            buf[3] = 0x02;
+            buf[4] = (buf.len() - 5) as u8;
+            buf[7] = 0x02;
+            buf[8..16].copy_from_slice(VENDOR);
+            buf[16..32].copy_from_slice(PRODUCT);
+            buf[32..36].copy_from_slice(REVISION);
+            // SAM-5, SPC-4, SBC-3
+            buf[58..60].copy_from_slice(&0x00a0u16.to_be_bytes());
+            buf[60..62].copy_from_slice(&0x0460u16.to_be_bytes());
+            buf[62..64].copy_from_slice(&0x04c0u16.to_be_bytes());
+            return Ok(buf);
+        }
+
+        let body: Vec<u8> = match page {
+            0x00 => vec![0x00, 0x80, 0x83, 0xb0, 0xb1],
+            0x80 => SERIAL.to_vec(),
+            // A single locally assigned NAA designator for the logical unit.
+            0x83 => {
+                let mut naa = vec![0x01, 0x03, 0x00, 0x08];
+                naa.extend_from_slice(&0x3000_0000_0000_0001u64.to_be_bytes());
+                naa
+            }
+            0xb0 => {
+                let max = (self.max_xfer / self.block_size as usize).min(u32::MAX as usize);
+                let mut limits = vec![0u8; 0x3c];
+                limits[4..8].copy_from_slice(&(max as u32).to_be_bytes());
+                limits
+            }
+            // Non-rotating medium.
+            0xb1 => {
+                let mut chars = vec![0u8; 0x3c];
+                chars[1] = 0x01;
+                chars
+            }
+            _ => return Err(INVALID_FIELD_IN_CDB),
+        };
+
+        let mut buf = vec![0x00, page];
+        buf.extend_from_slice(&(body.len() as u16).to_be_bytes());
+        buf.extend_from_slice(&body);
+        Ok(buf)
+    }
+
+    fn block_descriptor(&self, long: bool) -> Vec<u8> {
+        if long {
+            let mut bd = vec![0u8; 16];
+            bd[0..8].copy_from_slice(&self.blocks.to_be_bytes());
+            bd[12..16].copy_from_slice(&self.block_size.to_be_bytes());
+            bd
+        } else {
+            let blocks = self.blocks.min(0xff_ffff) as u32;
+            let mut bd = vec![0u8; 8];
+            bd[1..4].copy_from_slice(&blocks.to_be_bytes()[1..]);
+            bd[5..8].copy_from_slice(&self.block_size.to_be_bytes()[1..]);
+            bd
+        }
+    }
+
+    fn mode_pages(&self, pcpage: u8) -> Result<Vec<u8>, Check> {
+        let changeable = pcpage >> 6 == 0x01;
+
+        let mut caching = vec![0u8; 20];
+        caching[0] = 0x08;
+        caching[1] = (caching.len() - 2) as u8;
+
+        let mut control = vec![0u8; 12];
+        control[0] = 0x0a;
+        control[1] = (control.len() - 2) as u8;
+
+        if !changeable {
+            // WCE
+            caching[2] = 0x04;
+        }
+
+        match pcpage & 0x3f {
+            0x08 => Ok(caching),
+            0x0a => Ok(control),
+            0x3f => Ok([caching, control].concat()),
+            _ => Err(INVALID_FIELD_IN_CDB),
+        }
+    }
+
+    fn submit_contiguous(&mut self, cmd: &mut Command<'_>) -> Result<CommandResult, ScsiError> {
+        let reads = matches!(
+            cmd.cdb.first(),
+            Some(0x03 | 0x08 | 0x12 | 0x1a | 0x25 | 0x28 | 0x5a | 0x88 | 0x9e)
+        );
+        let writes = matches!(cmd.cdb.first(), Some(0x0a | 0x2a | 0x8a));
+
+        let dir = cmd.direction();
+        if dir == DataDirection::Bidirectional {
+            return Err(ScsiError::Unsupported("bidirectional transfers"));
+        }
+        if (reads && dir == DataDirection::Out && !cmd.data.is_empty())
+            || (writes && dir == DataDirection::In && !cmd.data.is_empty())
+        {
+            return Err(ScsiError::InvalidCommand(
+                "data direction does not match command",
+            ));
+        }
+
+        match self.execute(cmd) {
+            Ok(resid) => Ok(CommandResult {
+                status: ScsiStatus::Good,
+                resid,
+                rqresid: cmd.sense.as_ref().map_or(0, |s| s.len()),
+                rqstatus: ScsiStatus::Good,
+                retries: 0,
+            }),
+            Err(check) => {
+                let rqresid = match cmd.sense.as_deref_mut() {
+                    Some(sense) => {
+                        let fixed = check.fixed();
+                        let n = fixed.len().min(sense.len());
+                        sense[..n].copy_from_slice(&fixed[..n]);
+                        sense.len() - n
+                    }
+                    None => {
+                        self.pending = Some(check);
+                        0
+                    }
+                };
+
+                Ok(CommandResult {
+                    status: ScsiStatus::CheckCondition,
+                    resid: cmd.data.len(),
+                    rqresid,
+                    rqstatus: ScsiStatus::Good,
+                    retries: 0,
+                })
+            }
+        }
+    }
+}
+
+impl Transport for Emulator {
+    /// Vectored data is bounced through a contiguous buffer.
+    fn submit(&mut self, cmd: &mut Command<'_>) -> Result<CommandResult, ScsiError> {
+        cmd.with_contiguous_data(|cmd| self.submit_contiguous(cmd))
+    }
+
+    fn max_xfer(&mut self) -> Result<usize, ScsiError> {
+        Ok(self.max_xfer)
+    }
+}
+
+fn cdb_len(cdb: &[u8], len: usize) -> Result<(), Check> {
+    if cdb.len() < len {
+        return Err(INVALID_FIELD_IN_CDB);
+    }
+    Ok(())
+}
+
+/// Copy up to `alloc` bytes of `src` into the command's data buffer,
+/// returning the data residual.
+fn copy_in(cmd: &mut Command<'_>, src: &[u8], alloc: usize) -> usize {
+    let Some(data) = cmd.data.input() else {
+        return 0;
+    };
+    let n = src.len().min(alloc).min(data.len());
+    data[..n].copy_from_slice(&src[..n]);
+    data.len() - n
+}
+
+fn be16(b: &[u8]) -> u16 {
+    u16::from_be_bytes([b[0], b[1]])
+}
+
+fn be24(b: &[u8]) -> u32 {
+    u32::from_be_bytes([0, b[0], b[1], b[2]])
+}
+
+fn be32(b: &[u8]) -> u32 {
+    u32::from_be_bytes([b[0], b[1], b[2], b[3]])
+}
+
+fn be64(b: &[u8]) -> u64 {
+    let mut v = [0u8; 8];
+    v.copy_from_slice(&b[..8]);
+    u64::from_be_bytes(v)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cdb::{Cdb, CdbSize, Read, ReadCapacity10, ReadCapacity16, RequestSense, Write};
+    use crate::vpd::{self, RotationRate};
+    use crate::{BlockDevice, Capacity, Device, Flags, Sense, SenseKey, Timeout};
+    use std::io::{Read as _, Seek, SeekFrom, Write as _};
+
+    const BS: u32 = 512;
+    const BLOCKS: u64 = 1024;
+
+    fn emulator() -> Emulator {
+        Emulator::new(BS, BLOCKS)
+    }
+
+    fn pattern(len: usize, seed: u8) -> Vec<u8> {
+        (0..len)
+            .map(|i| (i as u8).wrapping_mul(31) ^ seed)
+            .collect()
+    }
+
+    fn read_cdb(lba: u64, blocks: u32, size: CdbSize) -> Vec<u8> {
+        let mut r = Read::new(lba, blocks, BS);
+        r.min_size = size;
+        r.to_bytes().to_vec()
+    }
+
+    fn write_cdb(lba: u64, blocks: u32, size: CdbSize) -> Vec<u8> {
+        let mut w = Write::new(lba, blocks, BS);
+        w.min_size = size;
+        w.to_bytes().to_vec()
+    }
+
+    #[test]
+    fn write_read_round_trip() {
+        let mut emu = emulator();
+        let sizes = [CdbSize::Six, CdbSize::Ten, CdbSize::Sixteen];
+
+        for (i, wsize) in sizes.into_iter().enumerate() {
+            let lba = 10 + 4 * i as u64;
+            let data = pattern(4 * BS as usize, i as u8);
+            let cdb = write_cdb(lba, 4, wsize);
+            assert_eq!(cdb.len(), wsize.length());
+            let result = emu.submit(&mut Command::new(&cdb).data_out(&data)).unwrap();
+            assert_eq!(result.status, ScsiStatus::Good);
+            assert_eq!(result.resid, 0);
+
+            let start = lba as usize * BS as usize;
+            assert_eq!(&emu.data()[start..start + data.len()], &data[..]);
+
+            // Read it back with each size of READ.
+            for rsize in sizes {
+                let mut buf = vec![0u8; data.len()];
+                let cdb = read_cdb(lba, 4, rsize);
+                let result = emu
+                    .submit(&mut Command::new(&cdb).data_in(&mut buf))
+                    .unwrap();
+                assert_eq!(result.status, ScsiStatus::Good);
+                assert_eq!(result.resid, 0);
+                assert_eq!(buf, data);
+            }
+        }
+    }
+
+    #[test]
+    fn short_buffer_resid() {
+        let mut emu = emulator();
+        let mut buf = vec![0u8; 3 * BS as usize];
+        let cdb = read_cdb(0, 2, CdbSize::Ten);
+        let result = emu
+            .submit(&mut Command::new(&cdb).data_in(&mut buf))
+            .unwrap();
+        assert_eq!(result.resid, BS as usize);
+    }
+
+    #[test]
+    fn read_capacity() {
+        let mut emu = emulator();
+
+        let mut buf = [0u8; ReadCapacity10::DATA_LEN];
+        let cdb = ReadCapacity10.to_bytes();
+        emu.submit(&mut Command::new(&cdb).data_in(&mut buf))
+            .unwrap();
+        let cap = Capacity::parse10(&buf).unwrap();
+        assert_eq!(cap.last_lba, BLOCKS - 1);
+        assert_eq!(cap.block_size, BS);
+
+        let mut buf = [0u8; ReadCapacity16::DATA_LEN];
+        let cdb = ReadCapacity16::default().to_bytes();
+        emu.submit(&mut Command::new(&cdb).data_in(&mut buf))
+            .unwrap();
+        let cap = Capacity::parse16(&buf).unwrap();
+        assert_eq!(cap.blocks(), BLOCKS);
+        assert_eq!(cap.bytes(), BLOCKS as u128 * BS as u128);
+
+        let cap = Device::new(emulator()).capacity().unwrap();
+        assert_eq!(cap.blocks(), BLOCKS);
+        assert_eq!(cap.block_size, BS);
+    }
+
+    #[test]
+    fn lba_out_of_range() {
+        let mut emu = emulator();
+
+        let mut buf = vec![0u8; 2 * BS as usize];
+        let mut sense = [0u8; 32];
+        let cdb = read_cdb(BLOCKS - 1, 2, CdbSize::Ten);
+        let result = emu
+            .submit(&mut Command::new(&cdb).data_in(&mut buf).sense(&mut sense))
+            .unwrap();
+        assert_eq!(result.status, ScsiStatus::CheckCondition);
+        assert_eq!(result.resid, buf.len());
+        assert_eq!(result.rqresid, 32 - FIXED_SENSE_LEN);
+
+        let sense = Sense::from_result(&sense, &result).unwrap();
+        assert_eq!(sense.response_code(), 0x70);
+        assert_eq!(sense.key(), SenseKey::IllegalRequest);
+        assert_eq!((sense.asc(), sense.ascq()), (0x21, 0x00));
+
+        // Without a sense buffer the sense data is held for REQUEST SENSE,
+        // and only until the next command.
+        let result = emu
+            .submit(&mut Command::new(&cdb).data_in(&mut buf))
+            .unwrap();
+        assert_eq!(result.status, ScsiStatus::CheckCondition);
+
+        let mut sense = [0u8; 18];
+        let rs = RequestSense::new(18).to_bytes();
+        emu.submit(&mut Command::new(&rs).data_in(&mut sense))
+            .unwrap();
+        let parsed = Sense::parse(&sense).unwrap();
+        assert_eq!((parsed.asc(), parsed.ascq()), (0x21, 0x00));
+
+        emu.submit(&mut Command::new(&rs).data_in(&mut sense))
+            .unwrap();
+        assert_eq!(Sense::parse(&sense).unwrap().key(), SenseKey::NoSense);
+
+        // Device reports it as an error carrying the sense data.
+        let mut dev = Device::new(emulator());
+        let err = dev
+            .read(&cdb, &mut buf, None, Flags::empty(), Timeout::DEFAULT)
+            .unwrap_err();
+        assert!(err.is_illegal_request());
+        assert_eq!(err.sense().map(|s| s.asc()), Some(0x21));
+
+        let err = dev.read_blocks(BLOCKS - 1, &mut buf).unwrap_err();
+        assert_eq!(err.lba, BLOCKS - 1);
+        assert_eq!(err.blocks_done, 0);
+    }
+
+    #[test]
+    fn invalid_opcode() {
+        let mut dev = Device::new(emulator());
+        let err = dev
+            .no_data(
+                &[0x1b, 0, 0, 0, 1, 0],
+                None,
+                Flags::empty(),
+                Timeout::DEFAULT,
+            )
+            .unwrap_err();
+        assert!(err.is_illegal_request());
+        assert_eq!(err.sense().map(|s| s.asc()), Some(0x20));
+    }
+
+    #[test]
+    fn inquiry() {
+        let inq = Device::new(emulator()).inquiry().unwrap();
+        assert_eq!(inq.device_type, 0x00);
+        assert_eq!(inq.vendor, "USCSI");
+        assert_eq!(inq.product, "EMULATED DISK");
+        assert_eq!(inq.revision, "0001");
+        assert_eq!(inq.version_descriptors.len(), 3);
+    }
+
+    #[test]
+    fn vpd_pages() {
+        let mut dev = Device::new(emulator().with_max_xfer(64 * 1024));
+
+        let pages =
+            vpd::SupportedPages::parse(&dev.vpd_page(vpd::SUPPORTED_PAGES).unwrap()).unwrap();
+        assert_eq!(pages.pages, [0x00, 0x80, 0x83, 0xb0, 0xb1]);
+
+        let serial =
+            vpd::UnitSerialNumber::parse(&dev.vpd_page(vpd::UNIT_SERIAL_NUMBER).unwrap()).unwrap();
+        assert_eq!(serial.serial, "EMU0000001");
+
+        let id =
+            vpd::DeviceIdentification::parse(&dev.vpd_page(vpd::DEVICE_IDENTIFICATION).unwrap())
+                .unwrap();
+        let lu: Vec<_> = id.logical_unit().collect();
+        assert_eq!(lu.len(), 1);
+        assert_eq!(lu[0].designator_type, vpd::DesignatorType::Naa);
+        assert!(matches!(
+            lu[0].decode(),
+            vpd::DesignatorValue::Naa { naa: 3, .. }
+        ));
+
+        let limits = vpd::BlockLimits::parse(&dev.vpd_page(vpd::BLOCK_LIMITS).unwrap()).unwrap();
+        assert_eq!(limits.maximum_transfer_length, 64 * 1024 / BS);
+
+        let chars = vpd::BlockDeviceCharacteristics::parse(
+            &dev.vpd_page(vpd::BLOCK_DEVICE_CHARACTERISTICS).unwrap(),
+        )
+        .unwrap();
+        assert_eq!(chars.medium_rotation_rate, RotationRate::NonRotating);
+
+        let err = dev.vpd_page(0xb2).unwrap_err();
+        assert!(err.is_illegal_request());
+        assert_eq!(err.sense().map(|s| s.asc()), Some(0x24));
+    }
+
+    #[test]
+    fn device_block_io() {
+        let mut dev = Device::new(emulator().with_max_xfer(4 * BS as usize));
+        let limits = dev.transfer_limits().unwrap();
+        assert_eq!(limits.block_size, BS);
+        assert_eq!(limits.max_blocks, 4);
+
+        // Ten blocks take three commands.
+        let data = pattern(10 * BS as usize, 0x5a);
+        dev.write_blocks(100, &data).unwrap();
+        let start = 100 * BS as usize;
+        assert_eq!(
+            &dev.transport().data()[start..start + data.len()],
+            &data[..]
+        );
+
+        let mut buf = vec![0u8; data.len()];
+        dev.read_blocks(100, &mut buf).unwrap();
+        assert_eq!(buf, data);
+
+        // A transfer that runs off the end fails at the first bad command,
+        // having transferred the commands before it.
+        let err = dev.read_blocks(BLOCKS - 6, &mut buf).unwrap_err();
+        assert_eq!(err.blocks_done, 4);
+        assert_eq!(err.lba, BLOCKS - 2);
+        assert!(err.error.is_illegal_request());
+
+        assert!(dev.read_blocks(0, &mut buf[..100]).is_err());
+    }
+
+    #[test]
+    fn block_device() {
+        let mut bdev = BlockDevice::new(Device::new(emulator())).unwrap();
+        assert_eq!(bdev.size(), BLOCKS * BS as u64);
+        assert_eq!(bdev.block_size(), BS);
+
+        // An unaligned write spanning partial blocks at both ends.
+        let data = pattern(3 * BS as usize, 0xa5);
+        bdev.seek(SeekFrom::Start(1000)).unwrap();
+        bdev.write_all(&data).unwrap();
+        bdev.flush().unwrap();
+        assert_eq!(bdev.stream_position().unwrap(), 1000 + data.len() as u64);
+
+        let mut buf = vec![0u8; data.len()];
+        bdev.seek(SeekFrom::Start(1000)).unwrap();
+        bdev.read_exact(&mut buf).unwrap();
+        assert_eq!(buf, data);
+
+        // The bytes around the write are untouched.
+        let mut around = [0xffu8; 8];
+        bdev.seek(SeekFrom::Start(992)).unwrap();
+        bdev.read_exact(&mut around).unwrap();
+        assert_eq!(around, [0; 8]);
+
+        let dev = bdev.into_inner();
+        let store = dev.transport().data();
+        assert_eq!(&store[1000..1000 + data.len()], &data[..]);
+
+        // Reads stop, and writes fail, at the end of the device.
+        let mut bdev = BlockDevice::new(dev).unwrap();
+        bdev.seek(SeekFrom::End(-4)).unwrap();
+        assert_eq!(bdev.read(&mut buf).unwrap(), 4);
+        assert_eq!(bdev.read(&mut buf).unwrap(), 0);
+        let err = bdev.write(&buf).unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::StorageFull);
+    }
+}
@@ -0,0 +1,180 @@
+/*
+ * Copyright 2025 Jason King
+ */
+
+use crate::{ScsiStatus, Sense, SenseKey};
+use std::fmt;
+
+/// An error issuing a SCSI command.
+#[derive(Debug)]
+pub enum ScsiError {
+    /// The operating system rejected the request (e.g. the ioctl failed).
+    Os(std::io::Error),
+    /// The host adapter or transport failed to deliver the command or its
+    /// response. The status values are backend specific (e.g. the SG_IO
+    /// `host_status` and `driver_status`).
+    Transport {
+        host_status: u16,
+        driver_status: u16,
+    },
+    /// The command did not complete within its timeout.
+    Timeout,
+    /// The command completed with a non-GOOD status.
+    Status {
+        status: ScsiStatus,
+        sense: Option<Sense>,
+    },
+    /// The command completed, but transferred less data than required.
+    ShortTransfer { expected: usize, actual: usize },
+    /// The command could not be issued as described.
+    InvalidCommand(&'static str),
+    /// The transport cannot issue this kind of command (e.g. a
+    /// bidirectional transfer).
+    Unsupported(&'static str),
+}
+
+impl ScsiError {
+    /// The SCSI status, if the command completed.
+    pub fn status(&self) -> Option<ScsiStatus> {
+        match self {
+            ScsiError::Status { status, .. } => Some(*status),
+            _ => None,
+        }
+    }
+
+    /// The sense data returned with a CHECK CONDITION, if any.
+    pub fn sense(&self) -> Option<&Sense> {
+        match self {
+            ScsiError::Status { sense, .. } => sense.as_ref(),
+            _ => None,
+        }
+    }
+
+    pub fn sense_key(&self) -> Option<SenseKey> {
+        self.sense().map(Sense::key)
+    }
+
+    pub fn is_unit_attention(&self) -> bool {
+        self.sense_key() == Some(SenseKey::UnitAttention)
+    }
+
+    pub fn is_medium_error(&self) -> bool {
+        self.sense_key() == Some(SenseKey::MediumError)
+    }
+
+    pub fn is_not_ready(&self) -> bool {
+        self.sense_key() == Some(SenseKey::NotReady)
+    }
+
+    pub fn is_illegal_request(&self) -> bool {
+        self.sense_key() == Some(SenseKey::IllegalRequest)
+    }
+
+    pub fn is_timeout(&self) -> bool {
+        matches!(self, ScsiError::Timeout)
+    }
+
+    /// True if reissuing the same command may succeed: timeouts, transport
+    /// failures, BUSY, TASK SET FULL, UNIT ATTENTION, ABORTED COMMAND and
+    /// NOT READY while the logical unit is becoming ready.
+    pub fn is_retryable(&self) -> bool {
+        match self {
+            ScsiError::Os(e) => matches!(
+                e.kind(),
+                std::io::ErrorKind::Interrupted | std::io::ErrorKind::WouldBlock
+            ),
+            ScsiError::Transport { .. } | ScsiError::Timeout => true,
+            ScsiError::Status { status, sense } => match status {
+                ScsiStatus::Busy | ScsiStatus::TaskSetFull => true,
+                ScsiStatus::CheckCondition => match sense {
+                    Some(s) => match s.key() {
+                        SenseKey::UnitAttention | SenseKey::AbortedCommand => true,
+                        SenseKey::NotReady => s.asc() == 0x04 && s.ascq() == 0x01,
+                        _ => false,
+                    },
+                    None => false,
+                },
+                _ => false,
+            },
+            ScsiError::ShortTransfer { .. }
+            | ScsiError::InvalidCommand(_)
+            | ScsiError::Unsupported(_) => false,
+        }
+    }
+
+    /// The [`std::io::ErrorKind`] the error converts to.
+    pub(crate) fn io_kind(&self) -> std::io::ErrorKind {
+        use std::io::ErrorKind;
+
+        match self {
+            ScsiError::Os(e) => e.kind(),
+            ScsiError::Timeout => ErrorKind::TimedOut,
+            ScsiError::InvalidCommand(_) => ErrorKind::InvalidInput,
+            ScsiError::Unsupported(_) => ErrorKind::Unsupported,
+            ScsiError::ShortTransfer { .. } => ErrorKind::UnexpectedEof,
+            ScsiError::Status {
+                status: ScsiStatus::Busy | ScsiStatus::TaskSetFull,
+                ..
+            } => ErrorKind::ResourceBusy,
+            ScsiError::Status { .. } if self.is_not_ready() => ErrorKind::ResourceBusy,
+            ScsiError::Status { .. } if self.is_illegal_request() => ErrorKind::InvalidInput,
+            ScsiError::Status { .. } | ScsiError::Transport { .. } => ErrorKind::Other,
+        }
+    }
+}
+
+impl fmt::Display for ScsiError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ScsiError::Os(e) => write!(f, "{e}"),
+            ScsiError::Transport {
+                host_status,
+                driver_status,
+            } => write!(
+                f,
+                "transport failure (host status {host_status:#x}, driver status {driver_status:#x})"
+            ),
+            ScsiError::Timeout => f.write_str("command timed out"),
+            ScsiError::Status {
+                status,
+                sense: Some(sense),
+            } => write!(f, "{status}: {sense}"),
+            ScsiError::Status {
+                status,
+                sense: None,
+            } => write!(f, "{status}"),
+            ScsiError::ShortTransfer { expected, actual } => {
+                write!(f, "short transfer ({actual} of {expected} bytes)")
+            }
+            ScsiError::InvalidCommand(msg) => write!(f, "invalid command: {msg}"),
+            ScsiError::Unsupported(msg) => write!(f, "{msg} not supported by transport"),
+        }
+    }
+}
+
+impl std::error::Error for ScsiError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            ScsiError::Os(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<std::io::Error> for ScsiError {
+    fn from(e: std::io::Error) -> Self {
+        match e.kind() {
+            std::io::ErrorKind::TimedOut => ScsiError::Timeout,
+            _ => ScsiError::Os(e),
+        }
+    }
+}
+
+impl From<ScsiError> for std::io::Error {
+    fn from(e: ScsiError) -> Self {
+        match e {
+            ScsiError::Os(e) => e,
+            e => std::io::Error::new(e.io_kind(), e),
+        }
+    }
+}
@@ -0,0 +1,543 @@
+/*
+ * Copyright 2025 Jason King
+ */
+
+use crate::cdb::SbcCdb;
+use crate::{Command, CommandResult, ScsiError, ScsiStatus, SenseKey, Transport};
+use std::collections::hash_map::RandomState;
+use std::hash::{BuildHasher, Hasher};
+use std::ops::Range;
+
+/// The length of the fixed-format sense data an injected CHECK CONDITION
+/// reports.
+const FIXED_SENSE_LEN: usize = 18;
+
+/// A failure a [`FaultInjector`] reports in place of (or on top of) the
+/// outcome of a command.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Fault {
+    /// Complete with CHECK CONDITION and fixed-format sense data holding
+    /// the given key, ASC and ASCQ. The command is not sent.
+    CheckCondition { key: SenseKey, asc: u8, ascq: u8 },
+    /// Complete with the given status (e.g. BUSY) and no data transferred.
+    /// The command is not sent.
+    Status(ScsiStatus),
+    /// Send the command, then report `resid` more bytes as not transferred
+    /// (up to the length of the data buffer).
+    ShortTransfer { resid: usize },
+    /// Fail with [`ScsiError::Timeout`]. The command is not sent.
+    Timeout,
+    /// Fail with the OS error `errno`. The command is not sent.
+    Os(i32),
+}
+
+impl Fault {
+    /// CHECK CONDITION with the given sense key, ASC and ASCQ.
+    pub fn check_condition(key: SenseKey, asc: u8, ascq: u8) -> Self {
+        Fault::CheckCondition { key, asc, ascq }
+    }
+}
+
+/// When a [`FaultInjector`] injects a [`Fault`].
+///
+/// A rule matches a command only if every condition set on it holds; a
+/// rule with no conditions matches every command.
+#[derive(Debug, Clone)]
+pub struct FaultRule {
+    fault: Fault,
+    opcode: Option<u8>,
+    lbas: Option<Range<u64>>,
+    nth: Option<u64>,
+    probability: Option<f64>,
+    remaining: Option<u32>,
+}
+
+impl FaultRule {
+    pub fn new(fault: Fault) -> Self {
+        Self {
+            fault,
+            opcode: None,
+            lbas: None,
+            nth: None,
+            probability: None,
+            remaining: None,
+        }
+    }
+
+    /// Only match commands with this operation code.
+    pub fn opcode(mut self, opcode: u8) -> Self {
+        self.opcode = Some(opcode);
+        self
+    }
+
+    /// Only match block commands addressing at least one LBA in `lbas`.
+    /// SYNCHRONIZE CACHE, PRE-FETCH and WRITE SAME with a count of zero
+    /// address every LBA from theirs to the end of the medium; commands
+    /// without an LBA, or transferring no blocks, never match.
+    pub fn lbas(mut self, lbas: Range<u64>) -> Self {
+        self.lbas = Some(lbas);
+        self
+    }
+
+    /// Only match the `n`th command (counting from 1) sent through the
+    /// injector.
+    pub fn nth(mut self, n: u64) -> Self {
+        self.nth = Some(n);
+        self
+    }
+
+    /// Only match with probability `p` (clamped to 0.0 to 1.0), checked
+    /// after the other conditions.
+    pub fn probability(mut self, p: f64) -> Self {
+        self.probability = Some(p.clamp(0.0, 1.0));
+        self
+    }
+
+    /// Stop matching after injecting the fault `n` times.
+    pub fn times(mut self, n: u32) -> Self {
+        self.remaining = Some(n);
+        self
+    }
+
+    pub fn fault(&self) -> Fault {
+        self.fault
+    }
+
+    fn matches(&self, cdb: &[u8], count: u64, rng: &mut Rng) -> bool {
+        if self.remaining == Some(0)
+            || self.opcode.is_some_and(|op| cdb.first() != Some(&op))
+            || self.nth.is_some_and(|n| n != count)
+        {
+            return false;
+        }
+        if let Some(lbas) = &self.lbas {
+            match cdb_lbas(cdb) {
+                Some(r) if r.start < lbas.end && lbas.start < r.end => {}
+                _ => return false,
+            }
+        }
+        match self.probability {
+            Some(p) => rng.next_f64() < p,
+            None => true,
+        }
+    }
+}
+
+/// A [`Transport`] that wraps another transport and injects failures into
+/// the commands sent through it, for exercising retry and error handling.
+///
+/// Rules are checked in the order they were added and the first one that
+/// matches a command decides its fault; commands no rule matches are passed
+/// through unchanged, as are resets and transfer size queries.
+///
+/// An injected CHECK CONDITION writes its sense data to the command's sense
+/// buffer, so the command needs one for the sense to be seen.
+#[derive(Debug)]
+pub struct FaultInjector<T: Transport> {
+    inner: T,
+    rules: Vec<FaultRule>,
+    rng: Rng,
+    commands: u64,
+    injected: u64,
+}
+
+impl<T: Transport> FaultInjector<T> {
+    /// Wrap `inner`, with no rules. Probabilistic rules are seeded randomly.
+    pub fn new(inner: T) -> Self {
+        Self {
+            inner,
+            rules: Vec::new(),
+            rng: Rng::new(RandomState::new().build_hasher().finish()),
+            commands: 0,
+            injected: 0,
+        }
+    }
+
+    /// Seed the generator used by probabilistic rules, making the faults
+    /// they inject repeatable.
+    pub fn with_seed(mut self, seed: u64) -> Self {
+        self.rng = Rng::new(seed);
+        self
+    }
+
+    pub fn with_rule(mut self, rule: FaultRule) -> Self {
+        self.rules.push(rule);
+        self
+    }
+
+    pub fn add_rule(&mut self, rule: FaultRule) {
+        self.rules.push(rule);
+    }
+
+    pub fn clear_rules(&mut self) {
+        self.rules.clear();
+    }
+
+    pub fn rules(&self) -> &[FaultRule] {
+        &self.rules
+    }
+
+    /// The number of commands sent through the injector.
+    pub fn commands(&self) -> u64 {
+        self.commands
+    }
+
+    /// The number of faults injected.
+    pub fn injected(&self) -> u64 {
+        self.injected
+    }
+
+    pub fn inner(&self) -> &T {
+        &self.inner
+    }
+
+    pub fn inner_mut(&mut self) -> &mut T {
+        &mut self.inner
+    }
+
+    pub fn into_inner(self) -> T {
+        self.inner
+    }
+
+    fn pick(&mut self, cdb: &[u8]) -> Option<Fault> {
+        let rule = self
+            .rules
+            .iter_mut()
+            .find(|rule| rule.matches(cdb, self.commands, &mut self.rng))?;
+        if let Some(n) = &mut rule.remaining {
+            *n -= 1;
+        }
+        self.injected += 1;
+        Some(rule.fault)
+    }
+}
+
+impl<T: Transport> Transport for FaultInjector<T> {
+    fn submit(&mut self, cmd: &mut Command<'_>) -> Result<CommandResult, ScsiError> {
+        self.commands += 1;
+        let Some(fault) = self.pick(cmd.cdb) else {
+            return self.inner.submit(cmd);
+        };
+
+        let sense_len = cmd.sense.as_ref().map_or(0, |s| s.len());
+        let failed = |status, rqresid| CommandResult {
+            status,
+            resid: cmd.data.len(),
+            rqresid,
+            rqstatus: ScsiStatus::Good,
+            retries: 0,
+        };
+
+        match fault {
+            Fault::CheckCondition { key, asc, ascq } => {
+                let mut fixed = [0u8; FIXED_SENSE_LEN];
+                fixed[0] = 0x70;
+                fixed[2] = key.into();
+                fixed[7] = (FIXED_SENSE_LEN - 8) as u8;
+                fixed[12] = asc;
+                fixed[13] = ascq;
+
+                let n = FIXED_SENSE_LEN.min(sense_len);
+                let result = failed(ScsiStatus::CheckCondition, sense_len - n);
+                if let Some(sense) = cmd.sense.as_deref_mut() {
+                    sense[..n].copy_from_slice(&fixed[..n]);
+                }
+                Ok(result)
+            }
+            Fault::Status(status) => Ok(failed(status, sense_len)),
+            Fault::ShortTransfer { resid } => {
+                let mut result = self.inner.submit(cmd)?;
+                result.resid = result.resid.saturating_add(resid).min(cmd.data.len());
+                Ok(result)
+            }
+            Fault::Timeout => Err(ScsiError::Timeout),
+            Fault::Os(errno) => Err(std::io::Error::from_raw_os_error(errno).into()),
+        }
+    }
+
+    fn reset(&mut self) -> Result<(), ScsiError> {
+        self.inner.reset()
+    }
+
+    fn max_xfer(&mut self) -> Result<usize, ScsiError> {
+        self.inner.max_xfer()
+    }
+}
+
+/// The LBAs a block command addresses, if it addresses any.
+fn cdb_lbas(cdb: &[u8]) -> Option<Range<u64>> {
+    // The block size only affects the decoded transfer length.
+    // A count of zero means through the end of the medium for the cache
+    // and WRITE SAME commands; the others transfer nothing.
+    let (lba, blocks, to_end) = match SbcCdb::decode(cdb, 512)? {
+        SbcCdb::Read(c) => (c.lba, c.blocks, false),
+        SbcCdb::Write(c) => (c.lba, c.blocks, false),
+        SbcCdb::Verify(c) => (c.lba, c.blocks, false),
+        SbcCdb::WriteAndVerify(c) => (c.lba, c.blocks, false),
+        SbcCdb::SynchronizeCache(c) => (c.lba, c.blocks, true),
+        SbcCdb::PreFetch(c) => (c.lba, c.blocks, true),
+        SbcCdb::WriteSame(c) => (c.lba, c.blocks, true),
+        SbcCdb::CompareAndWrite(c) => (c.lba, c.blocks.into(), false),
+        SbcCdb::GetLbaStatus(c) => (c.lba, 1, false),
+        _ => return None,
+    };
+    match (blocks, to_end) {
+        (0, true) => Some(lba..u64::MAX),
+        (0, false) => None,
+        (n, _) => Some(lba..lba.saturating_add(n.into())),
+    }
+}
+
+/// A xorshift64* generator; plenty for deciding whether to inject a fault.
+#[derive(Debug, Clone)]
+struct Rng(u64);
+
+impl Rng {
+    fn new(seed: u64) -> Self {
+        // The state must never be zero; map that seed to an arbitrary
+        // non-zero one rather than folding seeds together.
+        Self(match seed {
+            0 => 0x9e37_79b9_7f4a_7c15,
+            seed => seed,
+        })
+    }
+
+    fn next_f64(&mut self) -> f64 {
+        self.0 ^= self.0 >> 12;
+        self.0 ^= self.0 << 25;
+        self.0 ^= self.0 >> 27;
+        let v = self.0.wrapping_mul(0x2545_f491_4f6c_dd1d);
+        (v >> 11) as f64 / (1u64 << 53) as f64
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cdb::{
+        Cdb, CdbSize, PreFetch, Read, SynchronizeCache, TestUnitReady, Write, WriteSame,
+    };
+    use crate::{Emulator, Sense};
+
+    const BS: u32 = 512;
+
+    fn injector() -> FaultInjector<Emulator> {
+        FaultInjector::new(Emulator::new(BS, 1024)).with_seed(1)
+    }
+
+    fn read<T: Transport>(t: &mut T, lba: u64, blocks: u32) -> Result<CommandResult, ScsiError> {
+        let mut r = Read::new(lba, blocks, BS);
+        r.min_size = CdbSize::Ten;
+        let cdb = r.to_bytes();
+        let mut buf = vec![0u8; (blocks * BS) as usize];
+        t.submit(&mut Command::new(&cdb).data_in(&mut buf))
+    }
+
+    fn write<T: Transport>(t: &mut T, lba: u64, data: &[u8]) -> Result<CommandResult, ScsiError> {
+        let cdb = Write::new(lba, (data.len() / BS as usize) as u32, BS).to_bytes();
+        t.submit(&mut Command::new(&cdb).data_out(data))
+    }
+
+    fn status(result: Result<CommandResult, ScsiError>) -> ScsiStatus {
+        result.unwrap().status
+    }
+
+    #[test]
+    fn first_match() {
+        let mut t = injector()
+            .with_rule(FaultRule::new(Fault::Status(ScsiStatus::Busy)).opcode(0x28))
+            .with_rule(FaultRule::new(Fault::Status(ScsiStatus::TaskSetFull)))
+            .with_rule(FaultRule::new(Fault::Timeout));
+
+        let result = read(&mut t, 0, 2).unwrap();
+        assert_eq!(result.status, ScsiStatus::Busy);
+        assert_eq!(result.resid, 2 * BS as usize);
+        assert_eq!(status(write(&mut t, 0, &[1; 512])), ScsiStatus::TaskSetFull);
+        // Nothing got through to the emulator.
+        assert_eq!(t.inner().data()[0], 0);
+        assert_eq!((t.commands(), t.injected()), (2, 2));
+
+        t.clear_rules();
+        assert_eq!(status(write(&mut t, 0, &[1; 512])), ScsiStatus::Good);
+        assert_eq!(t.inner().data()[0], 1);
+        assert_eq!((t.commands(), t.injected()), (3, 2));
+    }
+
+    #[test]
+    fn nth() {
+        let mut t = injector().with_rule(FaultRule::new(Fault::Status(ScsiStatus::Busy)).nth(2));
+        let statuses: Vec<_> = (0..4).map(|_| status(read(&mut t, 0, 1))).collect();
+        assert_eq!(
+            statuses,
+            [
+                ScsiStatus::Good,
+                ScsiStatus::Busy,
+                ScsiStatus::Good,
+                ScsiStatus::Good
+            ]
+        );
+        assert_eq!((t.commands(), t.injected()), (4, 1));
+    }
+
+    #[test]
+    fn times() {
+        let mut t = injector()
+            .with_rule(FaultRule::new(Fault::Timeout).times(0))
+            .with_rule(FaultRule::new(Fault::Status(ScsiStatus::Busy)).times(2));
+
+        assert_eq!(status(read(&mut t, 0, 1)), ScsiStatus::Busy);
+        assert_eq!(status(read(&mut t, 0, 1)), ScsiStatus::Busy);
+        assert_eq!(status(read(&mut t, 0, 1)), ScsiStatus::Good);
+        assert_eq!(status(read(&mut t, 0, 1)), ScsiStatus::Good);
+        assert_eq!(t.injected(), 2);
+    }
+
+    #[test]
+    fn lbas() {
+        let busy = Fault::Status(ScsiStatus::Busy);
+        let mut t = injector().with_rule(FaultRule::new(busy).lbas(100..104));
+
+        assert_eq!(status(read(&mut t, 96, 4)), ScsiStatus::Good);
+        assert_eq!(status(read(&mut t, 99, 1)), ScsiStatus::Good);
+        assert_eq!(status(read(&mut t, 99, 2)), ScsiStatus::Busy);
+        assert_eq!(status(read(&mut t, 103, 8)), ScsiStatus::Busy);
+        assert_eq!(status(read(&mut t, 104, 1)), ScsiStatus::Good);
+        assert_eq!(status(write(&mut t, 102, &[0; 512])), ScsiStatus::Busy);
+
+        // A count of zero reaches to the end of the medium.
+        let sync = |lba, blocks| SynchronizeCache::range(lba, blocks).to_bytes();
+        for (cdb, expected) in [
+            (sync(50, 0), ScsiStatus::Busy),
+            (sync(103, 0), ScsiStatus::Busy),
+            (sync(104, 0), ScsiStatus::Good),
+            (sync(50, 50), ScsiStatus::Good),
+            (sync(50, 51), ScsiStatus::Busy),
+        ] {
+            let result = t.submit(&mut Command::new(&cdb)).unwrap();
+            assert_eq!(result.status, expected, "{cdb:02x?}");
+        }
+
+        // For the others it transfers nothing, so never matches.
+        let read10 = Read {
+            min_size: CdbSize::Ten,
+            ..Read::new(100, 0, BS)
+        };
+        let write16 = Write {
+            min_size: CdbSize::Sixteen,
+            ..Write::new(100, 0, BS)
+        };
+        for cdb in [read10.to_bytes(), write16.to_bytes()] {
+            assert_eq!(cdb_lbas(&cdb), None);
+            let result = t.submit(&mut Command::new(&cdb)).unwrap();
+            assert_eq!(result.status, ScsiStatus::Good, "{cdb:02x?}");
+        }
+        assert_eq!(cdb_lbas(&PreFetch::new(7, 0).to_bytes()), Some(7..u64::MAX));
+        assert_eq!(
+            cdb_lbas(&WriteSame::new(7, 0, BS).to_bytes()),
+            Some(7..u64::MAX)
+        );
+        assert_eq!(cdb_lbas(&WriteSame::new(7, 2, BS).to_bytes()), Some(7..9));
+
+        // Commands without an LBA never match.
+        let cdb = TestUnitReady.to_bytes();
+        let result = t.submit(&mut Command::new(&cdb)).unwrap();
+        assert_eq!(result.status, ScsiStatus::Good);
+    }
+
+    #[test]
+    fn probability() {
+        let pattern = |seed| {
+            let mut t = injector()
+                .with_seed(seed)
+                .with_rule(FaultRule::new(Fault::Status(ScsiStatus::Busy)).probability(0.5));
+            (0..64)
+                .map(|_| status(read(&mut t, 0, 1)) == ScsiStatus::Busy)
+                .collect::<Vec<_>>()
+        };
+
+        let a = pattern(42);
+        assert_eq!(a, pattern(42));
+        assert_ne!(a, pattern(43));
+        let n = a.iter().filter(|&&busy| busy).count();
+        assert!(n > 0 && n < a.len(), "{n} of {} injected", a.len());
+
+        // Probabilities are clamped.
+        for (p, busy) in [(-1.0, false), (0.0, false), (1.0, true), (2.0, true)] {
+            let mut t = injector()
+                .with_rule(FaultRule::new(Fault::Status(ScsiStatus::Busy)).probability(p));
+            for _ in 0..16 {
+                assert_eq!(status(read(&mut t, 0, 1)) == ScsiStatus::Busy, busy, "{p}");
+            }
+        }
+    }
+
+    #[test]
+    fn check_condition() {
+        let fault = Fault::check_condition(SenseKey::MediumError, 0x11, 0x04);
+        let mut t = injector().with_rule(FaultRule::new(fault));
+        let cdb = Write::new(0, 1, BS).to_bytes();
+        let data = [0xaa; 512];
+
+        let mut sense = [0xffu8; 32];
+        let result = t
+            .submit(&mut Command::new(&cdb).data_out(&data).sense(&mut sense))
+            .unwrap();
+        assert_eq!(result.status, ScsiStatus::CheckCondition);
+        assert_eq!(result.resid, data.len());
+        assert_eq!(result.rqresid, sense.len() - FIXED_SENSE_LEN);
+        assert_eq!(
+            &sense[..FIXED_SENSE_LEN],
+            &[0x70, 0, 0x03, 0, 0, 0, 0, 10, 0, 0, 0, 0, 0x11, 0x04, 0, 0, 0, 0]
+        );
+        assert!(sense[FIXED_SENSE_LEN..].iter().all(|&b| b == 0xff));
+        let parsed = Sense::from_result(&sense, &result).unwrap();
+        assert_eq!(parsed.key(), SenseKey::MediumError);
+        assert_eq!((parsed.asc(), parsed.ascq()), (0x11, 0x04));
+        assert_eq!(t.inner().data()[0], 0);
+
+        // A short sense buffer gets what fits.
+        let mut sense = [0u8; 8];
+        let result = t
+            .submit(&mut Command::new(&cdb).data_out(&data).sense(&mut sense))
+            .unwrap();
+        assert_eq!(result.rqresid, 0);
+        assert_eq!(sense, [0x70, 0, 0x03, 0, 0, 0, 0, 10]);
+
+        let result = t.submit(&mut Command::new(&cdb).data_out(&data)).unwrap();
+        assert_eq!(result.status, ScsiStatus::CheckCondition);
+        assert_eq!(result.rqresid, 0);
+    }
+
+    #[test]
+    fn short_transfer() {
+        let mut t = injector()
+            .with_rule(FaultRule::new(Fault::ShortTransfer { resid: 512 }).nth(1))
+            .with_rule(FaultRule::new(Fault::ShortTransfer { resid: usize::MAX }));
+
+        let data: Vec<u8> = (0..2048).map(|i| i as u8).collect();
+        let result = write(&mut t, 0, &data).unwrap();
+        assert_eq!(result.status, ScsiStatus::Good);
+        assert_eq!(result.resid, 512);
+        // The command was still sent.
+        assert_eq!(&t.inner().data()[..2048], &data[..]);
+
+        assert_eq!(read(&mut t, 0, 4).unwrap().resid, 2048);
+        let cdb = TestUnitReady.to_bytes();
+        assert_eq!(t.submit(&mut Command::new(&cdb)).unwrap().resid, 0);
+    }
+
+    #[test]
+    fn errors() {
+        let mut t = injector()
+            .with_rule(FaultRule::new(Fault::Timeout).nth(1))
+            .with_rule(FaultRule::new(Fault::Os(libc::EIO)).nth(2));
+
+        assert!(matches!(read(&mut t, 0, 1), Err(ScsiError::Timeout)));
+        match read(&mut t, 0, 1) {
+            Err(ScsiError::Os(e)) => assert_eq!(e.raw_os_error(), Some(libc::EIO)),
+            r => panic!("expected EIO, got {r:?}"),
+        }
+        assert_eq!(status(read(&mut t, 0, 1)), ScsiStatus::Good);
+        assert_eq!(t.max_xfer().unwrap(), t.inner_mut().max_xfer().unwrap());
+    }
+}
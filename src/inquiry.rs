@@ -0,0 +1,358 @@
+/*
+ * Copyright 2025 Jason King
+ */
+
+use std::fmt;
+
+/// The length of standard INQUIRY data up to and including the product
+/// revision level, the minimum a device must return.
+pub const INQUIRY_MIN_LEN: usize = 36;
+
+/// Decoded standard INQUIRY data (SPC-5 6.7.2).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StandardInquiry {
+    pub peripheral_qualifier: u8,
+    pub device_type: u8,
+    /// Removable medium.
+    pub rmb: bool,
+    pub version: u8,
+    /// Supports setting NACA in the CDB control byte.
+    pub normaca: bool,
+    /// Uses the hierarchical addressing model for LUNs.
+    pub hisup: bool,
+    pub response_data_format: u8,
+    /// The number of bytes following byte 4, as reported by the device.
+    pub additional_length: u8,
+    /// Contains an embedded storage array controller.
+    pub sccs: bool,
+    /// Contains an access controls coordinator.
+    pub acc: bool,
+    /// Target port group support (asymmetric logical unit access).
+    pub tpgs: u8,
+    /// Supports third-party copy commands.
+    pub three_pc: bool,
+    /// Supports protection information.
+    pub protect: bool,
+    /// Contains an embedded enclosure services component.
+    pub encserv: bool,
+    /// Multiple SCSI target ports.
+    pub multip: bool,
+    /// Supports command queuing.
+    pub cmdque: bool,
+    pub vendor: String,
+    pub product: String,
+    pub revision: String,
+    /// Bytes 36 through 55, if returned.
+    pub vendor_specific: Vec<u8>,
+    /// The non-zero version descriptors, in the order returned.
+    pub version_descriptors: Vec<VersionDescriptor>,
+}
+
+impl StandardInquiry {
+    /// Decode standard INQUIRY data. Returns `None` if `buf` is shorter
+    /// than [`INQUIRY_MIN_LEN`]. Data beyond the additional length reported
+    /// by the device is ignored.
+    pub fn parse(buf: &[u8]) -> Option<Self> {
+        if buf.len() < INQUIRY_MIN_LEN {
+            return None;
+        }
+
+        let buf = &buf[..(buf[4] as usize + 5).clamp(INQUIRY_MIN_LEN, buf.len())];
+        let vendor_specific = match buf.len() {
+            n if n > 36 => buf[36..n.min(56)].to_vec(),
+            _ => Vec::new(),
+        };
+        let version_descriptors = match buf.get(58..) {
+            Some(vd) => vd[..vd.len().min(16)]
+                .chunks_exact(2)
+                .map(|b| u16::from_be_bytes([b[0], b[1]]))
+                .filter(|&v| v != 0)
+                .map(VersionDescriptor)
+                .collect(),
+            None => Vec::new(),
+        };
+
+        Some(Self {
+            peripheral_qualifier: buf[0] >> 5,
+            device_type: buf[0] & 0x1f,
+            rmb: buf[1] & 0x80 != 0,
+            version: buf[2],
+            normaca: buf[3] & 0x20 != 0,
+            hisup: buf[3] & 0x10 != 0,
+            response_data_format: buf[3] & 0x0f,
+            additional_length: buf[4],
+            sccs: buf[5] & 0x80 != 0,
+            acc: buf[5] & 0x40 != 0,
+            tpgs: (buf[5] >> 4) & 0x03,
+            three_pc: buf[5] & 0x08 != 0,
+            protect: buf[5] & 0x01 != 0,
+            encserv: buf[6] & 0x40 != 0,
+            multip: buf[6] & 0x10 != 0,
+            cmdque: buf[7] & 0x02 != 0,
+            vendor: ascii(&buf[8..16]),
+            product: ascii(&buf[16..32]),
+            revision: ascii(&buf[32..36]),
+            vendor_specific,
+            version_descriptors,
+        })
+    }
+
+    /// The total length of the INQUIRY data the device has available.
+    pub fn total_length(&self) -> usize {
+        self.additional_length as usize + 5
+    }
+}
+
+/// Convert a space padded ASCII field to a trimmed string.
+fn ascii(b: &[u8]) -> String {
+    String::from_utf8_lossy(b)
+        .trim_matches(|c: char| c == ' ' || c == '\0')
+        .to_string()
+}
+
+/// A version descriptor from standard INQUIRY data, identifying a standard
+/// the device claims conformance to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct VersionDescriptor(pub u16);
+
+impl VersionDescriptor {
+    /// The standard this descriptor belongs to (e.g. "SPC-4"), if known.
+    ///
+    /// Descriptors are assigned in blocks of 32 per standard; the low bits
+    /// select a particular revision, which is not distinguished here.
+    pub fn name(&self) -> Option<&'static str> {
+        let base = self.0 & !0x1f;
+        VERSION_DESCRIPTORS
+            .binary_search_by_key(&base, |&(v, _)| v)
+            .ok()
+            .map(|i| VERSION_DESCRIPTORS[i].1)
+    }
+
+    /// True if the descriptor claims the standard without a specific
+    /// revision.
+    pub fn no_version_claimed(&self) -> bool {
+        self.0 & 0x1f == 0
+    }
+}
+
+impl fmt::Display for VersionDescriptor {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.name() {
+            Some(name) if self.no_version_claimed() => {
+                write!(f, "{name} (no version claimed)")
+            }
+            Some(name) => write!(f, "{name} ({:#06x})", self.0),
+            None => write!(f, "{:#06x}", self.0),
+        }
+    }
+}
+
+/// The first code of each standard's block of version descriptors.
+/// Must be kept sorted.
+static VERSION_DESCRIPTORS: &[(u16, &str)] = &[
+    (0x0020, "SAM"),
+    (0x0040, "SAM-2"),
+    (0x0060, "SAM-3"),
+    (0x0080, "SAM-4"),
+    (0x00a0, "SAM-5"),
+    (0x00c0, "SAM-6"),
+    (0x0120, "SPC"),
+    (0x0140, "MMC"),
+    (0x0160, "SCC"),
+    (0x0180, "SBC"),
+    (0x01a0, "SMC"),
+    (0x01c0, "SES"),
+    (0x01e0, "SCC-2"),
+    (0x0200, "SSC"),
+    (0x0220, "RBC"),
+    (0x0240, "MMC-2"),
+    (0x0260, "SPC-2"),
+    (0x0280, "OCRW"),
+    (0x02a0, "MMC-3"),
+    (0x02c0, "RMC"),
+    (0x02e0, "SMC-2"),
+    (0x0300, "SPC-3"),
+    (0x0320, "SBC-2"),
+    (0x0340, "OSD"),
+    (0x0360, "SSC-2"),
+    (0x0380, "BCC"),
+    (0x03a0, "MMC-4"),
+    (0x03c0, "ADC"),
+    (0x03e0, "SES-2"),
+    (0x0400, "SSC-3"),
+    (0x0420, "MMC-5"),
+    (0x0440, "OSD-2"),
+    (0x0460, "SPC-4"),
+    (0x0480, "SMC-3"),
+    (0x04a0, "ADC-2"),
+    (0x04c0, "SBC-3"),
+    (0x04e0, "MMC-6"),
+    (0x0500, "ADC-3"),
+    (0x0520, "SSC-4"),
+    (0x0560, "OSD-3"),
+    (0x0580, "SES-3"),
+    (0x05a0, "SSC-5"),
+    (0x05c0, "SPC-5"),
+    (0x05e0, "SFSC"),
+    (0x0600, "SBC-4"),
+    (0x0620, "ZBC"),
+    (0x0640, "ADC-4"),
+    (0x0660, "ZBC-2"),
+    (0x0680, "SES-4"),
+    (0x0820, "SSA-TL2"),
+    (0x0840, "SSA-TL1"),
+    (0x0860, "SSA-S3P"),
+    (0x0880, "SSA-S2P"),
+    (0x08a0, "SIP"),
+    (0x08c0, "FCP"),
+    (0x08e0, "SBP-2"),
+    (0x0900, "FCP-2"),
+    (0x0920, "SST"),
+    (0x0940, "SRP"),
+    (0x0960, "iSCSI"),
+    (0x0980, "SBP-3"),
+    (0x09a0, "SRP-2"),
+    (0x09c0, "ADP"),
+    (0x09e0, "ADT"),
+    (0x0a00, "FCP-3"),
+    (0x0a20, "ADT-2"),
+    (0x0a40, "FCP-4"),
+    (0x0a60, "ADT-3"),
+    (0x0be0, "SAS"),
+    (0x0c00, "SAS-1.1"),
+    (0x0c20, "SAS-2"),
+    (0x0c40, "SAS-2.1"),
+    (0x0c60, "SAS-3"),
+    (0x0c80, "SAS-4"),
+    (0x1720, "USB"),
+    (0x1ea0, "SAT"),
+    (0x1ec0, "SAT-2"),
+    (0x1ee0, "SAT-3"),
+    (0x1f00, "SAT-4"),
+];
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Standard INQUIRY data as returned by a USB flash drive: just the
+    /// mandatory 36 bytes.
+    const USB_STICK: [u8; 36] = *b"\x00\x80\x06\x02\x1f\x00\x00\x00\
+        SanDisk \
+        Cruzer Blade    \
+        1.00";
+
+    /// Standard INQUIRY data as returned by a SAS disk, with the vendor
+    /// specific bytes and version descriptors (SAM-5, SAS-3, SPC-4 and
+    /// SBC-3 revisions, then no version claimed for SPC-5).
+    fn sas_disk() -> [u8; 96] {
+        let mut buf = [0u8; 96];
+        buf[..8].copy_from_slice(&[0x00, 0x00, 0x06, 0x12, 91, 0xb9, 0x10, 0x02]);
+        buf[8..36].copy_from_slice(b"SEAGATE ST4000NM0023    0004");
+        buf[36..56].copy_from_slice(b"Z1Z8ABCD            ");
+        for (i, v) in [0x00a2u16, 0x0c60, 0x0460, 0x04c5, 0x05c0]
+            .into_iter()
+            .enumerate()
+        {
+            buf[58 + 2 * i..60 + 2 * i].copy_from_slice(&v.to_be_bytes());
+        }
+        buf
+    }
+
+    #[test]
+    fn parse_minimal() {
+        let inq = StandardInquiry::parse(&USB_STICK).unwrap();
+        assert_eq!(inq.peripheral_qualifier, 0);
+        assert_eq!(inq.device_type, 0);
+        assert!(inq.rmb);
+        assert_eq!(inq.version, 6);
+        assert_eq!(inq.response_data_format, 2);
+        assert_eq!(inq.additional_length, 31);
+        assert_eq!(inq.total_length(), INQUIRY_MIN_LEN);
+        assert!(!inq.cmdque && !inq.protect && !inq.hisup);
+        assert_eq!(inq.vendor, "SanDisk");
+        assert_eq!(inq.product, "Cruzer Blade");
+        assert_eq!(inq.revision, "1.00");
+        assert!(inq.vendor_specific.is_empty());
+        assert!(inq.version_descriptors.is_empty());
+
+        assert_eq!(StandardInquiry::parse(&USB_STICK[..35]), None);
+    }
+
+    #[test]
+    fn parse_full() {
+        let buf = sas_disk();
+        let inq = StandardInquiry::parse(&buf).unwrap();
+        assert!(!inq.rmb);
+        assert!(inq.hisup);
+        assert_eq!(inq.response_data_format, 2);
+        assert_eq!(inq.total_length(), 96);
+        assert!(inq.sccs && !inq.acc);
+        assert_eq!(inq.tpgs, 3);
+        assert!(inq.three_pc && inq.protect);
+        assert!(inq.multip && !inq.encserv);
+        assert!(inq.cmdque);
+        assert_eq!(inq.vendor, "SEAGATE");
+        assert_eq!(inq.product, "ST4000NM0023");
+        assert_eq!(inq.revision, "0004");
+        assert_eq!(inq.vendor_specific, b"Z1Z8ABCD            ");
+        let names: Vec<_> = inq.version_descriptors.iter().map(|v| v.name()).collect();
+        assert_eq!(
+            names,
+            [
+                Some("SAM-5"),
+                Some("SAS-3"),
+                Some("SPC-4"),
+                Some("SBC-3"),
+                Some("SPC-5")
+            ]
+        );
+
+        // Data beyond the additional length is ignored, and a short
+        // buffer yields only what it holds.
+        let mut short = buf;
+        short[4] = 31;
+        let inq = StandardInquiry::parse(&short).unwrap();
+        assert!(inq.vendor_specific.is_empty());
+        assert!(inq.version_descriptors.is_empty());
+        let inq = StandardInquiry::parse(&buf[..62]).unwrap();
+        assert_eq!(inq.vendor_specific.len(), 20);
+        assert_eq!(
+            inq.version_descriptors,
+            [VersionDescriptor(0x00a2), VersionDescriptor(0x0c60)]
+        );
+    }
+
+    #[test]
+    fn version_descriptors() {
+        assert_eq!(VersionDescriptor(0x0460).name(), Some("SPC-4"));
+        assert_eq!(VersionDescriptor(0x047f).name(), Some("SPC-4"));
+        assert_eq!(VersionDescriptor(0x0480).name(), Some("SMC-3"));
+        assert_eq!(VersionDescriptor(0x1f00).name(), Some("SAT-4"));
+        assert_eq!(VersionDescriptor(0x0000).name(), None);
+        assert_eq!(VersionDescriptor(0xffe0).name(), None);
+
+        assert!(VersionDescriptor(0x05c0).no_version_claimed());
+        assert!(!VersionDescriptor(0x04c5).no_version_claimed());
+
+        assert_eq!(
+            VersionDescriptor(0x05c0).to_string(),
+            "SPC-5 (no version claimed)"
+        );
+        assert_eq!(VersionDescriptor(0x04c5).to_string(), "SBC-3 (0x04c5)");
+        assert_eq!(VersionDescriptor(0xffe1).to_string(), "0xffe1");
+    }
+
+    #[test]
+    fn table_sorted_and_unique() {
+        for pair in VERSION_DESCRIPTORS.windows(2) {
+            assert!(pair[0].0 < pair[1].0, "{:04x?}", pair);
+        }
+        for &(code, name) in VERSION_DESCRIPTORS {
+            // Lookups mask off the revision bits.
+            assert_eq!(code & 0x1f, 0, "{name}");
+            assert_eq!(VersionDescriptor(code).name(), Some(name));
+            assert_eq!(VersionDescriptor(code | 0x1f).name(), Some(name));
+        }
+    }
+}
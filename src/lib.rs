@@ -4,31 +4,170 @@
 
 use bitflags::bitflags;
 use libc::{c_int, c_short, c_uchar, c_ulong, c_void, ioctl, size_t, uintptr_t};
+use std::marker::PhantomData;
 use std::os::fd::RawFd;
 
+mod aligned;
+mod asc;
+mod block;
+mod blockdev;
+mod capacity;
+pub mod cdb;
+mod device;
+mod emulator;
+mod error;
+mod fault;
+mod inquiry;
+mod replay;
+mod retry;
+mod sense;
+#[cfg(target_os = "linux")]
+pub mod sgio;
+mod status;
+mod timeout;
+mod trace;
+mod transport;
+mod uscsi;
+pub mod vpd;
+
+pub use aligned::{alignment_for, AlignedBuf, BufPool};
+pub use asc::{asc_ascq_str, asc_ascq_text, ASC_ASCQ, ASC_ASCQ_RANGES};
+pub use block::{BlockIoError, TransferLimits};
+pub use blockdev::BlockDevice;
+pub use capacity::Capacity;
+pub use device::Device;
+pub use emulator::Emulator;
+pub use error::ScsiError;
+pub use fault::{Fault, FaultInjector, FaultRule};
+pub use inquiry::{StandardInquiry, VersionDescriptor, INQUIRY_MIN_LEN};
+pub use replay::{Recorder, Replay, ReplayError, FORMAT_VERSION};
+pub use retry::{is_idempotent, RetryCondition, RetryPolicy, RetryRule};
+pub use sense::{
+    AtaStatusReturn, Descriptor, Descriptors, Sense, SenseFormat, SenseKey, SenseKeySpecific,
+    UserDataSegment,
+};
+#[cfg(target_os = "linux")]
+pub use sgio::SgIo;
+pub use status::{CommandResult, ScsiStatus};
+pub use timeout::Timeout;
+pub use transport::{Command, DataBuffer, DataDirection, IoVecMut, Transport};
+pub use uscsi::Uscsi;
+
+/// The native transport of the host platform, used by default by
+/// [`Device`] and [`BlockDevice`]: [`SgIo`] on Linux, [`Uscsi`] elsewhere.
+#[cfg(target_os = "linux")]
+pub type NativeTransport = SgIo;
+/// The native transport of the host platform, used by default by
+/// [`Device`] and [`BlockDevice`]: [`SgIo`] on Linux, [`Uscsi`] elsewhere.
+#[cfg(not(target_os = "linux"))]
+pub type NativeTransport = Uscsi;
+
 pub const USCSIIOC: c_ulong = 0x04 << 8;
 pub const USCSICMD: c_ulong = USCSIIOC | 201;
 pub const USCSIMAXXFER: c_ulong = USCSIIOC | 202;
 
 bitflags! {
+    /// The `uscsi_flags` of a command (see uscsi(4I)).
+    #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
     pub struct Flags: c_int {
+        /// Send data to the device (the absence of [`Flags::READ`]).
+        const WRITE = 0x0000_0000;
+        /// Do not log error messages for the command.
         const SILENT = 0x0000_0001;
+        /// Fail the command on any error, without retries by the driver.
         const DIAGNOSE = 0x0000_0002;
+        /// Isolate the command from normal commands in the driver's queue.
         const ISOLATE = 0x0000_0004;
+        /// Read data from the device.
         const READ = 0x0000_0008;
-        const WRITE = 0x0000_0000;
+        /// Run the command without parity checking.
+        const NOPARITY = 0x0000_0010;
+        /// Run the command without allowing disconnects.
+        const NODISCON = 0x0000_0020;
+        /// Run the command in polled mode, without interrupts. Not for use
+        /// by normal applications.
+        const NOINTR = 0x0000_0040;
+        /// Disable tagged queueing for the command.
+        const NOTAG = 0x0000_0100;
+        /// Issue the command with an ORDERED queue tag.
+        const OTAG = 0x0000_0200;
+        /// Issue the command with a HEAD OF QUEUE tag.
+        const HTAG = 0x0000_0400;
+        /// Place the command at the head of the host adapter's queue.
+        const HEAD = 0x0000_0800;
+        /// Set the bus to asynchronous mode.
+        const ASYNC = 0x0000_1000;
+        /// Return the bus to synchronous mode, if possible.
+        const SYNC = 0x0000_2000;
+        /// Reset the target.
         const RESET = 0x0000_4000;
+        /// An alias for [`Flags::RESET`].
+        const RESET_TARGET = 0x0000_4000;
+        /// Reset all targets on the bus.
         const RESET_ALL = 0x0000_8000;
+        /// Fetch sense data into the request sense buffer on a CHECK
+        /// CONDITION. Set automatically when a sense buffer is supplied.
         const RQENABLE = 0x0001_0000;
+        /// Renegotiate wide and synchronous transfers on the next command.
         const RENEGOT = 0x0002_0000;
+        /// Reset the logical unit.
         const RESET_LUN = 0x0004_0000;
+        /// Send the command down the path given by `path_instance`. Set
+        /// automatically when a path instance is supplied.
         const PATH_INSTANCE = 0x0008_0000;
     }
 }
 
+/// The queue tag a command is issued with.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum TagQueue {
+    /// A SIMPLE tag (the default).
+    #[default]
+    Simple,
+    Ordered,
+    HeadOfQueue,
+    /// No tag: tagged queueing is disabled for the command.
+    Untagged,
+}
+
+impl TagQueue {
+    const MASK: Flags = Flags::NOTAG.union(Flags::OTAG).union(Flags::HTAG);
+
+    /// The flags that select this tag.
+    pub fn flags(&self) -> Flags {
+        match self {
+            TagQueue::Simple => Flags::empty(),
+            TagQueue::Ordered => Flags::OTAG,
+            TagQueue::HeadOfQueue => Flags::HTAG,
+            TagQueue::Untagged => Flags::NOTAG,
+        }
+    }
+
+    /// The tag selected by `flags`. If more than one tag flag is set,
+    /// NOTAG takes precedence, then HTAG.
+    pub fn from_flags(flags: Flags) -> Self {
+        if flags.contains(Flags::NOTAG) {
+            TagQueue::Untagged
+        } else if flags.contains(Flags::HTAG) {
+            TagQueue::HeadOfQueue
+        } else if flags.contains(Flags::OTAG) {
+            TagQueue::Ordered
+        } else {
+            TagQueue::Simple
+        }
+    }
+
+    /// Replace the tag selected by `flags` with this one.
+    pub fn apply(&self, flags: Flags) -> Flags {
+        flags.difference(Self::MASK) | self.flags()
+    }
+}
+
+/// A validated `struct uscsi_cmd`, borrowing the buffers of the
+/// [`Command`] it was built from.
 #[repr(C)]
 #[derive(Debug, Default)]
-pub struct UScsiCmd {
+pub struct UScsiCmd<'a> {
     flags: c_int,
     status: c_short,
     timeout: c_short,
@@ -42,96 +181,201 @@ pub struct UScsiCmd {
     rqresid: c_uchar,
     rqbuf: uintptr_t,
     path_instance: c_ulong,
+    _buffers: PhantomData<&'a mut [u8]>,
+}
+
+impl<'a> UScsiCmd<'a> {
+    /// Build the descriptor for `cmd`.
+    ///
+    /// The CDB length must agree with its opcode's group code (see
+    /// [`cdb::validate`]). A sense buffer longer than 255 bytes is clamped,
+    /// as only that much can be requested. `USCSICMD` cannot carry
+    /// bidirectional or vectored transfers, which fail with
+    /// [`ScsiError::Unsupported`] (see [`Command::with_contiguous_data`]
+    /// for the latter).
+    pub fn new(cmd: &'a mut Command<'_>) -> Result<Self, ScsiError> {
+        cdb::validate(cmd.cdb)?;
+        let cdblen = c_uchar::try_from(cmd.cdb.len())
+            .map_err(|_| ScsiError::InvalidCommand("CDB too long"))?;
+
+        let mut flags = cmd.flags - Flags::READ;
+        let (bufaddr, buflen) = match &mut cmd.data {
+            DataBuffer::None => (0, 0),
+            DataBuffer::In(data) => {
+                flags |= Flags::READ;
+                (data.as_mut_ptr() as uintptr_t, data.len())
+            }
+            DataBuffer::Out(data) => (data.as_ptr() as uintptr_t, data.len()),
+            DataBuffer::Bidirectional { .. } => {
+                return Err(ScsiError::Unsupported("bidirectional transfers"))
+            }
+            DataBuffer::InVectored(_) | DataBuffer::OutVectored(_) => {
+                return Err(ScsiError::Unsupported("vectored transfers"))
+            }
+        };
+        if cmd.path_instance.is_some() {
+            flags |= Flags::PATH_INSTANCE;
+        }
+        let (rqbuf, rqlen) = match cmd.sense.as_deref_mut() {
+            Some(sensebuf) => {
+                flags |= Flags::RQENABLE;
+                let len = sensebuf.len().min(c_uchar::MAX as usize) as c_uchar;
+                (sensebuf.as_mut_ptr() as uintptr_t, len)
+            }
+            None => (0, 0),
+        };
+
+        Ok(Self {
+            flags: flags.bits(),
+            // Timeout::MAX fits in a c_short.
+            timeout: cmd.timeout.as_secs() as c_short,
+            cdb: cmd.cdb.as_ptr() as uintptr_t,
+            bufaddr: if buflen == 0 { 0 } else { bufaddr },
+            buflen: buflen as size_t,
+            cdblen,
+            rqlen,
+            rqbuf,
+            path_instance: cmd.path_instance.unwrap_or(0) as c_ulong,
+            ..Default::default()
+        })
+    }
+}
+
+/// Vectored data is bounced through a contiguous buffer, as `USCSICMD`
+/// takes a single data address.
+unsafe fn common(fd: RawFd, cmd: &mut Command<'_>) -> Result<CommandResult, ScsiError> {
+    cmd.with_contiguous_data(|cmd| {
+        let mut cmd = UScsiCmd::new(cmd)?;
+
+        let ret = ioctl(fd, USCSICMD, &mut cmd as *mut _ as *mut c_void);
+        let err = std::io::Error::last_os_error();
+
+        // A command that completes with a non-GOOD status fails with EIO,
+        // but still has its status (and any sense data) filled in.
+        if ret == 0 || (err.raw_os_error() == Some(libc::EIO) && cmd.status != 0) {
+            Ok(CommandResult {
+                status: ScsiStatus::from(cmd.status as u8),
+                resid: cmd.resid,
+                rqresid: cmd.rqresid as usize,
+                rqstatus: ScsiStatus::from(cmd.rqstatus),
+                retries: 0,
+            })
+        } else {
+            Err(err.into())
+        }
+    })
 }
 
-unsafe fn common(
+/// Issue a data-in command, returning its status and residuals.
+///
+/// # Safety
+///
+/// `fd` must be an open descriptor for a device that understands
+/// `USCSICMD`. The caller is responsible for the CDB being one that is
+/// safe to send to the device. [`Device::read`] is the safe equivalent.
+pub unsafe fn read(
     fd: RawFd,
     cdb: &[u8],
-    data: uintptr_t,
-    datalen: usize,
+    data: &mut [u8],
     sense: Option<&mut [u8]>,
     flags: Flags,
     timeout: u16,
-) -> Result<(usize, usize), std::io::Error> {
-    let mut flags = flags;
-    let (rqbuf, rqlen) = if let Some(sensebuf) = sense {
-        flags = flags | Flags::RQENABLE;
-        (sensebuf.as_ptr() as uintptr_t, sensebuf.len() as c_uchar)
-    } else {
-        (0, 0)
-    };
-
-    let mut cmd = UScsiCmd {
-        flags: flags.bits(),
-        status: 0,
-        timeout: timeout as i16,
-        cdb: cdb.as_ptr() as _,
-        bufaddr: data,
-        buflen: datalen as size_t,
-        resid: 0,
-        cdblen: cdb.len() as c_uchar,
-        rqlen: rqlen,
-        rqstatus: 0,
-        rqresid: 0,
-        rqbuf: rqbuf,
-        path_instance: 0,
+) -> Result<CommandResult, ScsiError> {
+    let mut cmd = Command {
+        cdb,
+        data: DataBuffer::In(data),
+        sense,
+        flags,
+        timeout: Timeout::from_secs(timeout)?,
+        path_instance: None,
     };
 
-    match ioctl(fd, USCSICMD, &mut cmd as *mut _ as *mut c_void) {
-        0 => Ok((cmd.resid, cmd.rqresid as usize)),
-        _ => Err(std::io::Error::last_os_error()),
-    }
+    common(fd, &mut cmd)
 }
 
-pub unsafe fn read(
+/// Issue a data-out command, returning its status and residuals.
+///
+/// # Safety
+///
+/// `fd` must be an open descriptor for a device that understands
+/// `USCSICMD`. The caller is responsible for the CDB being one that is
+/// safe to send to the device. [`Device::write`] is the safe equivalent.
+///
+/// `data` is only read: it is passed to the driver as the source of the
+/// transfer, so it may be borrowed from shared or read-only memory (e.g. a
+/// memory-mapped file).
+pub unsafe fn write(
     fd: RawFd,
     cdb: &[u8],
-    data: &mut [u8],
+    data: &[u8],
     sense: Option<&mut [u8]>,
     flags: Flags,
-    timeout: u16
-) -> Result<(usize, usize), std::io::Error> {
-    let data_addr = data.as_mut_ptr() as uintptr_t;
-    let data_len = data.len();
-    let flags = flags | Flags::READ;
+    timeout: u16,
+) -> Result<CommandResult, ScsiError> {
+    let mut cmd = Command {
+        cdb,
+        data: DataBuffer::Out(data),
+        sense,
+        flags,
+        timeout: Timeout::from_secs(timeout)?,
+        path_instance: None,
+    };
 
-    common(fd, cdb, data_addr, data_len, sense, flags, timeout)
+    common(fd, &mut cmd)
 }
 
-pub unsafe fn write(
+/// Issue a command that transfers no data, returning its status.
+///
+/// # Safety
+///
+/// `fd` must be an open descriptor for a device that understands
+/// `USCSICMD`. The caller is responsible for the CDB being one that is
+/// safe to send to the device. [`Device::no_data`] is the safe equivalent.
+pub unsafe fn no_data(
     fd: RawFd,
     cdb: &[u8],
-    data: &mut [u8],
     sense: Option<&mut [u8]>,
     flags: Flags,
-    timeout: u16
-) -> Result<(usize, usize), std::io::Error> {
-    let data_addr = data.as_ptr() as uintptr_t;
-    let data_len = data.len();
-    let flags = flags | Flags::WRITE;
+    timeout: u16,
+) -> Result<CommandResult, ScsiError> {
+    let mut cmd = Command {
+        cdb,
+        data: DataBuffer::None,
+        sense,
+        flags,
+        timeout: Timeout::from_secs(timeout)?,
+        path_instance: None,
+    };
 
-    common(fd, cdb, data_addr, data_len, sense, flags, timeout)
+    common(fd, &mut cmd)
 }
 
-pub unsafe fn reset(fd: RawFd) -> Result<(), std::io::Error> {
+/// Reset the target.
+///
+/// # Safety
+///
+/// `fd` must be an open descriptor for a device that understands
+/// `USCSICMD`. [`Device::reset`] is the safe equivalent.
+pub unsafe fn reset(fd: RawFd) -> Result<(), ScsiError> {
     let flags = Flags::RESET;
-    let mut cmd = UScsiCmd::default();
-
-    cmd.flags = flags.bits();
+    let mut cmd = UScsiCmd {
+        flags: flags.bits(),
+        ..Default::default()
+    };
 
     match ioctl(fd, USCSICMD, &mut cmd as *mut _ as *mut c_void) {
         0 => Ok(()),
-        _ => Err(std::io::Error::last_os_error()),
+        _ => Err(std::io::Error::last_os_error().into()),
     }
 }
 
-pub fn max_xfer(fd: RawFd) -> Result<usize, std::io::Error> {
+pub fn max_xfer(fd: RawFd) -> Result<usize, ScsiError> {
     let mut val: u64 = 0;
 
     // SAFETY: This should only query the kernel driver and not result
     // in any device I/O
     match unsafe { ioctl(fd, USCSIMAXXFER, &mut val as *mut _) } {
         0 => Ok(val as usize),
-        _ => Err(std::io::Error::last_os_error()),
+        _ => Err(std::io::Error::last_os_error().into()),
     }
-}
\ No newline at end of file
+}
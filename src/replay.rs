@@ -0,0 +1,728 @@
+/*
+ * Copyright 2025 Jason King
+ */
+
+//! Recording and replaying the commands sent to a transport.
+//!
+//! A capture starts with the magic `USCSIREC` and a little-endian `u16`
+//! format version, followed by one record per call made on the recorded
+//! transport. All integers are little-endian and byte strings are prefixed
+//! with their `u32` length. A command record holds:
+//!
+//! | field       | encoding                                            |
+//! |-------------|-----------------------------------------------------|
+//! | kind        | `u8`, 1                                             |
+//! | cdb         | bytes                                               |
+//! | direction   | `u8` (0 none, 1 in, 2 out, 3 bidirectional)         |
+//! | flags       | `u32`                                               |
+//! | timeout     | `u16` seconds                                       |
+//! | data-out    | bytes                                               |
+//! | data-in len | `u64`, the length of the data-in buffer             |
+//! | duration    | `u64` microseconds                                  |
+//! | outcome     | `u8` 0 then a result, or `u8` 1 then an error       |
+//!
+//! A result is the status and request sense status (`u8` each), the data
+//! and sense residuals (`u64` each), then the data-in and sense bytes that
+//! were transferred. Reset (kind 2) and maximum transfer size (kind 3)
+//! records hold only the outcome, a successful maximum transfer size being
+//! a `u64`.
+
+use crate::{Command, CommandResult, DataDirection, ScsiError, ScsiStatus, Sense, Transport};
+use std::fmt;
+use std::io::{self, ErrorKind, Read, Write};
+use std::time::Instant;
+
+const MAGIC: &[u8; 8] = b"USCSIREC";
+
+/// The version of the capture format written by [`Recorder`].
+pub const FORMAT_VERSION: u16 = 1;
+
+const KIND_COMMAND: u8 = 1;
+const KIND_RESET: u8 = 2;
+const KIND_MAX_XFER: u8 = 3;
+
+/// The I/O error kinds preserved in a capture, by code. Others are recorded
+/// as [`ErrorKind::Other`]; raw errno values are not kept, as they differ
+/// between platforms.
+const ERROR_KINDS: [ErrorKind; 12] = [
+    ErrorKind::Other,
+    ErrorKind::NotFound,
+    ErrorKind::PermissionDenied,
+    ErrorKind::Interrupted,
+    ErrorKind::WouldBlock,
+    ErrorKind::TimedOut,
+    ErrorKind::InvalidInput,
+    ErrorKind::InvalidData,
+    ErrorKind::Unsupported,
+    ErrorKind::OutOfMemory,
+    ErrorKind::ResourceBusy,
+    ErrorKind::UnexpectedEof,
+];
+
+/// A [`Transport`] that passes everything through to another transport,
+/// recording each call and its outcome to a capture that [`Replay`] can
+/// serve back.
+///
+/// Each record is flushed as soon as it is written, so a capture survives
+/// the process crashing. A failure to write the capture does not fail the
+/// command; the first such error is returned by
+/// [`finish`](Self::finish).
+#[derive(Debug)]
+pub struct Recorder<T: Transport, W: Write> {
+    inner: T,
+    out: W,
+    error: Option<io::Error>,
+}
+
+impl<T: Transport, W: Write> Recorder<T, W> {
+    /// Record the commands sent to `inner` to `out`, starting by writing
+    /// the capture header.
+    pub fn new(inner: T, mut out: W) -> io::Result<Self> {
+        out.write_all(MAGIC)?;
+        out.write_all(&FORMAT_VERSION.to_le_bytes())?;
+        out.flush()?;
+        Ok(Self {
+            inner,
+            out,
+            error: None,
+        })
+    }
+
+    pub fn inner(&self) -> &T {
+        &self.inner
+    }
+
+    pub fn inner_mut(&mut self) -> &mut T {
+        &mut self.inner
+    }
+
+    /// Stop recording, returning the transport and the capture writer, or
+    /// the first error hit writing the capture.
+    pub fn finish(mut self) -> io::Result<(T, W)> {
+        match self.error.take() {
+            Some(e) => Err(e),
+            None => Ok((self.inner, self.out)),
+        }
+    }
+
+    fn save(&mut self, rec: Vec<u8>) {
+        if self.error.is_some() {
+            return;
+        }
+        if let Err(e) = self.out.write_all(&rec).and_then(|_| self.out.flush()) {
+            self.error = Some(e);
+        }
+    }
+}
+
+impl<T: Transport, W: Write> Transport for Recorder<T, W> {
+    fn submit(&mut self, cmd: &mut Command<'_>) -> Result<CommandResult, ScsiError> {
+        let mut rec = vec![KIND_COMMAND];
+        put_bytes(&mut rec, cmd.cdb);
+        rec.push(direction_code(cmd.direction()));
+        rec.extend_from_slice(&(cmd.flags.bits() as u32).to_le_bytes());
+        rec.extend_from_slice(&cmd.timeout.as_secs().to_le_bytes());
+        put_bytes(&mut rec, &cmd.data.output_bytes(usize::MAX));
+        let in_len = cmd.data.input_len();
+        put_u64(&mut rec, in_len as u64);
+
+        let start = Instant::now();
+        let result = self.inner.submit(cmd);
+        put_u64(&mut rec, start.elapsed().as_micros() as u64);
+
+        match &result {
+            Ok(r) => {
+                rec.push(0);
+                rec.push(r.status.into());
+                rec.push(r.rqstatus.into());
+                put_u64(&mut rec, r.resid as u64);
+                put_u64(&mut rec, r.rqresid as u64);
+                let data_in = cmd.data.input_bytes(in_len.saturating_sub(r.resid));
+                put_bytes(&mut rec, &data_in);
+                let sense = match cmd.sense.as_deref() {
+                    Some(buf) => &buf[..r.sense_len(buf.len())],
+                    None => &[],
+                };
+                put_bytes(&mut rec, sense);
+            }
+            Err(e) => {
+                rec.push(1);
+                put_error(&mut rec, e);
+            }
+        }
+
+        self.save(rec);
+        result
+    }
+
+    fn reset(&mut self) -> Result<(), ScsiError> {
+        let result = self.inner.reset();
+        let mut rec = vec![KIND_RESET];
+        match &result {
+            Ok(()) => rec.push(0),
+            Err(e) => {
+                rec.push(1);
+                put_error(&mut rec, e);
+            }
+        }
+        self.save(rec);
+        result
+    }
+
+    fn max_xfer(&mut self) -> Result<usize, ScsiError> {
+        let result = self.inner.max_xfer();
+        let mut rec = vec![KIND_MAX_XFER];
+        match &result {
+            Ok(max) => {
+                rec.push(0);
+                put_u64(&mut rec, *max as u64);
+            }
+            Err(e) => {
+                rec.push(1);
+                put_error(&mut rec, e);
+            }
+        }
+        self.save(rec);
+        result
+    }
+}
+
+/// A [`Transport`] that serves back the outcomes recorded by a
+/// [`Recorder`], in order.
+///
+/// Each call must match the next record: the same kind of call and, for
+/// commands, the same CDB, direction, data-out and data-in length. On the
+/// first mismatch (or a malformed or exhausted capture) the call fails with
+/// an [`ErrorKind::InvalidData`] error wrapping a [`ReplayError`], as do
+/// all later calls.
+#[derive(Debug)]
+pub struct Replay {
+    buf: Vec<u8>,
+    pos: usize,
+    record: u64,
+    failed: Option<ReplayError>,
+}
+
+impl Replay {
+    /// Read a capture from `input`, checking its header.
+    pub fn new<R: Read>(mut input: R) -> io::Result<Self> {
+        let mut buf = Vec::new();
+        input.read_to_end(&mut buf)?;
+        Self::from_bytes(buf)
+    }
+
+    /// Use the capture in `buf`, checking its header.
+    pub fn from_bytes(buf: Vec<u8>) -> io::Result<Self> {
+        if buf.len() < MAGIC.len() + 2 || &buf[..MAGIC.len()] != MAGIC {
+            return Err(io::Error::new(
+                ErrorKind::InvalidData,
+                "not a uscsi command capture",
+            ));
+        }
+        let version = u16::from_le_bytes([buf[8], buf[9]]);
+        if version != FORMAT_VERSION {
+            return Err(io::Error::new(
+                ErrorKind::InvalidData,
+                format!("unsupported capture format version {version}"),
+            ));
+        }
+
+        Ok(Self {
+            buf,
+            pos: MAGIC.len() + 2,
+            record: 0,
+            failed: None,
+        })
+    }
+
+    /// The number of records served so far.
+    pub fn position(&self) -> u64 {
+        self.record
+    }
+
+    /// True once every record has been served.
+    pub fn is_finished(&self) -> bool {
+        self.pos >= self.buf.len()
+    }
+
+    /// Serve the next record, which must be of `kind`, via `f`, which
+    /// fails with the reason the call does not match the record. Any
+    /// failure is latched so that every later call fails too.
+    fn next<V>(
+        &mut self,
+        kind: u8,
+        f: impl FnOnce(&mut Reader<'_>) -> Result<V, String>,
+    ) -> Result<V, ReplayError> {
+        if let Some(e) = &self.failed {
+            return Err(e.clone());
+        }
+
+        let mut r = Reader {
+            buf: &self.buf,
+            pos: self.pos,
+        };
+        let result = match r.u8() {
+            Ok(k) if k == kind => f(&mut r),
+            Err(reason) => Err(reason),
+            Ok(k) => Err(format!(
+                "expected a {} record, found a {}",
+                kind_name(kind),
+                kind_name(k)
+            )),
+        };
+
+        match result {
+            Ok(v) => {
+                self.pos = r.pos;
+                self.record += 1;
+                Ok(v)
+            }
+            Err(reason) => {
+                let e = ReplayError {
+                    record: self.record,
+                    reason,
+                };
+                self.failed = Some(e.clone());
+                Err(e)
+            }
+        }
+    }
+}
+
+impl Transport for Replay {
+    fn submit(&mut self, cmd: &mut Command<'_>) -> Result<CommandResult, ScsiError> {
+        self.next(KIND_COMMAND, |r| {
+            let cdb = r.bytes()?;
+            if cdb != cmd.cdb {
+                return Err(format!("CDB {:02x?} was recorded as {:02x?}", cmd.cdb, cdb));
+            }
+            let direction = r.u8()?;
+            if direction != direction_code(cmd.direction()) {
+                return Err(format!(
+                    "direction {:?} does not match the recording",
+                    cmd.direction()
+                ));
+            }
+            let _flags = r.u32()?;
+            let _timeout = r.u16()?;
+            if r.bytes()? != cmd.data.output_bytes(usize::MAX) {
+                return Err("data-out differs from the recording".into());
+            }
+            let in_len = r.u64()?;
+            if in_len != cmd.data.input_len() as u64 {
+                return Err(format!(
+                    "data-in length {} was recorded as {in_len}",
+                    cmd.data.input_len()
+                ));
+            }
+            let _duration = r.u64()?;
+
+            if r.u8()? != 0 {
+                return Ok(Err(r.error()?));
+            }
+            let status = ScsiStatus::from(r.u8()?);
+            let rqstatus = ScsiStatus::from(r.u8()?);
+            let resid = r.u64()? as usize;
+            let _rqresid = r.u64()?;
+            cmd.data.fill_input(r.bytes()?);
+            let sense = r.bytes()?;
+            let rqresid = match cmd.sense.as_deref_mut() {
+                Some(buf) => {
+                    let n = sense.len().min(buf.len());
+                    buf[..n].copy_from_slice(&sense[..n]);
+                    buf.len() - n
+                }
+                None => 0,
+            };
+
+            Ok(Ok(CommandResult {
+                status,
+                resid,
+                rqresid,
+                rqstatus,
+                retries: 0,
+            }))
+        })
+        .unwrap_or_else(|e| Err(e.into()))
+    }
+
+    fn reset(&mut self) -> Result<(), ScsiError> {
+        self.next(KIND_RESET, |r| match r.u8()? {
+            0 => Ok(Ok(())),
+            _ => Ok(Err(r.error()?)),
+        })
+        .unwrap_or_else(|e| Err(e.into()))
+    }
+
+    fn max_xfer(&mut self) -> Result<usize, ScsiError> {
+        self.next(KIND_MAX_XFER, |r| match r.u8()? {
+            0 => Ok(Ok(r.u64()? as usize)),
+            _ => Ok(Err(r.error()?)),
+        })
+        .unwrap_or_else(|e| Err(e.into()))
+    }
+}
+
+/// A replayed call that diverged from the capture, or a malformed capture.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ReplayError {
+    /// The index of the record being served.
+    pub record: u64,
+    pub reason: String,
+}
+
+impl fmt::Display for ReplayError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "replay diverged at record {}: {}",
+            self.record, self.reason
+        )
+    }
+}
+
+impl std::error::Error for ReplayError {}
+
+impl From<ReplayError> for ScsiError {
+    fn from(e: ReplayError) -> Self {
+        ScsiError::Os(io::Error::new(ErrorKind::InvalidData, e))
+    }
+}
+
+struct Reader<'a> {
+    buf: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, n: usize) -> Result<&'a [u8], String> {
+        match self.buf.get(self.pos..self.pos.saturating_add(n)) {
+            Some(b) => {
+                self.pos += n;
+                Ok(b)
+            }
+            None if self.pos >= self.buf.len() => Err("the capture has no more records".into()),
+            None => Err("truncated capture record".into()),
+        }
+    }
+
+    fn u8(&mut self) -> Result<u8, String> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn u16(&mut self) -> Result<u16, String> {
+        let b = self.take(2)?;
+        Ok(u16::from_le_bytes([b[0], b[1]]))
+    }
+
+    fn u32(&mut self) -> Result<u32, String> {
+        let b = self.take(4)?;
+        Ok(u32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+    }
+
+    fn u64(&mut self) -> Result<u64, String> {
+        let mut v = [0u8; 8];
+        v.copy_from_slice(self.take(8)?);
+        Ok(u64::from_le_bytes(v))
+    }
+
+    fn bytes(&mut self) -> Result<&'a [u8], String> {
+        let len = self.u32()? as usize;
+        self.take(len)
+    }
+
+    fn string(&mut self) -> Result<String, String> {
+        Ok(String::from_utf8_lossy(self.bytes()?).into_owned())
+    }
+
+    /// Decode an error written by put_error().
+    fn error(&mut self) -> Result<ScsiError, String> {
+        let e = match self.u8()? {
+            0 => {
+                let kind = ERROR_KINDS
+                    .get(self.u8()? as usize)
+                    .copied()
+                    .unwrap_or(ErrorKind::Other);
+                ScsiError::Os(io::Error::new(kind, self.string()?))
+            }
+            1 => ScsiError::Transport {
+                host_status: self.u16()?,
+                driver_status: self.u16()?,
+            },
+            2 => ScsiError::Timeout,
+            3 => ScsiError::Status {
+                status: ScsiStatus::from(self.u8()?),
+                sense: Sense::parse(self.bytes()?),
+            },
+            4 => ScsiError::ShortTransfer {
+                expected: self.u64()? as usize,
+                actual: self.u64()? as usize,
+            },
+            // The message is kept in the capture for reference, but
+            // these variants only carry static strings.
+            5 => {
+                self.string()?;
+                ScsiError::InvalidCommand("recorded as an invalid command")
+            }
+            6 => {
+                self.string()?;
+                ScsiError::Unsupported("recorded command")
+            }
+            tag => return Err(format!("unknown error type {tag} in capture")),
+        };
+        Ok(e)
+    }
+}
+
+fn put_u64(rec: &mut Vec<u8>, v: u64) {
+    rec.extend_from_slice(&v.to_le_bytes());
+}
+
+fn put_bytes(rec: &mut Vec<u8>, b: &[u8]) {
+    let len = u32::try_from(b.len()).unwrap_or(u32::MAX);
+    rec.extend_from_slice(&len.to_le_bytes());
+    rec.extend_from_slice(&b[..len as usize]);
+}
+
+fn put_error(rec: &mut Vec<u8>, e: &ScsiError) {
+    match e {
+        ScsiError::Os(os) => {
+            rec.push(0);
+            let code = ERROR_KINDS.iter().position(|k| *k == os.kind());
+            rec.push(code.unwrap_or(0) as u8);
+            put_bytes(rec, os.to_string().as_bytes());
+        }
+        ScsiError::Transport {
+            host_status,
+            driver_status,
+        } => {
+            rec.push(1);
+            rec.extend_from_slice(&host_status.to_le_bytes());
+            rec.extend_from_slice(&driver_status.to_le_bytes());
+        }
+        ScsiError::Timeout => rec.push(2),
+        ScsiError::Status { status, sense } => {
+            rec.push(3);
+            rec.push((*status).into());
+            put_bytes(rec, sense.as_ref().map_or(&[], |s| s.as_bytes()));
+        }
+        ScsiError::ShortTransfer { expected, actual } => {
+            rec.push(4);
+            put_u64(rec, *expected as u64);
+            put_u64(rec, *actual as u64);
+        }
+        ScsiError::InvalidCommand(msg) => {
+            rec.push(5);
+            put_bytes(rec, msg.as_bytes());
+        }
+        ScsiError::Unsupported(msg) => {
+            rec.push(6);
+            put_bytes(rec, msg.as_bytes());
+        }
+    }
+}
+
+fn direction_code(dir: DataDirection) -> u8 {
+    match dir {
+        DataDirection::None => 0,
+        DataDirection::In => 1,
+        DataDirection::Out => 2,
+        DataDirection::Bidirectional => 3,
+    }
+}
+
+fn kind_name(kind: u8) -> &'static str {
+    match kind {
+        KIND_COMMAND => "command",
+        KIND_RESET => "reset",
+        KIND_MAX_XFER => "max transfer size",
+        _ => "unknown",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cdb::{Cdb, Read as ReadCdb, Write as WriteCdb};
+    use crate::{Emulator, Fault, FaultInjector, FaultRule};
+    use std::io::IoSliceMut;
+
+    const BS: u32 = 512;
+
+    /// What a call returned, and the data-in and sense it transferred.
+    type Outcome = (Result<CommandResult, String>, Vec<u8>, Vec<u8>);
+
+    fn pattern(len: usize) -> Vec<u8> {
+        (0..len).map(|i| (i as u8).wrapping_mul(13)).collect()
+    }
+
+    fn command<T: Transport>(t: &mut T, cdb: &[u8], data_out: &[u8], in_len: usize) -> Outcome {
+        let mut data_in = vec![0u8; in_len];
+        let mut sense = [0u8; 32];
+        let mut cmd = Command::new(cdb).sense(&mut sense);
+        cmd = match (data_out.is_empty(), in_len) {
+            (true, 0) => cmd,
+            (true, _) => cmd.data_in(&mut data_in),
+            (false, _) => cmd.data_out(data_out),
+        };
+        let result = t.submit(&mut cmd).map_err(|e| e.to_string());
+        (result, data_in, sense.to_vec())
+    }
+
+    /// The calls of a short session: a write, reads that succeed, fail and
+    /// time out, a vectored read, a reset and a transfer size query.
+    fn session<T: Transport>(t: &mut T) -> Vec<Outcome> {
+        let data = pattern(4 * BS as usize);
+        let mut out = vec![
+            command(t, &WriteCdb::new(8, 4, BS).to_bytes(), &data, 0),
+            command(t, &ReadCdb::new(8, 4, BS).to_bytes(), &[], data.len()),
+            command(t, &ReadCdb::new(1 << 20, 1, BS).to_bytes(), &[], 512),
+            command(t, &ReadCdb::new(0, 1, BS).to_bytes(), &[], 512),
+        ];
+
+        let (mut a, mut b) = ([0u8; 100], [0u8; 412]);
+        let mut iov = [IoSliceMut::new(&mut a), IoSliceMut::new(&mut b)];
+        let cdb = ReadCdb::new(9, 1, BS).to_bytes();
+        let result = t.submit(&mut Command::new(&cdb).data_in_vectored(&mut iov));
+        out.push((
+            result.map_err(|e| e.to_string()),
+            [&a[..], &b[..]].concat(),
+            Vec::new(),
+        ));
+
+        out.push((
+            t.reset()
+                .map(|()| CommandResult::default())
+                .map_err(|e| e.to_string()),
+            Vec::new(),
+            Vec::new(),
+        ));
+        let max = t.max_xfer().map_err(|e| e.to_string());
+        out.push((
+            Ok(CommandResult::default()),
+            max.map(|n| n.to_le_bytes().to_vec()).unwrap_or_default(),
+            Vec::new(),
+        ));
+        out
+    }
+
+    /// Record a session against an emulator that times out the fourth
+    /// command, returning what it saw and the capture.
+    fn record() -> (Vec<Outcome>, Vec<u8>) {
+        let emu = FaultInjector::new(Emulator::new(BS, 1024))
+            .with_rule(FaultRule::new(Fault::Timeout).nth(4));
+        let mut rec = Recorder::new(emu, Vec::new()).unwrap();
+        let outcomes = session(&mut rec);
+        let (_, capture) = rec.finish().unwrap();
+        (outcomes, capture)
+    }
+
+    fn replay_error(e: ScsiError) -> ReplayError {
+        match e {
+            ScsiError::Os(e) => {
+                assert_eq!(e.kind(), ErrorKind::InvalidData);
+                *e.into_inner().unwrap().downcast::<ReplayError>().unwrap()
+            }
+            e => panic!("expected a replay error, got {e:?}"),
+        }
+    }
+
+    #[test]
+    fn round_trip() {
+        let (recorded, capture) = record();
+        assert_eq!(recorded[1].1, pattern(4 * BS as usize));
+        assert!(matches!(recorded[2].0, Ok(r) if r.status == ScsiStatus::CheckCondition));
+        assert_eq!(recorded[2].2[12], 0x21);
+        assert!(recorded[3].0.is_err());
+        assert!(recorded[5].0.is_err());
+
+        let mut replay = Replay::from_bytes(capture.clone()).unwrap();
+        assert_eq!(session(&mut replay), recorded);
+        assert_eq!(replay.position(), 7);
+        assert!(replay.is_finished());
+
+        let replay = Replay::new(&capture[..]).unwrap();
+        assert_eq!(replay.position(), 0);
+        assert!(!replay.is_finished());
+    }
+
+    #[test]
+    fn wrong_cdb() {
+        let (_, capture) = record();
+        let mut replay = Replay::from_bytes(capture).unwrap();
+        let data = pattern(4 * BS as usize);
+
+        let (result, _, _) = command(&mut replay, &WriteCdb::new(9, 4, BS).to_bytes(), &data, 0);
+        assert!(result.is_err());
+        assert_eq!(replay.position(), 0);
+
+        // The failure is latched, so even the recorded command now fails.
+        let cdb = WriteCdb::new(8, 4, BS).to_bytes();
+        let e = replay
+            .submit(&mut Command::new(&cdb).data_out(&data))
+            .unwrap_err();
+        let e = replay_error(e);
+        assert_eq!(e.record, 0);
+        assert!(e.reason.starts_with("CDB"), "{}", e.reason);
+    }
+
+    #[test]
+    fn wrong_data_out() {
+        let (_, capture) = record();
+        let mut replay = Replay::from_bytes(capture).unwrap();
+
+        let mut data = pattern(4 * BS as usize);
+        data[700] ^= 1;
+        let cdb = WriteCdb::new(8, 4, BS).to_bytes();
+        let e = replay
+            .submit(&mut Command::new(&cdb).data_out(&data))
+            .unwrap_err();
+        assert_eq!(
+            replay_error(e),
+            ReplayError {
+                record: 0,
+                reason: "data-out differs from the recording".into(),
+            }
+        );
+    }
+
+    #[test]
+    fn wrong_kind() {
+        let (_, capture) = record();
+        let mut replay = Replay::from_bytes(capture).unwrap();
+        let e = replay_error(replay.reset().unwrap_err());
+        assert_eq!(e.reason, "expected a reset record, found a command");
+    }
+
+    #[test]
+    fn extra_command() {
+        let (_, capture) = record();
+        let mut replay = Replay::from_bytes(capture).unwrap();
+        session(&mut replay);
+        assert!(replay.is_finished());
+
+        let cdb = ReadCdb::new(0, 1, BS).to_bytes();
+        let mut buf = [0u8; 512];
+        let e = replay
+            .submit(&mut Command::new(&cdb).data_in(&mut buf))
+            .unwrap_err();
+        assert_eq!(replay_error(e).record, 7);
+    }
+
+    #[test]
+    fn bad_header() {
+        let (_, capture) = record();
+
+        let mut bad_magic = capture.clone();
+        bad_magic[0] = b'X';
+        let mut bad_version = capture.clone();
+        bad_version[8..10].copy_from_slice(&(FORMAT_VERSION + 1).to_le_bytes());
+
+        for buf in [bad_magic, bad_version, capture[..9].to_vec(), Vec::new()] {
+            let e = Replay::new(&buf[..]).unwrap_err();
+            assert_eq!(e.kind(), ErrorKind::InvalidData);
+            let e = Replay::from_bytes(buf).unwrap_err();
+            assert_eq!(e.kind(), ErrorKind::InvalidData);
+        }
+    }
+}
@@ -0,0 +1,448 @@
+/*
+ * Copyright 2025 Jason King
+ */
+
+use crate::{ScsiError, ScsiStatus, SenseKey};
+use std::collections::hash_map::RandomState;
+use std::hash::{BuildHasher, Hasher};
+use std::time::{Duration, Instant};
+
+/// A transient condition a [`RetryPolicy`] may retry.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum RetryCondition {
+    /// CHECK CONDITION with UNIT ATTENTION.
+    UnitAttention,
+    /// NOT READY, logical unit is in the process of becoming ready (04/01).
+    BecomingReady,
+    Busy,
+    TaskSetFull,
+    /// CHECK CONDITION with ABORTED COMMAND.
+    AbortedCommand,
+    /// A transport failure, or the submission was interrupted.
+    Transport,
+    Timeout,
+}
+
+impl RetryCondition {
+    const ALL: [RetryCondition; 7] = [
+        RetryCondition::UnitAttention,
+        RetryCondition::BecomingReady,
+        RetryCondition::Busy,
+        RetryCondition::TaskSetFull,
+        RetryCondition::AbortedCommand,
+        RetryCondition::Transport,
+        RetryCondition::Timeout,
+    ];
+
+    /// The transient condition `err` represents, if any.
+    pub fn classify(err: &ScsiError) -> Option<Self> {
+        match err {
+            ScsiError::Os(e) if e.kind() == std::io::ErrorKind::Interrupted => {
+                Some(RetryCondition::Transport)
+            }
+            ScsiError::Transport { .. } => Some(RetryCondition::Transport),
+            ScsiError::Timeout => Some(RetryCondition::Timeout),
+            ScsiError::Status { status, sense } => match status {
+                ScsiStatus::Busy => Some(RetryCondition::Busy),
+                ScsiStatus::TaskSetFull => Some(RetryCondition::TaskSetFull),
+                ScsiStatus::CheckCondition => {
+                    let sense = sense.as_ref()?;
+                    match sense.key() {
+                        SenseKey::UnitAttention => Some(RetryCondition::UnitAttention),
+                        SenseKey::AbortedCommand => Some(RetryCondition::AbortedCommand),
+                        SenseKey::NotReady if sense.asc() == 0x04 && sense.ascq() == 0x01 => {
+                            Some(RetryCondition::BecomingReady)
+                        }
+                        _ => None,
+                    }
+                }
+                _ => None,
+            },
+            _ => None,
+        }
+    }
+
+    fn index(self) -> usize {
+        self as usize
+    }
+}
+
+/// How a [`RetryPolicy`] handles one [`RetryCondition`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct RetryRule {
+    /// The number of times a command failing with this condition is
+    /// reissued. Zero disables retries for the condition.
+    pub max_retries: u32,
+    /// Wait (with exponential backoff) before reissuing.
+    pub backoff: bool,
+}
+
+impl RetryRule {
+    pub const NEVER: RetryRule = RetryRule {
+        max_retries: 0,
+        backoff: false,
+    };
+
+    pub fn immediate(max_retries: u32) -> Self {
+        Self {
+            max_retries,
+            backoff: false,
+        }
+    }
+
+    pub fn backoff(max_retries: u32) -> Self {
+        Self {
+            max_retries,
+            backoff: true,
+        }
+    }
+}
+
+/// When and how a [`Device`](crate::Device) reissues commands that fail
+/// with a transient condition.
+///
+/// Commands that are not idempotent (see [`is_idempotent`]) are never
+/// retried unless [`allow_non_idempotent`](Self::allow_non_idempotent) is
+/// set.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RetryPolicy {
+    /// The maximum number of times a command is issued, including the
+    /// first attempt.
+    pub max_attempts: u32,
+    rules: [RetryRule; RetryCondition::ALL.len()],
+    /// The delay before the first retry that uses backoff.
+    pub initial_backoff: Duration,
+    /// The longest delay between attempts.
+    pub max_backoff: Duration,
+    /// Randomize each delay to between half and all of its nominal value.
+    pub jitter: bool,
+    /// Stop retrying once this much time has passed since the first
+    /// attempt.
+    pub deadline: Option<Duration>,
+    pub allow_non_idempotent: bool,
+}
+
+impl Default for RetryPolicy {
+    /// Retry UNIT ATTENTION and ABORTED COMMAND immediately, and BUSY,
+    /// TASK SET FULL, becoming ready and transport failures with backoff.
+    /// Timeouts are not retried.
+    fn default() -> Self {
+        let mut policy = Self::none();
+        policy.max_attempts = 10;
+        policy
+            .rule(RetryCondition::UnitAttention, RetryRule::immediate(3))
+            .rule(RetryCondition::AbortedCommand, RetryRule::immediate(3))
+            .rule(RetryCondition::BecomingReady, RetryRule::backoff(8))
+            .rule(RetryCondition::Busy, RetryRule::backoff(5))
+            .rule(RetryCondition::TaskSetFull, RetryRule::backoff(5))
+            .rule(RetryCondition::Transport, RetryRule::backoff(2))
+    }
+}
+
+impl RetryPolicy {
+    /// A policy that never retries.
+    pub fn none() -> Self {
+        Self {
+            max_attempts: 1,
+            rules: [RetryRule::NEVER; RetryCondition::ALL.len()],
+            initial_backoff: Duration::from_millis(50),
+            max_backoff: Duration::from_secs(2),
+            jitter: true,
+            deadline: None,
+            allow_non_idempotent: false,
+        }
+    }
+
+    /// Set the rule for `cond`.
+    pub fn rule(mut self, cond: RetryCondition, rule: RetryRule) -> Self {
+        self.rules[cond.index()] = rule;
+        self
+    }
+
+    pub fn get_rule(&self, cond: RetryCondition) -> RetryRule {
+        self.rules[cond.index()]
+    }
+
+    /// Decide whether to reissue a command that failed with `err`,
+    /// returning the delay before the next attempt or `None` to give up.
+    pub(crate) fn next(
+        &self,
+        state: &mut RetryState,
+        cdb: &[u8],
+        err: &ScsiError,
+    ) -> Option<Duration> {
+        if state.attempts >= self.max_attempts
+            || (!self.allow_non_idempotent && !is_idempotent(cdb))
+        {
+            return None;
+        }
+
+        let cond = RetryCondition::classify(err)?;
+        let rule = self.get_rule(cond);
+        let count = &mut state.counts[cond.index()];
+        if *count >= rule.max_retries {
+            return None;
+        }
+        *count += 1;
+
+        let delay = match rule.backoff {
+            true => self.backoff(*count - 1),
+            false => Duration::ZERO,
+        };
+        match self.deadline {
+            Some(deadline) if state.start.elapsed() + delay >= deadline => None,
+            _ => Some(delay),
+        }
+    }
+
+    fn backoff(&self, n: u32) -> Duration {
+        let delay = self
+            .initial_backoff
+            .saturating_mul(1u32.checked_shl(n).unwrap_or(u32::MAX))
+            .min(self.max_backoff);
+        if !self.jitter {
+            return delay;
+        }
+
+        let r = RandomState::new().build_hasher().finish();
+        let frac = (r >> 11) as f64 / (1u64 << 53) as f64;
+        delay.mul_f64(0.5 + frac / 2.0)
+    }
+}
+
+/// The progress of a single command through its retries.
+pub(crate) struct RetryState {
+    /// The number of times the command has been issued.
+    pub(crate) attempts: u32,
+    counts: [u32; RetryCondition::ALL.len()],
+    start: Instant,
+}
+
+impl RetryState {
+    pub(crate) fn new() -> Self {
+        Self {
+            attempts: 0,
+            counts: [0; RetryCondition::ALL.len()],
+            start: Instant::now(),
+        }
+    }
+
+    pub(crate) fn retries(&self) -> u32 {
+        self.attempts.saturating_sub(1)
+    }
+}
+
+/// True if issuing `cdb` twice has the same effect as issuing it once, so
+/// that it is safe to retry after a failure that may have occurred after
+/// the device started executing it.
+///
+/// Commands that change device state in ways that depend on the current
+/// state (COMPARE AND WRITE, XPWRITE, XDWRITEREAD, PERSISTENT RESERVE OUT,
+/// FORMAT UNIT, SANITIZE, WRITE BUFFER, third-party copy and MAINTENANCE
+/// OUT), REQUEST SENSE (which clears the sense data it returns), ATA
+/// PASS-THROUGH (which may carry any ATA command, e.g. SECURITY ERASE or
+/// DOWNLOAD MICROCODE) and vendor specific commands are treated as not
+/// idempotent. For variable length CDBs the service action decides: only
+/// XPWRITE(32) and XDWRITEREAD(32) are excluded.
+pub fn is_idempotent(cdb: &[u8]) -> bool {
+    match cdb {
+        [] => false,
+        [0x7f, ..] => !matches!(cdb.get(8..10), Some([0x00, 0x06 | 0x07])),
+        [op, ..] => !matches!(
+            op,
+            0x03 | 0x04 | 0x3b | 0x48 | 0x51 | 0x53 | 0x5f | 0x83 | 0x85 | 0x89 | 0xa1 | 0xa4 | 0xc0
+                ..=0xff
+        ),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Sense;
+
+    const READ_10: [u8; 10] = [0x28, 0, 0, 0, 0, 0, 0, 0, 1, 0];
+    const COMPARE_AND_WRITE: [u8; 16] = [0x89, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 1, 0, 0];
+
+    fn check_condition(key: u8, asc: u8, ascq: u8) -> ScsiError {
+        let mut buf = [0u8; 18];
+        buf[0] = 0x70;
+        buf[2] = key;
+        buf[7] = 10;
+        buf[12] = asc;
+        buf[13] = ascq;
+        ScsiError::Status {
+            status: ScsiStatus::CheckCondition,
+            sense: Sense::parse(&buf),
+        }
+    }
+
+    fn busy() -> ScsiError {
+        ScsiError::Status {
+            status: ScsiStatus::Busy,
+            sense: None,
+        }
+    }
+
+    /// Run `policy` against a command that always fails with `err`, as
+    /// Device does, returning the delays before each retry.
+    fn retries(policy: &RetryPolicy, cdb: &[u8], err: &ScsiError) -> Vec<Duration> {
+        let mut state = RetryState::new();
+        let mut delays = Vec::new();
+        loop {
+            state.attempts += 1;
+            match policy.next(&mut state, cdb, err) {
+                Some(delay) => delays.push(delay),
+                None => return delays,
+            }
+        }
+    }
+
+    #[test]
+    fn classify() {
+        use RetryCondition::*;
+
+        assert_eq!(
+            RetryCondition::classify(&check_condition(0x06, 0x29, 0)),
+            Some(UnitAttention)
+        );
+        assert_eq!(
+            RetryCondition::classify(&check_condition(0x0b, 0, 0)),
+            Some(AbortedCommand)
+        );
+        assert_eq!(
+            RetryCondition::classify(&check_condition(0x02, 0x04, 0x01)),
+            Some(BecomingReady)
+        );
+        assert_eq!(
+            RetryCondition::classify(&check_condition(0x02, 0x3a, 0)),
+            None
+        );
+        assert_eq!(
+            RetryCondition::classify(&check_condition(0x03, 0x11, 0)),
+            None
+        );
+        assert_eq!(RetryCondition::classify(&busy()), Some(Busy));
+        assert_eq!(RetryCondition::classify(&ScsiError::Timeout), Some(Timeout));
+        assert_eq!(
+            RetryCondition::classify(&ScsiError::InvalidCommand("bad")),
+            None
+        );
+    }
+
+    #[test]
+    fn per_condition_cap() {
+        let policy = RetryPolicy::default();
+        let ua = check_condition(0x06, 0x29, 0);
+        assert_eq!(retries(&policy, &READ_10, &ua), vec![Duration::ZERO; 3]);
+
+        // Timeouts are not retried by default.
+        assert!(retries(&policy, &READ_10, &ScsiError::Timeout).is_empty());
+
+        // Each condition is counted separately.
+        let mut state = RetryState::new();
+        for err in [&ua, &ua, &ua] {
+            state.attempts += 1;
+            assert!(policy.next(&mut state, &READ_10, err).is_some());
+        }
+        state.attempts += 1;
+        assert_eq!(policy.next(&mut state, &READ_10, &ua), None);
+        state.attempts += 1;
+        let aborted = check_condition(0x0b, 0, 0);
+        assert_eq!(
+            policy.next(&mut state, &READ_10, &aborted),
+            Some(Duration::ZERO)
+        );
+    }
+
+    #[test]
+    fn max_attempts() {
+        let mut policy = RetryPolicy::none().rule(RetryCondition::Busy, RetryRule::immediate(100));
+        policy.max_attempts = 4;
+        assert_eq!(retries(&policy, &READ_10, &busy()).len(), 3);
+
+        // RetryPolicy::none never retries.
+        assert!(retries(&RetryPolicy::none(), &READ_10, &busy()).is_empty());
+    }
+
+    #[test]
+    fn backoff() {
+        let mut policy = RetryPolicy::none().rule(RetryCondition::Busy, RetryRule::backoff(6));
+        policy.max_attempts = 10;
+        policy.jitter = false;
+        policy.initial_backoff = Duration::from_millis(10);
+        policy.max_backoff = Duration::from_millis(100);
+
+        let ms = |n| Duration::from_millis(n);
+        assert_eq!(
+            retries(&policy, &READ_10, &busy()),
+            vec![ms(10), ms(20), ms(40), ms(80), ms(100), ms(100)]
+        );
+
+        policy.jitter = true;
+        for (delay, nominal) in retries(&policy, &READ_10, &busy())
+            .into_iter()
+            .zip([10, 20, 40, 80, 100, 100])
+        {
+            assert!(delay >= ms(nominal) / 2 && delay <= ms(nominal));
+        }
+    }
+
+    #[test]
+    fn deadline() {
+        let mut policy = RetryPolicy::none().rule(RetryCondition::Busy, RetryRule::backoff(5));
+        policy.max_attempts = 10;
+        policy.jitter = false;
+        policy.initial_backoff = Duration::from_millis(50);
+
+        // The first delay would pass the deadline.
+        policy.deadline = Some(Duration::from_millis(10));
+        assert!(retries(&policy, &READ_10, &busy()).is_empty());
+
+        // Immediate retries stop once the deadline has passed.
+        policy.deadline = Some(Duration::ZERO);
+        let policy = policy.rule(RetryCondition::Busy, RetryRule::immediate(5));
+        assert!(retries(&policy, &READ_10, &busy()).is_empty());
+    }
+
+    #[test]
+    fn non_idempotent() {
+        let mut policy = RetryPolicy::default();
+        let ua = check_condition(0x06, 0x29, 0);
+        assert!(retries(&policy, &COMPARE_AND_WRITE, &ua).is_empty());
+
+        policy.allow_non_idempotent = true;
+        assert_eq!(retries(&policy, &COMPARE_AND_WRITE, &ua).len(), 3);
+    }
+
+    #[test]
+    fn idempotent_opcodes() {
+        for op in [
+            0x00, 0x08, 0x12, 0x25, 0x28, 0x2a, 0x35, 0x88, 0x8a, 0x9e, 0xa0,
+        ] {
+            assert!(is_idempotent(&[op]), "{op:#04x}");
+        }
+        for op in [
+            0x03, 0x04, 0x3b, 0x48, 0x51, 0x53, 0x5f, 0x83, 0x85, 0x89, 0xa1, 0xa4, 0xc0, 0xff,
+        ] {
+            assert!(!is_idempotent(&[op]), "{op:#04x}");
+        }
+        assert!(!is_idempotent(&[]));
+
+        // Variable length CDBs go by their service action.
+        let cdb32 = |sa: u16| {
+            let mut cdb = [0u8; 32];
+            cdb[0] = 0x7f;
+            cdb[7] = 0x18;
+            cdb[8..10].copy_from_slice(&sa.to_be_bytes());
+            cdb
+        };
+        for sa in [0x0009, 0x000a, 0x000b, 0x000c, 0x000d] {
+            assert!(is_idempotent(&cdb32(sa)), "{sa:#06x}");
+        }
+        for sa in [0x0006, 0x0007] {
+            assert!(!is_idempotent(&cdb32(sa)), "{sa:#06x}");
+        }
+        assert!(is_idempotent(&[0x7f, 0, 0, 0, 0, 0, 0, 0]));
+    }
+}
@@ -0,0 +1,874 @@
+/*
+ * Copyright 2025 Jason King
+ */
+
+use crate::{asc_ascq_text, CommandResult, ScsiStatus};
+use std::borrow::Cow;
+use std::fmt;
+
+/// The sense key (SPC-5 4.4.8).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum SenseKey {
+    NoSense,
+    RecoveredError,
+    NotReady,
+    MediumError,
+    HardwareError,
+    IllegalRequest,
+    UnitAttention,
+    DataProtect,
+    BlankCheck,
+    VendorSpecific,
+    CopyAborted,
+    AbortedCommand,
+    Reserved,
+    VolumeOverflow,
+    Miscompare,
+    Completed,
+}
+
+impl From<u8> for SenseKey {
+    fn from(val: u8) -> Self {
+        match val & 0x0f {
+            0x0 => SenseKey::NoSense,
+            0x1 => SenseKey::RecoveredError,
+            0x2 => SenseKey::NotReady,
+            0x3 => SenseKey::MediumError,
+            0x4 => SenseKey::HardwareError,
+            0x5 => SenseKey::IllegalRequest,
+            0x6 => SenseKey::UnitAttention,
+            0x7 => SenseKey::DataProtect,
+            0x8 => SenseKey::BlankCheck,
+            0x9 => SenseKey::VendorSpecific,
+            0xa => SenseKey::CopyAborted,
+            0xb => SenseKey::AbortedCommand,
+            0xc => SenseKey::Reserved,
+            0xd => SenseKey::VolumeOverflow,
+            0xe => SenseKey::Miscompare,
+            _ => SenseKey::Completed,
+        }
+    }
+}
+
+impl From<SenseKey> for u8 {
+    fn from(val: SenseKey) -> Self {
+        val as u8
+    }
+}
+
+impl fmt::Display for SenseKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            SenseKey::NoSense => "NO SENSE",
+            SenseKey::RecoveredError => "RECOVERED ERROR",
+            SenseKey::NotReady => "NOT READY",
+            SenseKey::MediumError => "MEDIUM ERROR",
+            SenseKey::HardwareError => "HARDWARE ERROR",
+            SenseKey::IllegalRequest => "ILLEGAL REQUEST",
+            SenseKey::UnitAttention => "UNIT ATTENTION",
+            SenseKey::DataProtect => "DATA PROTECT",
+            SenseKey::BlankCheck => "BLANK CHECK",
+            SenseKey::VendorSpecific => "VENDOR SPECIFIC",
+            SenseKey::CopyAborted => "COPY ABORTED",
+            SenseKey::AbortedCommand => "ABORTED COMMAND",
+            SenseKey::Reserved => "RESERVED",
+            SenseKey::VolumeOverflow => "VOLUME OVERFLOW",
+            SenseKey::Miscompare => "MISCOMPARE",
+            SenseKey::Completed => "COMPLETED",
+        })
+    }
+}
+
+/// Whether sense data is in fixed or descriptor format.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SenseFormat {
+    Fixed,
+    Descriptor,
+}
+
+/// The decoded SENSE KEY SPECIFIC field, whose meaning depends on the
+/// sense key (SPC-5 4.4.2.4).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SenseKeySpecific {
+    /// ILLEGAL REQUEST: the byte (and optionally bit) in error, in either
+    /// the CDB (`cdb` is true) or the parameter data.
+    FieldPointer {
+        cdb: bool,
+        bit: Option<u8>,
+        field: u16,
+    },
+    /// RECOVERED ERROR, HARDWARE ERROR or MEDIUM ERROR.
+    ActualRetryCount(u16),
+    /// NO SENSE or NOT READY: progress of an operation, out of 65536.
+    Progress(u16),
+    /// COPY ABORTED: the byte (and optionally bit) in error within the
+    /// segment descriptor (`sd` is true) or parameter list.
+    SegmentPointer {
+        sd: bool,
+        bit: Option<u8>,
+        field: u16,
+    },
+    /// UNIT ATTENTION: whether the unit attention condition queue overflowed.
+    UnitAttentionOverflow(bool),
+    /// Any other sense key.
+    Other([u8; 3]),
+}
+
+impl SenseKeySpecific {
+    /// Decode the 3-byte field, returning `None` if SKSV is not set.
+    fn decode(key: SenseKey, b: &[u8]) -> Option<Self> {
+        let b: [u8; 3] = b.get(..3)?.try_into().ok()?;
+        if b[0] & 0x80 == 0 {
+            return None;
+        }
+
+        let val = u16::from_be_bytes([b[1], b[2]]);
+        let bit = match b[0] & 0x08 {
+            0 => None,
+            _ => Some(b[0] & 0x07),
+        };
+
+        Some(match key {
+            SenseKey::IllegalRequest => SenseKeySpecific::FieldPointer {
+                cdb: b[0] & 0x40 != 0,
+                bit,
+                field: val,
+            },
+            SenseKey::RecoveredError | SenseKey::HardwareError | SenseKey::MediumError => {
+                SenseKeySpecific::ActualRetryCount(val)
+            }
+            SenseKey::NoSense | SenseKey::NotReady => SenseKeySpecific::Progress(val),
+            SenseKey::CopyAborted => SenseKeySpecific::SegmentPointer {
+                sd: b[0] & 0x20 != 0,
+                bit,
+                field: val,
+            },
+            SenseKey::UnitAttention => SenseKeySpecific::UnitAttentionOverflow(b[0] & 0x01 != 0),
+            _ => SenseKeySpecific::Other(b),
+        })
+    }
+}
+
+/// The registers returned in an ATA Status Return descriptor (SAT-5 12.2.2.7).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AtaStatusReturn {
+    pub extend: bool,
+    pub error: u8,
+    pub count: u16,
+    pub lba: u64,
+    pub device: u8,
+    pub status: u8,
+}
+
+/// One segment of a User Data Segment Referral descriptor (SBC-4 4.29).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UserDataSegment {
+    pub first_lba: u64,
+    pub last_lba: u64,
+    /// (asymmetric access state, target port group) pairs.
+    pub target_port_groups: Vec<(u8, u16)>,
+}
+
+/// A single sense data descriptor (SPC-5 4.4.2).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Descriptor<'a> {
+    Information {
+        valid: bool,
+        information: u64,
+    },
+    CommandSpecific(u64),
+    SenseKeySpecific(Option<SenseKeySpecific>),
+    Fru(u8),
+    StreamCommands {
+        filemark: bool,
+        eom: bool,
+        ili: bool,
+    },
+    BlockCommands {
+        ili: bool,
+    },
+    AtaStatusReturn(AtaStatusReturn),
+    /// Progress of an operation other than the one reported by the sense
+    /// data the descriptor is in.
+    Progress {
+        key: SenseKey,
+        asc: u8,
+        ascq: u8,
+        progress: u16,
+    },
+    UserDataSegmentReferral {
+        not_all_r: bool,
+        segments: Vec<UserDataSegment>,
+    },
+    ForwardedSense {
+        fsdt: bool,
+        source: u8,
+        status: ScsiStatus,
+        sense: &'a [u8],
+    },
+    /// A descriptor type not decoded above (including vendor specific
+    /// types 0x80-0xff); `data` excludes the two byte header.
+    Other {
+        kind: u8,
+        data: &'a [u8],
+    },
+}
+
+impl<'a> Descriptor<'a> {
+    fn decode(key: SenseKey, kind: u8, d: &'a [u8]) -> Self {
+        let be64 = |b: &[u8]| {
+            b.get(..8)
+                .map(|b| u64::from_be_bytes(b.try_into().unwrap()))
+        };
+        let byte = |i: usize| d.get(i).copied().unwrap_or(0);
+        let other = Descriptor::Other { kind, data: d };
+
+        // `d` excludes the type and additional length bytes, so offsets
+        // below are two less than those in the standard.
+        match kind {
+            0x00 => match be64(d.get(2..).unwrap_or(&[])) {
+                Some(information) => Descriptor::Information {
+                    valid: byte(0) & 0x80 != 0,
+                    information,
+                },
+                None => other,
+            },
+            0x01 => match be64(d.get(2..).unwrap_or(&[])) {
+                Some(info) => Descriptor::CommandSpecific(info),
+                None => other,
+            },
+            0x02 => Descriptor::SenseKeySpecific(SenseKeySpecific::decode(
+                key,
+                d.get(2..).unwrap_or(&[]),
+            )),
+            0x03 => Descriptor::Fru(byte(1)),
+            0x04 => Descriptor::StreamCommands {
+                filemark: byte(1) & 0x80 != 0,
+                eom: byte(1) & 0x40 != 0,
+                ili: byte(1) & 0x20 != 0,
+            },
+            0x05 => Descriptor::BlockCommands {
+                ili: byte(1) & 0x20 != 0,
+            },
+            0x09 if d.len() >= 12 => {
+                let lba = [byte(8), byte(6), byte(4), byte(9), byte(7), byte(5)]
+                    .iter()
+                    .fold(0u64, |acc, b| acc << 8 | u64::from(*b));
+                Descriptor::AtaStatusReturn(AtaStatusReturn {
+                    extend: byte(0) & 0x01 != 0,
+                    error: byte(1),
+                    count: u16::from_be_bytes([byte(2), byte(3)]),
+                    lba,
+                    device: byte(10),
+                    status: byte(11),
+                })
+            }
+            0x0a if d.len() >= 6 => Descriptor::Progress {
+                key: SenseKey::from(byte(0)),
+                asc: byte(1),
+                ascq: byte(2),
+                progress: u16::from_be_bytes([byte(4), byte(5)]),
+            },
+            0x0b if !d.is_empty() => {
+                let mut segments = Vec::new();
+                let mut rest = d.get(2..).unwrap_or(&[]);
+
+                while rest.len() >= 20 {
+                    let ntpg = rest[3] as usize;
+                    let end = (20 + ntpg * 4).min(rest.len());
+                    let target_port_groups = rest[20..end]
+                        .chunks_exact(4)
+                        .map(|g| (g[0] & 0x0f, u16::from_be_bytes([g[2], g[3]])))
+                        .collect();
+
+                    segments.push(UserDataSegment {
+                        first_lba: be64(&rest[4..12]).unwrap(),
+                        last_lba: be64(&rest[12..20]).unwrap(),
+                        target_port_groups,
+                    });
+                    rest = &rest[end..];
+                }
+
+                Descriptor::UserDataSegmentReferral {
+                    not_all_r: byte(0) & 0x01 != 0,
+                    segments,
+                }
+            }
+            0x0c if d.len() >= 2 => Descriptor::ForwardedSense {
+                fsdt: byte(0) & 0x80 != 0,
+                source: byte(0) & 0x0f,
+                status: ScsiStatus::from(byte(1)),
+                sense: &d[2..],
+            },
+            _ => other,
+        }
+    }
+}
+
+/// An iterator over the descriptors in descriptor format sense data.
+#[derive(Debug, Clone)]
+pub struct Descriptors<'a> {
+    key: SenseKey,
+    buf: &'a [u8],
+}
+
+impl<'a> Iterator for Descriptors<'a> {
+    type Item = Descriptor<'a>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.buf.len() < 2 {
+            return None;
+        }
+
+        let kind = self.buf[0];
+        let end = (2 + self.buf[1] as usize).min(self.buf.len());
+        let data = &self.buf[2..end];
+        self.buf = &self.buf[end..];
+
+        Some(Descriptor::decode(self.key, kind, data))
+    }
+}
+
+/// Parsed sense data, in either fixed or descriptor format.
+///
+/// Only the bytes that were actually returned are retained, so accessors
+/// for fields beyond the end of short sense data return zero or `None`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Sense {
+    buf: Vec<u8>,
+}
+
+impl Sense {
+    /// Parse sense data, returning `None` if it does not start with a
+    /// fixed (0x70/0x71) or descriptor (0x72/0x73) response code.
+    pub fn parse(buf: &[u8]) -> Option<Self> {
+        match buf.first()? & 0x7f {
+            0x70..=0x73 => {}
+            _ => return None,
+        }
+
+        let len = match buf.get(7) {
+            Some(n) => (8 + *n as usize).min(buf.len()),
+            None => buf.len(),
+        };
+
+        Some(Self {
+            buf: buf[..len].to_vec(),
+        })
+    }
+
+    /// Parse the sense data returned in `buf` for a command, honoring the
+    /// sense residual so that only valid bytes are considered.
+    pub fn from_result(buf: &[u8], result: &CommandResult) -> Option<Self> {
+        Self::parse(&buf[..result.sense_len(buf.len())])
+    }
+
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.buf
+    }
+
+    fn byte(&self, i: usize) -> u8 {
+        self.buf.get(i).copied().unwrap_or(0)
+    }
+
+    pub fn response_code(&self) -> u8 {
+        self.byte(0) & 0x7f
+    }
+
+    pub fn format(&self) -> SenseFormat {
+        match self.response_code() {
+            0x70 | 0x71 => SenseFormat::Fixed,
+            _ => SenseFormat::Descriptor,
+        }
+    }
+
+    /// True if the sense data is for a deferred error rather than the
+    /// command it was returned with.
+    pub fn is_deferred(&self) -> bool {
+        matches!(self.response_code(), 0x71 | 0x73)
+    }
+
+    pub fn key(&self) -> SenseKey {
+        match self.format() {
+            SenseFormat::Fixed => SenseKey::from(self.byte(2)),
+            SenseFormat::Descriptor => SenseKey::from(self.byte(1)),
+        }
+    }
+
+    pub fn asc(&self) -> u8 {
+        match self.format() {
+            SenseFormat::Fixed => self.byte(12),
+            SenseFormat::Descriptor => self.byte(2),
+        }
+    }
+
+    pub fn ascq(&self) -> u8 {
+        match self.format() {
+            SenseFormat::Fixed => self.byte(13),
+            SenseFormat::Descriptor => self.byte(3),
+        }
+    }
+
+    /// The text for the ASC/ASCQ pair.
+    pub fn description(&self) -> Cow<'static, str> {
+        asc_ascq_text(self.asc(), self.ascq())
+            .unwrap_or(Cow::Borrowed("Unknown additional sense code"))
+    }
+
+    /// The VALID bit, indicating the INFORMATION field is meaningful.
+    pub fn valid(&self) -> bool {
+        match self.format() {
+            SenseFormat::Fixed => self.byte(0) & 0x80 != 0,
+            SenseFormat::Descriptor => self
+                .descriptors()
+                .any(|d| matches!(d, Descriptor::Information { valid: true, .. })),
+        }
+    }
+
+    /// The INFORMATION field (typically the LBA of an error), if VALID.
+    pub fn information(&self) -> Option<u64> {
+        match self.format() {
+            SenseFormat::Fixed if self.valid() && self.buf.len() >= 7 => Some(u64::from(
+                u32::from_be_bytes(self.buf[3..7].try_into().unwrap()),
+            )),
+            SenseFormat::Fixed => None,
+            SenseFormat::Descriptor => self.descriptors().find_map(|d| match d {
+                Descriptor::Information {
+                    valid: true,
+                    information,
+                } => Some(information),
+                _ => None,
+            }),
+        }
+    }
+
+    pub fn command_specific(&self) -> Option<u64> {
+        match self.format() {
+            SenseFormat::Fixed => self
+                .buf
+                .get(8..12)
+                .map(|b| u64::from(u32::from_be_bytes(b.try_into().unwrap()))),
+            SenseFormat::Descriptor => self.descriptors().find_map(|d| match d {
+                Descriptor::CommandSpecific(info) => Some(info),
+                _ => None,
+            }),
+        }
+    }
+
+    /// The FIELD REPLACEABLE UNIT CODE, if non-zero.
+    pub fn fru(&self) -> Option<u8> {
+        let fru = match self.format() {
+            SenseFormat::Fixed => self.byte(14),
+            SenseFormat::Descriptor => self
+                .descriptors()
+                .find_map(|d| match d {
+                    Descriptor::Fru(fru) => Some(fru),
+                    _ => None,
+                })
+                .unwrap_or(0),
+        };
+
+        (fru != 0).then_some(fru)
+    }
+
+    pub fn sense_key_specific(&self) -> Option<SenseKeySpecific> {
+        match self.format() {
+            SenseFormat::Fixed => {
+                SenseKeySpecific::decode(self.key(), self.buf.get(15..).unwrap_or(&[]))
+            }
+            SenseFormat::Descriptor => self.descriptors().find_map(|d| match d {
+                Descriptor::SenseKeySpecific(sks) => sks,
+                _ => None,
+            }),
+        }
+    }
+
+    /// The progress indication, as a fraction between 0 and 1.
+    pub fn progress(&self) -> Option<f64> {
+        match self.sense_key_specific()? {
+            SenseKeySpecific::Progress(p) => Some(f64::from(p) / 65536.0),
+            _ => None,
+        }
+    }
+
+    pub fn filemark(&self) -> bool {
+        match self.format() {
+            SenseFormat::Fixed => self.byte(2) & 0x80 != 0,
+            SenseFormat::Descriptor => self
+                .descriptors()
+                .any(|d| matches!(d, Descriptor::StreamCommands { filemark: true, .. })),
+        }
+    }
+
+    pub fn eom(&self) -> bool {
+        match self.format() {
+            SenseFormat::Fixed => self.byte(2) & 0x40 != 0,
+            SenseFormat::Descriptor => self
+                .descriptors()
+                .any(|d| matches!(d, Descriptor::StreamCommands { eom: true, .. })),
+        }
+    }
+
+    /// The INCORRECT LENGTH INDICATOR.
+    pub fn ili(&self) -> bool {
+        match self.format() {
+            SenseFormat::Fixed => self.byte(2) & 0x20 != 0,
+            SenseFormat::Descriptor => self.descriptors().any(|d| {
+                matches!(
+                    d,
+                    Descriptor::StreamCommands { ili: true, .. }
+                        | Descriptor::BlockCommands { ili: true }
+                )
+            }),
+        }
+    }
+
+    /// The descriptors in descriptor format sense data. Fixed format sense
+    /// data has no descriptors.
+    pub fn descriptors(&self) -> Descriptors<'_> {
+        let buf = match self.format() {
+            SenseFormat::Fixed => &[],
+            SenseFormat::Descriptor => self.buf.get(8..).unwrap_or(&[]),
+        };
+
+        Descriptors {
+            key: self.key(),
+            buf,
+        }
+    }
+}
+
+/// Renders e.g. `MEDIUM ERROR: Unrecovered read error (11/00) at LBA 0x1234`.
+impl fmt::Display for Sense {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.is_deferred() {
+            f.write_str("deferred ")?;
+        }
+
+        write!(
+            f,
+            "{}: {} ({:02x}/{:02x})",
+            self.key(),
+            self.description(),
+            self.asc(),
+            self.ascq()
+        )?;
+
+        match (self.key(), self.information()) {
+            (
+                SenseKey::RecoveredError | SenseKey::MediumError | SenseKey::HardwareError,
+                Some(lba),
+            ) => write!(f, " at LBA {lba:#x}")?,
+            (_, Some(info)) => write!(f, ", information {info:#x}")?,
+            (_, None) => {}
+        }
+
+        match self.sense_key_specific() {
+            Some(SenseKeySpecific::Progress(p)) => {
+                write!(f, ", {:.1}% complete", f64::from(p) * 100.0 / 65536.0)
+            }
+            Some(SenseKeySpecific::FieldPointer { cdb, bit, field }) => {
+                let what = if cdb { "CDB" } else { "parameter data" };
+                write!(f, ", invalid field in {what} byte {field}")?;
+                match bit {
+                    Some(bit) => write!(f, " bit {bit}"),
+                    None => Ok(()),
+                }
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Fixed format MEDIUM ERROR sense with every field set.
+    const FIXED: [u8; 18] = [
+        0xf0, 0x00, 0x23, 0x00, 0x00, 0x12, 0x34, 0x0a, 0xde, 0xad, 0xbe, 0xef, 0x11, 0x00, 0x05,
+        0x80, 0x00, 0x03,
+    ];
+
+    fn fixed(key: u8, asc: u8, ascq: u8, sks: [u8; 3]) -> [u8; 18] {
+        let mut buf = [0u8; 18];
+        buf[0] = 0x70;
+        buf[2] = key;
+        buf[7] = 10;
+        buf[12] = asc;
+        buf[13] = ascq;
+        buf[15..18].copy_from_slice(&sks);
+        buf
+    }
+
+    /// Descriptor format sense data for MEDIUM ERROR holding `descs`.
+    fn descriptor(descs: &[&[u8]]) -> Vec<u8> {
+        let mut buf = vec![0x72, 0x03, 0x11, 0x00, 0, 0, 0, 0];
+        for d in descs {
+            buf.extend_from_slice(d);
+        }
+        buf[7] = (buf.len() - 8) as u8;
+        buf
+    }
+
+    #[test]
+    fn fixed_fields() {
+        let sense = Sense::parse(&FIXED).unwrap();
+        assert_eq!(sense.format(), SenseFormat::Fixed);
+        assert!(!sense.is_deferred());
+        assert_eq!(sense.key(), SenseKey::MediumError);
+        assert_eq!((sense.asc(), sense.ascq()), (0x11, 0x00));
+        assert!(sense.valid());
+        assert_eq!(sense.information(), Some(0x1234));
+        assert_eq!(sense.command_specific(), Some(0xdead_beef));
+        assert_eq!(sense.fru(), Some(0x05));
+        assert_eq!(
+            sense.sense_key_specific(),
+            Some(SenseKeySpecific::ActualRetryCount(3))
+        );
+        assert!(sense.ili());
+        assert!(!sense.filemark() && !sense.eom());
+        assert_eq!(sense.descriptors().count(), 0);
+        assert_eq!(
+            sense.to_string(),
+            "MEDIUM ERROR: Unrecovered read error (11/00) at LBA 0x1234"
+        );
+
+        // Without VALID the information field is ignored.
+        let mut buf = FIXED;
+        buf[0] = 0x70;
+        let sense = Sense::parse(&buf).unwrap();
+        assert!(!sense.valid());
+        assert_eq!(sense.information(), None);
+
+        buf[0] = 0x71;
+        let sense = Sense::parse(&buf).unwrap();
+        assert!(sense.is_deferred());
+        assert!(sense.to_string().starts_with("deferred MEDIUM ERROR"));
+    }
+
+    #[test]
+    fn sense_key_specific() {
+        // Format in progress, half done.
+        let sense = Sense::parse(&fixed(0x02, 0x04, 0x04, [0x80, 0x80, 0x00])).unwrap();
+        assert_eq!(
+            sense.sense_key_specific(),
+            Some(SenseKeySpecific::Progress(0x8000))
+        );
+        assert_eq!(sense.progress(), Some(0.5));
+        assert!(sense.to_string().ends_with(", 50.0% complete"));
+
+        // SKSV clear.
+        let sense = Sense::parse(&fixed(0x02, 0x04, 0x04, [0x00, 0x80, 0x00])).unwrap();
+        assert_eq!(sense.sense_key_specific(), None);
+        assert_eq!(sense.progress(), None);
+
+        let sense = Sense::parse(&fixed(0x05, 0x24, 0x00, [0xcb, 0x00, 0x02])).unwrap();
+        assert_eq!(
+            sense.sense_key_specific(),
+            Some(SenseKeySpecific::FieldPointer {
+                cdb: true,
+                bit: Some(3),
+                field: 2
+            })
+        );
+        assert!(sense
+            .to_string()
+            .ends_with("invalid field in CDB byte 2 bit 3"));
+        assert_eq!(sense.progress(), None);
+
+        let sense = Sense::parse(&fixed(0x0a, 0x26, 0x00, [0xa0, 0x01, 0x00])).unwrap();
+        assert_eq!(
+            sense.sense_key_specific(),
+            Some(SenseKeySpecific::SegmentPointer {
+                sd: true,
+                bit: None,
+                field: 0x100
+            })
+        );
+
+        let sense = Sense::parse(&fixed(0x06, 0x29, 0x00, [0x81, 0x00, 0x00])).unwrap();
+        assert_eq!(
+            sense.sense_key_specific(),
+            Some(SenseKeySpecific::UnitAttentionOverflow(true))
+        );
+    }
+
+    #[test]
+    fn truncated() {
+        assert_eq!(Sense::parse(&[]), None);
+        assert_eq!(Sense::parse(&[0x00, 0x00, 0x05]), None);
+
+        // Shorter than the additional length byte.
+        let sense = Sense::parse(&FIXED[..3]).unwrap();
+        assert_eq!(sense.as_bytes(), &FIXED[..3]);
+        assert_eq!(sense.key(), SenseKey::MediumError);
+        assert_eq!((sense.asc(), sense.ascq()), (0, 0));
+        assert_eq!(sense.information(), None);
+        assert_eq!(sense.command_specific(), None);
+        assert_eq!(sense.sense_key_specific(), None);
+        assert_eq!(sense.fru(), None);
+
+        // Shorter than the additional length claims.
+        let sense = Sense::parse(&FIXED[..14]).unwrap();
+        assert_eq!(sense.as_bytes().len(), 14);
+        assert_eq!((sense.asc(), sense.ascq()), (0x11, 0x00));
+        assert_eq!(sense.information(), Some(0x1234));
+        assert_eq!(sense.fru(), None);
+        assert_eq!(sense.sense_key_specific(), None);
+
+        // Bytes beyond the additional length are dropped.
+        let mut buf = FIXED.to_vec();
+        buf.extend_from_slice(&[0xff; 14]);
+        assert_eq!(Sense::parse(&buf).unwrap().as_bytes(), &FIXED);
+
+        // Only the bytes the sense residual says were returned are used.
+        let mut buf = [0u8; 32];
+        buf[..18].copy_from_slice(&FIXED);
+        let result = CommandResult {
+            status: ScsiStatus::CheckCondition,
+            rqresid: 32 - 8,
+            ..CommandResult::default()
+        };
+        let sense = Sense::from_result(&buf, &result).unwrap();
+        assert_eq!(sense.as_bytes(), &FIXED[..8]);
+        assert_eq!(sense.asc(), 0);
+    }
+
+    #[test]
+    fn descriptor_fields() {
+        let buf = descriptor(&[
+            &[0x00, 0x0a, 0x80, 0x00, 0, 0, 0, 1, 0, 0, 0, 0],
+            &[0x01, 0x0a, 0x00, 0x00, 0, 0, 0, 0, 0, 0, 0, 0x42],
+            &[0x02, 0x06, 0x00, 0x00, 0x80, 0x00, 0x05, 0x00],
+            &[0x03, 0x02, 0x00, 0x07],
+            &[0x05, 0x02, 0x00, 0x20],
+        ]);
+        let sense = Sense::parse(&buf).unwrap();
+        assert_eq!(sense.format(), SenseFormat::Descriptor);
+        assert_eq!(sense.key(), SenseKey::MediumError);
+        assert_eq!((sense.asc(), sense.ascq()), (0x11, 0x00));
+        assert!(sense.valid());
+        assert_eq!(sense.information(), Some(0x1_0000_0000));
+        assert_eq!(sense.command_specific(), Some(0x42));
+        assert_eq!(
+            sense.sense_key_specific(),
+            Some(SenseKeySpecific::ActualRetryCount(5))
+        );
+        assert_eq!(sense.fru(), Some(7));
+        assert!(sense.ili());
+        assert!(!sense.filemark());
+        assert_eq!(
+            sense.to_string(),
+            "MEDIUM ERROR: Unrecovered read error (11/00) at LBA 0x100000000"
+        );
+    }
+
+    #[test]
+    fn descriptor_iteration() {
+        let uds: Vec<u8> = [0x0b, 26, 0x01, 0x00, 0, 0, 0, 1]
+            .into_iter()
+            .chain(0x1000u64.to_be_bytes())
+            .chain(0x1fffu64.to_be_bytes())
+            .chain([0x02, 0x00, 0x00, 0x07])
+            .collect();
+        let buf = descriptor(&[
+            &[
+                0x09, 0x0c, 0x01, 0x04, 0x00, 0x01, 0x11, 0x22, 0x33, 0x44, 0x55, 0x66, 0xa0, 0x51,
+            ],
+            &[0x0a, 0x06, 0x02, 0x04, 0x04, 0x00, 0x40, 0x00],
+            &uds,
+            &[0x0c, 0x04, 0x81, 0x02, 0x70, 0x00],
+            &[0x04, 0x02, 0x00, 0xc0],
+            &[0x80, 0x02, 0xaa, 0xbb],
+        ]);
+        let sense = Sense::parse(&buf).unwrap();
+        let descs: Vec<_> = sense.descriptors().collect();
+        assert_eq!(
+            descs,
+            [
+                Descriptor::AtaStatusReturn(AtaStatusReturn {
+                    extend: true,
+                    error: 0x04,
+                    count: 0x0001,
+                    lba: 0x5533_1166_4422,
+                    device: 0xa0,
+                    status: 0x51,
+                }),
+                Descriptor::Progress {
+                    key: SenseKey::NotReady,
+                    asc: 0x04,
+                    ascq: 0x04,
+                    progress: 0x4000,
+                },
+                Descriptor::UserDataSegmentReferral {
+                    not_all_r: true,
+                    segments: vec![UserDataSegment {
+                        first_lba: 0x1000,
+                        last_lba: 0x1fff,
+                        target_port_groups: vec![(0x02, 0x0007)],
+                    }],
+                },
+                Descriptor::ForwardedSense {
+                    fsdt: true,
+                    source: 1,
+                    status: ScsiStatus::CheckCondition,
+                    sense: &[0x70, 0x00],
+                },
+                Descriptor::StreamCommands {
+                    filemark: true,
+                    eom: true,
+                    ili: false,
+                },
+                Descriptor::Other {
+                    kind: 0x80,
+                    data: &[0xaa, 0xbb],
+                },
+            ]
+        );
+        assert!(sense.filemark() && sense.eom());
+        assert!(!sense.valid());
+        assert_eq!(sense.information(), None);
+    }
+
+    #[test]
+    fn descriptor_bogus_length() {
+        // An information descriptor claiming more bytes than remain.
+        let buf = descriptor(&[&[0x03, 0x02, 0x00, 0x07], &[0x00, 0xff, 0x80, 0x00, 1, 2]]);
+        let sense = Sense::parse(&buf).unwrap();
+        let descs: Vec<_> = sense.descriptors().collect();
+        assert_eq!(
+            descs,
+            [
+                Descriptor::Fru(7),
+                Descriptor::Other {
+                    kind: 0x00,
+                    data: &[0x80, 0x00, 1, 2],
+                },
+            ]
+        );
+        assert_eq!(sense.information(), None);
+
+        // A lone trailing byte ends the iteration.
+        let buf = descriptor(&[&[0x03, 0x02, 0x00, 0x07], &[0x05]]);
+        let sense = Sense::parse(&buf).unwrap();
+        assert_eq!(
+            sense.descriptors().collect::<Vec<_>>(),
+            [Descriptor::Fru(7)]
+        );
+
+        // An additional length beyond the buffer.
+        let mut buf = descriptor(&[&[0x03, 0x02, 0x00, 0x07]]);
+        buf[7] = 0xf4;
+        let sense = Sense::parse(&buf).unwrap();
+        assert_eq!(
+            sense.descriptors().collect::<Vec<_>>(),
+            [Descriptor::Fru(7)]
+        );
+
+        // Sense data with no room for descriptors.
+        let sense = Sense::parse(&buf[..6]).unwrap();
+        assert_eq!(sense.descriptors().count(), 0);
+        assert_eq!(sense.key(), SenseKey::MediumError);
+    }
+}
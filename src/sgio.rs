@@ -0,0 +1,568 @@
+/*
+ * Copyright 2025 Jason King
+ */
+
+use crate::{Command, CommandResult, DataBuffer, ScsiError, ScsiStatus, Transport};
+use libc::{c_int, c_uchar, c_uint, c_ushort, c_void, ioctl};
+use std::fs::OpenOptions;
+use std::os::fd::{AsFd, AsRawFd, BorrowedFd, OwnedFd, RawFd};
+use std::os::unix::fs::OpenOptionsExt;
+use std::path::Path;
+
+pub const SG_IO: c_int = 0x2285;
+const SG_SCSI_RESET: c_int = 0x2284;
+const BLKSECTGET: c_int = 0x1267;
+
+pub const SG_DXFER_NONE: c_int = -1;
+pub const SG_DXFER_TO_DEV: c_int = -2;
+pub const SG_DXFER_FROM_DEV: c_int = -3;
+
+const SG_SCSI_RESET_TARGET: c_int = 4;
+const SCSI_GENERIC_MAJOR: u32 = 21;
+
+const DID_OK: c_ushort = 0x00;
+const DID_TIME_OUT: c_ushort = 0x03;
+
+const DRIVER_OK: c_ushort = 0x00;
+const DRIVER_TIMEOUT: c_ushort = 0x06;
+const DRIVER_SENSE: c_ushort = 0x08;
+
+/// The Linux `sg_io_hdr_t` from `<scsi/sg.h>`.
+#[repr(C)]
+#[derive(Debug)]
+pub struct SgIoHdr {
+    pub interface_id: c_int,
+    pub dxfer_direction: c_int,
+    pub cmd_len: c_uchar,
+    pub mx_sb_len: c_uchar,
+    pub iovec_count: c_ushort,
+    pub dxfer_len: c_uint,
+    pub dxferp: *mut c_void,
+    pub cmdp: *const c_uchar,
+    pub sbp: *mut c_uchar,
+    pub timeout: c_uint,
+    pub flags: c_uint,
+    pub pack_id: c_int,
+    pub usr_ptr: *mut c_void,
+    pub status: c_uchar,
+    pub masked_status: c_uchar,
+    pub msg_status: c_uchar,
+    pub sb_len_wr: c_uchar,
+    pub host_status: c_ushort,
+    pub driver_status: c_ushort,
+    pub resid: c_int,
+    pub duration: c_uint,
+    pub info: c_uint,
+}
+
+impl Default for SgIoHdr {
+    fn default() -> Self {
+        Self {
+            interface_id: b'S' as c_int,
+            dxfer_direction: SG_DXFER_NONE,
+            cmd_len: 0,
+            mx_sb_len: 0,
+            iovec_count: 0,
+            dxfer_len: 0,
+            dxferp: std::ptr::null_mut(),
+            cmdp: std::ptr::null(),
+            sbp: std::ptr::null_mut(),
+            timeout: 0,
+            flags: 0,
+            pack_id: 0,
+            usr_ptr: std::ptr::null_mut(),
+            status: 0,
+            masked_status: 0,
+            msg_status: 0,
+            sb_len_wr: 0,
+            host_status: 0,
+            driver_status: 0,
+            resid: 0,
+            duration: 0,
+            info: 0,
+        }
+    }
+}
+
+/// The ioctls used by [`SgIo`].
+///
+/// This is implemented for [`OwnedFd`] by issuing the real ioctls; other
+/// implementations can stand in for the kernel so the translation done by
+/// [`SgIo`] can be exercised without a device.
+pub trait SgIoctl {
+    /// Issue `SG_IO`. All pointers in `hdr` are valid for the duration
+    /// of the call.
+    fn sg_io(&mut self, hdr: &mut SgIoHdr) -> Result<(), std::io::Error>;
+
+    /// Reset the target.
+    fn reset(&mut self) -> Result<(), std::io::Error> {
+        Err(std::io::ErrorKind::Unsupported.into())
+    }
+
+    /// The maximum transfer size (in bytes) of a single `SG_IO` request.
+    fn max_xfer(&mut self) -> Result<usize, std::io::Error> {
+        Err(std::io::ErrorKind::Unsupported.into())
+    }
+}
+
+impl SgIoctl for OwnedFd {
+    fn sg_io(&mut self, hdr: &mut SgIoHdr) -> Result<(), std::io::Error> {
+        // SAFETY: The descriptor is open, and the caller guarantees the
+        // buffers referenced by `hdr` outlive the call.
+        match unsafe { ioctl(self.as_raw_fd(), SG_IO as _, hdr as *mut SgIoHdr) } {
+            0 => Ok(()),
+            _ => Err(std::io::Error::last_os_error()),
+        }
+    }
+
+    fn reset(&mut self) -> Result<(), std::io::Error> {
+        let mut op = SG_SCSI_RESET_TARGET;
+
+        // SAFETY: The descriptor is open and `op` outlives the call.
+        match unsafe { ioctl(self.as_raw_fd(), SG_SCSI_RESET as _, &mut op as *mut c_int) } {
+            0 => Ok(()),
+            _ => Err(std::io::Error::last_os_error()),
+        }
+    }
+
+    fn max_xfer(&mut self) -> Result<usize, std::io::Error> {
+        // BLKSECTGET reports bytes (as an int) on sg(4) nodes, but sectors
+        // (as an unsigned short) on block devices.
+        let sg = std::fs::File::from(self.try_clone()?)
+            .metadata()
+            .map(|md| {
+                use std::os::unix::fs::{FileTypeExt, MetadataExt};
+                md.file_type().is_char_device()
+                    && libc::major(md.rdev() as libc::dev_t) == SCSI_GENERIC_MAJOR
+            })?;
+
+        if sg {
+            let mut val: c_int = 0;
+            // SAFETY: The descriptor is open and `val` outlives the call.
+            match unsafe { ioctl(self.as_raw_fd(), BLKSECTGET as _, &mut val as *mut c_int) } {
+                0 => Ok(val as usize),
+                _ => Err(std::io::Error::last_os_error()),
+            }
+        } else {
+            let mut val: c_ushort = 0;
+            // SAFETY: The descriptor is open and `val` outlives the call.
+            match unsafe { ioctl(self.as_raw_fd(), BLKSECTGET as _, &mut val as *mut c_ushort) } {
+                0 => Ok(val as usize * 512),
+                _ => Err(std::io::Error::last_os_error()),
+            }
+        }
+    }
+}
+
+/// The Linux `SG_IO` ioctl transport.
+///
+/// Works with both sg(4) nodes (`/dev/sg*`) and SCSI block devices
+/// (`/dev/sd*`).
+#[derive(Debug)]
+pub struct SgIo<I: SgIoctl = OwnedFd> {
+    ioctl: I,
+}
+
+impl SgIo<OwnedFd> {
+    /// Open a SCSI device node.
+    ///
+    /// The device is opened read/write with `O_NONBLOCK` so that devices
+    /// with no media present can still be opened and sent commands.
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self, std::io::Error> {
+        let file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .custom_flags(libc::O_NONBLOCK)
+            .open(path)?;
+
+        Ok(Self::new(file.into()))
+    }
+}
+
+impl<I: SgIoctl> SgIo<I> {
+    pub fn new(ioctl: I) -> Self {
+        Self { ioctl }
+    }
+
+    pub fn into_inner(self) -> I {
+        self.ioctl
+    }
+}
+
+impl<I: SgIoctl> Transport for SgIo<I> {
+    fn submit(&mut self, cmd: &mut Command<'_>) -> Result<CommandResult, ScsiError> {
+        crate::cdb::validate(cmd.cdb)?;
+        let cmd_len = c_uchar::try_from(cmd.cdb.len())
+            .map_err(|_| ScsiError::InvalidCommand("CDB too long"))?;
+
+        // An sg_iovec has the same layout as IoSlice and IoSliceMut.
+        let mut iovec_count = 0;
+        let len = cmd.data.len();
+        let (dxfer_direction, dxferp) = match &mut cmd.data {
+            DataBuffer::None => (SG_DXFER_NONE, std::ptr::null_mut()),
+            DataBuffer::In(data) => (SG_DXFER_FROM_DEV, data.as_mut_ptr() as *mut c_void),
+            DataBuffer::Out(data) => (SG_DXFER_TO_DEV, data.as_ptr() as *mut c_void),
+            DataBuffer::InVectored(iov) => {
+                iovec_count = iov.len();
+                (SG_DXFER_FROM_DEV, iov.as_ptr() as *mut c_void)
+            }
+            DataBuffer::OutVectored(iov) => {
+                iovec_count = iov.len();
+                (SG_DXFER_TO_DEV, iov.as_ptr() as *mut c_void)
+            }
+            // SG_DXFER_TO_FROM_DEV is an indirect data-in transfer, not a
+            // true bidirectional one; sg has no way to issue those.
+            DataBuffer::Bidirectional { .. } => {
+                return Err(ScsiError::Unsupported("bidirectional transfers"))
+            }
+        };
+        let dxfer_len =
+            c_uint::try_from(len).map_err(|_| ScsiError::InvalidCommand("data buffer too long"))?;
+        let iovec_count = c_ushort::try_from(iovec_count)
+            .map_err(|_| ScsiError::InvalidCommand("too many data segments"))?;
+
+        let (sbp, mx_sb_len) = match cmd.sense.as_deref_mut() {
+            Some(sense) => (sense.as_mut_ptr(), sense.len().min(c_uchar::MAX as usize)),
+            None => (std::ptr::null_mut(), 0),
+        };
+
+        let mut hdr = SgIoHdr {
+            dxfer_direction,
+            cmd_len,
+            mx_sb_len: mx_sb_len as c_uchar,
+            iovec_count,
+            dxfer_len,
+            dxferp,
+            cmdp: cmd.cdb.as_ptr(),
+            sbp,
+            timeout: u32::from(cmd.timeout.as_secs()) * 1000,
+            ..Default::default()
+        };
+
+        self.ioctl.sg_io(&mut hdr)?;
+
+        if hdr.host_status == DID_TIME_OUT || hdr.driver_status & 0x0f == DRIVER_TIMEOUT {
+            return Err(ScsiError::Timeout);
+        }
+
+        if hdr.host_status != DID_OK {
+            return Err(ScsiError::Transport {
+                host_status: hdr.host_status,
+                driver_status: hdr.driver_status,
+            });
+        }
+
+        let status = match hdr.status {
+            0 => hdr.masked_status << 1,
+            s => s,
+        };
+
+        let driver = hdr.driver_status & 0x0f;
+        if status == 0 && driver != DRIVER_OK && driver != DRIVER_SENSE {
+            return Err(ScsiError::Transport {
+                host_status: hdr.host_status,
+                driver_status: hdr.driver_status,
+            });
+        }
+
+        Ok(CommandResult {
+            status: ScsiStatus::from(status),
+            resid: hdr.resid.max(0) as usize,
+            rqresid: cmd
+                .sense
+                .as_ref()
+                .map_or(0, |s| s.len() - usize::from(hdr.sb_len_wr)),
+            rqstatus: ScsiStatus::Good,
+            retries: 0,
+        })
+    }
+
+    fn reset(&mut self) -> Result<(), ScsiError> {
+        Ok(self.ioctl.reset()?)
+    }
+
+    fn max_xfer(&mut self) -> Result<usize, ScsiError> {
+        Ok(self.ioctl.max_xfer()?)
+    }
+}
+
+impl From<OwnedFd> for SgIo<OwnedFd> {
+    fn from(fd: OwnedFd) -> Self {
+        Self::new(fd)
+    }
+}
+
+impl<I: SgIoctl + AsFd> AsFd for SgIo<I> {
+    fn as_fd(&self) -> BorrowedFd<'_> {
+        self.ioctl.as_fd()
+    }
+}
+
+impl<I: SgIoctl + AsRawFd> AsRawFd for SgIo<I> {
+    fn as_raw_fd(&self) -> RawFd {
+        self.ioctl.as_raw_fd()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Sense, SenseKey, Timeout};
+    use std::io::{IoSlice, IoSliceMut};
+
+    const READ_10: [u8; 10] = [0x28, 0, 0, 0, 0, 0, 0, 0, 1, 0];
+    const WRITE_10: [u8; 10] = [0x2a, 0, 0, 0, 0, 0, 0, 0, 1, 0];
+    const TEST_UNIT_READY: [u8; 6] = [0; 6];
+
+    /// Stands in for the kernel, completing every request with the
+    /// configured status fields and recording what it was sent.
+    #[derive(Debug, Default)]
+    struct Mock {
+        status: c_uchar,
+        masked_status: c_uchar,
+        host_status: c_ushort,
+        driver_status: c_ushort,
+        resid: c_int,
+        sense: Vec<u8>,
+        errno: Option<i32>,
+        calls: usize,
+        sent: Option<Sent>,
+    }
+
+    #[derive(Debug, PartialEq, Eq)]
+    struct Sent {
+        dxfer_direction: c_int,
+        dxfer_len: c_uint,
+        iovec_count: c_ushort,
+        cmd_len: c_uchar,
+        mx_sb_len: c_uchar,
+        timeout: c_uint,
+    }
+
+    impl SgIoctl for Mock {
+        fn sg_io(&mut self, hdr: &mut SgIoHdr) -> Result<(), std::io::Error> {
+            self.calls += 1;
+            if let Some(errno) = self.errno {
+                return Err(std::io::Error::from_raw_os_error(errno));
+            }
+            self.sent = Some(Sent {
+                dxfer_direction: hdr.dxfer_direction,
+                dxfer_len: hdr.dxfer_len,
+                iovec_count: hdr.iovec_count,
+                cmd_len: hdr.cmd_len,
+                mx_sb_len: hdr.mx_sb_len,
+                timeout: hdr.timeout,
+            });
+
+            let n = self.sense.len().min(hdr.mx_sb_len as usize);
+            if n > 0 {
+                // SAFETY: sbp is valid for mx_sb_len bytes.
+                unsafe { std::ptr::copy_nonoverlapping(self.sense.as_ptr(), hdr.sbp, n) };
+            }
+            hdr.sb_len_wr = n as c_uchar;
+            hdr.status = self.status;
+            hdr.masked_status = self.masked_status;
+            hdr.host_status = self.host_status;
+            hdr.driver_status = self.driver_status;
+            hdr.resid = self.resid;
+            Ok(())
+        }
+    }
+
+    fn medium_error() -> Vec<u8> {
+        let mut sense = vec![0u8; 18];
+        sense[0] = 0x70;
+        sense[2] = 0x03;
+        sense[7] = 10;
+        sense[12] = 0x11;
+        sense
+    }
+
+    fn read(sg: &mut SgIo<Mock>) -> (Result<CommandResult, ScsiError>, [u8; 32]) {
+        let mut data = [0u8; 512];
+        let mut sense = [0u8; 32];
+        let result = sg.submit(
+            &mut Command::new(&READ_10)
+                .data_in(&mut data)
+                .sense(&mut sense)
+                .timeout(Timeout::from_secs(30).unwrap()),
+        );
+        (result, sense)
+    }
+
+    #[test]
+    fn good() {
+        let mut sg = SgIo::new(Mock {
+            resid: 12,
+            ..Mock::default()
+        });
+        let result = read(&mut sg).0.unwrap();
+        assert_eq!(result.status, ScsiStatus::Good);
+        assert_eq!(result.resid, 12);
+        assert_eq!(result.rqresid, 32);
+
+        assert_eq!(
+            sg.into_inner().sent.unwrap(),
+            Sent {
+                dxfer_direction: SG_DXFER_FROM_DEV,
+                dxfer_len: 512,
+                iovec_count: 0,
+                cmd_len: 10,
+                mx_sb_len: 32,
+                timeout: 30_000,
+            }
+        );
+    }
+
+    #[test]
+    fn directions() {
+        let mut sg = SgIo::new(Mock::default());
+        sg.submit(&mut Command::new(&TEST_UNIT_READY)).unwrap();
+        let sent = sg.ioctl.sent.take().unwrap();
+        assert_eq!(sent.dxfer_direction, SG_DXFER_NONE);
+        assert_eq!(sent.dxfer_len, 0);
+        assert_eq!(sent.mx_sb_len, 0);
+
+        let data = [0u8; 512];
+        sg.submit(&mut Command::new(&WRITE_10).data_out(&data))
+            .unwrap();
+        let sent = sg.ioctl.sent.take().unwrap();
+        assert_eq!(sent.dxfer_direction, SG_DXFER_TO_DEV);
+        assert_eq!(sent.dxfer_len, 512);
+
+        let (mut a, mut b) = ([0u8; 100], [0u8; 412]);
+        let mut iov = [IoSliceMut::new(&mut a), IoSliceMut::new(&mut b)];
+        sg.submit(&mut Command::new(&READ_10).data_in_vectored(&mut iov))
+            .unwrap();
+        let sent = sg.ioctl.sent.take().unwrap();
+        assert_eq!(sent.dxfer_direction, SG_DXFER_FROM_DEV);
+        assert_eq!(sent.dxfer_len, 512);
+        assert_eq!(sent.iovec_count, 2);
+
+        let iov = [IoSlice::new(&data[..256]), IoSlice::new(&data[256..])];
+        sg.submit(&mut Command::new(&WRITE_10).data_out_vectored(&iov))
+            .unwrap();
+        let sent = sg.ioctl.sent.take().unwrap();
+        assert_eq!(sent.dxfer_direction, SG_DXFER_TO_DEV);
+        assert_eq!(sent.iovec_count, 2);
+    }
+
+    #[test]
+    fn check_condition() {
+        let mut sg = SgIo::new(Mock {
+            status: 0x02,
+            driver_status: DRIVER_SENSE,
+            resid: 512,
+            sense: medium_error(),
+            ..Mock::default()
+        });
+        let (result, sense) = read(&mut sg);
+        let result = result.unwrap();
+        assert_eq!(result.status, ScsiStatus::CheckCondition);
+        assert_eq!(result.resid, 512);
+        assert_eq!(result.rqresid, 32 - 18);
+
+        let sense = Sense::from_result(&sense, &result).unwrap();
+        assert_eq!(sense.key(), SenseKey::MediumError);
+        assert_eq!(sense.asc(), 0x11);
+    }
+
+    #[test]
+    fn masked_status() {
+        // Old drivers only fill in the status shifted right by one.
+        let mut sg = SgIo::new(Mock {
+            masked_status: 0x08 >> 1,
+            ..Mock::default()
+        });
+        assert_eq!(read(&mut sg).0.unwrap().status, ScsiStatus::Busy);
+
+        // The full status takes precedence.
+        let mut sg = SgIo::new(Mock {
+            status: 0x18,
+            masked_status: 0x08 >> 1,
+            ..Mock::default()
+        });
+        assert_eq!(
+            read(&mut sg).0.unwrap().status,
+            ScsiStatus::ReservationConflict
+        );
+    }
+
+    #[test]
+    fn timeout() {
+        let mut sg = SgIo::new(Mock {
+            host_status: DID_TIME_OUT,
+            ..Mock::default()
+        });
+        assert!(matches!(read(&mut sg).0, Err(ScsiError::Timeout)));
+
+        let mut sg = SgIo::new(Mock {
+            driver_status: 0x10 | DRIVER_TIMEOUT,
+            ..Mock::default()
+        });
+        assert!(matches!(read(&mut sg).0, Err(ScsiError::Timeout)));
+    }
+
+    #[test]
+    fn transport_error() {
+        // DID_NO_CONNECT
+        let mut sg = SgIo::new(Mock {
+            host_status: 0x01,
+            ..Mock::default()
+        });
+        assert!(matches!(
+            read(&mut sg).0,
+            Err(ScsiError::Transport {
+                host_status: 0x01,
+                driver_status: 0
+            })
+        ));
+
+        // DRIVER_ERROR with no SCSI status.
+        let mut sg = SgIo::new(Mock {
+            driver_status: 0x04,
+            ..Mock::default()
+        });
+        assert!(matches!(
+            read(&mut sg).0,
+            Err(ScsiError::Transport {
+                host_status: 0,
+                driver_status: 0x04
+            })
+        ));
+
+        // A driver error alongside a SCSI status still reports the status.
+        let mut sg = SgIo::new(Mock {
+            status: 0x08,
+            driver_status: 0x04,
+            ..Mock::default()
+        });
+        assert_eq!(read(&mut sg).0.unwrap().status, ScsiStatus::Busy);
+    }
+
+    #[test]
+    fn ioctl_error() {
+        let mut sg = SgIo::new(Mock {
+            errno: Some(libc::EIO),
+            ..Mock::default()
+        });
+        match read(&mut sg).0 {
+            Err(ScsiError::Os(e)) => assert_eq!(e.raw_os_error(), Some(libc::EIO)),
+            r => panic!("unexpected {r:?}"),
+        }
+    }
+
+    #[test]
+    fn rejected_before_submission() {
+        let mut sg = SgIo::new(Mock::default());
+
+        let (out, mut data_in) = ([0u8; 512], [0u8; 512]);
+        let r = sg.submit(&mut Command::new(&READ_10).bidirectional(&out, &mut data_in));
+        assert!(matches!(r, Err(ScsiError::Unsupported(_))));
+
+        let r = sg.submit(&mut Command::new(&READ_10[..6]));
+        assert!(matches!(r, Err(ScsiError::InvalidCommand(_))));
+
+        assert_eq!(sg.into_inner().calls, 0);
+    }
+}
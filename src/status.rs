@@ -0,0 +1,128 @@
+/*
+ * Copyright 2025 Jason King
+ */
+
+use crate::{ScsiError, Sense};
+use std::fmt;
+
+/// A SCSI status byte (SAM-5 5.3).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum ScsiStatus {
+    #[default]
+    Good,
+    CheckCondition,
+    ConditionMet,
+    Busy,
+    ReservationConflict,
+    TaskSetFull,
+    AcaActive,
+    TaskAborted,
+    /// An obsolete, reserved or otherwise unrecognized status value.
+    Other(u8),
+}
+
+impl ScsiStatus {
+    pub fn is_good(&self) -> bool {
+        matches!(self, ScsiStatus::Good | ScsiStatus::ConditionMet)
+    }
+}
+
+impl From<u8> for ScsiStatus {
+    fn from(val: u8) -> Self {
+        match val {
+            0x00 => ScsiStatus::Good,
+            0x02 => ScsiStatus::CheckCondition,
+            0x04 => ScsiStatus::ConditionMet,
+            0x08 => ScsiStatus::Busy,
+            0x18 => ScsiStatus::ReservationConflict,
+            0x28 => ScsiStatus::TaskSetFull,
+            0x30 => ScsiStatus::AcaActive,
+            0x40 => ScsiStatus::TaskAborted,
+            v => ScsiStatus::Other(v),
+        }
+    }
+}
+
+impl From<ScsiStatus> for u8 {
+    fn from(val: ScsiStatus) -> Self {
+        match val {
+            ScsiStatus::Good => 0x00,
+            ScsiStatus::CheckCondition => 0x02,
+            ScsiStatus::ConditionMet => 0x04,
+            ScsiStatus::Busy => 0x08,
+            ScsiStatus::ReservationConflict => 0x18,
+            ScsiStatus::TaskSetFull => 0x28,
+            ScsiStatus::AcaActive => 0x30,
+            ScsiStatus::TaskAborted => 0x40,
+            ScsiStatus::Other(v) => v,
+        }
+    }
+}
+
+impl fmt::Display for ScsiStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ScsiStatus::Good => f.write_str("GOOD"),
+            ScsiStatus::CheckCondition => f.write_str("CHECK CONDITION"),
+            ScsiStatus::ConditionMet => f.write_str("CONDITION MET"),
+            ScsiStatus::Busy => f.write_str("BUSY"),
+            ScsiStatus::ReservationConflict => f.write_str("RESERVATION CONFLICT"),
+            ScsiStatus::TaskSetFull => f.write_str("TASK SET FULL"),
+            ScsiStatus::AcaActive => f.write_str("ACA ACTIVE"),
+            ScsiStatus::TaskAborted => f.write_str("TASK ABORTED"),
+            ScsiStatus::Other(v) => write!(f, "status {v:#04x}"),
+        }
+    }
+}
+
+/// The outcome of a command that was delivered to the device.
+///
+/// Any sense data returned is written into the command's sense buffer;
+/// `rqresid` is the number of bytes of that buffer that were not filled in,
+/// and `rqstatus` is the status of the REQUEST SENSE used to fetch it.
+/// `retries` is the number of times the command was reissued under a
+/// [`RetryPolicy`](crate::RetryPolicy).
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct CommandResult {
+    pub status: ScsiStatus,
+    pub resid: usize,
+    pub rqresid: usize,
+    pub rqstatus: ScsiStatus,
+    pub retries: u32,
+}
+
+impl CommandResult {
+    pub fn is_good(&self) -> bool {
+        self.status.is_good()
+    }
+
+    /// The number of valid sense bytes in a sense buffer of `len` bytes.
+    pub fn sense_len(&self, len: usize) -> usize {
+        len.saturating_sub(self.rqresid)
+    }
+
+    /// Convert a non-GOOD status into [`ScsiError::Status`], parsing any
+    /// sense data returned in `sense` (the command's sense buffer).
+    pub fn check(self, sense: Option<&[u8]>) -> Result<Self, ScsiError> {
+        if self.is_good() {
+            return Ok(self);
+        }
+
+        Err(ScsiError::Status {
+            status: self.status,
+            sense: sense.and_then(|buf| Sense::from_result(buf, &self)),
+        })
+    }
+
+    /// Fail with [`ScsiError::ShortTransfer`] unless all `len` bytes of the
+    /// data buffer were transferred.
+    pub fn require_full(self, len: usize) -> Result<Self, ScsiError> {
+        match self.resid {
+            0 => Ok(self),
+            resid => Err(ScsiError::ShortTransfer {
+                expected: len,
+                actual: len.saturating_sub(resid),
+            }),
+        }
+    }
+}
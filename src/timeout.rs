@@ -0,0 +1,117 @@
+/*
+ * Copyright 2025 Jason King
+ */
+
+use crate::ScsiError;
+use std::fmt;
+use std::time::Duration;
+
+/// A command timeout, in whole seconds.
+///
+/// `uscsi_timeout` is a signed short, so the longest timeout that can be
+/// expressed is [`Timeout::MAX`] (32767 seconds). A zero timeout leaves the
+/// choice to the transport's default.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Default)]
+pub struct Timeout(u16);
+
+impl Timeout {
+    /// The timeout used by [`Device`](crate::Device)'s convenience methods.
+    pub const DEFAULT: Timeout = Timeout(60);
+    pub const MAX: Timeout = Timeout(i16::MAX as u16);
+
+    /// A timeout of `secs` seconds, if it is no more than [`Timeout::MAX`].
+    pub const fn from_secs(secs: u16) -> Result<Self, ScsiError> {
+        if secs > Self::MAX.0 {
+            return Err(ScsiError::InvalidCommand("timeout exceeds 32767 seconds"));
+        }
+        Ok(Timeout(secs))
+    }
+
+    /// `d` rounded up to whole seconds and clamped to [`Timeout::MAX`].
+    pub fn saturating(d: Duration) -> Self {
+        Self::try_from(d).unwrap_or(Self::MAX)
+    }
+
+    pub fn as_secs(&self) -> u16 {
+        self.0
+    }
+
+    pub fn as_duration(&self) -> Duration {
+        Duration::from_secs(self.0 as u64)
+    }
+}
+
+impl TryFrom<Duration> for Timeout {
+    type Error = ScsiError;
+
+    /// Round `d` up to whole seconds, failing if that exceeds
+    /// [`Timeout::MAX`].
+    fn try_from(d: Duration) -> Result<Self, ScsiError> {
+        let secs = d.as_secs().saturating_add(u64::from(d.subsec_nanos() != 0));
+        match u16::try_from(secs) {
+            Ok(secs) => Self::from_secs(secs),
+            Err(_) => Err(ScsiError::InvalidCommand("timeout exceeds 32767 seconds")),
+        }
+    }
+}
+
+impl TryFrom<u16> for Timeout {
+    type Error = ScsiError;
+
+    fn try_from(secs: u16) -> Result<Self, ScsiError> {
+        Self::from_secs(secs)
+    }
+}
+
+impl From<Timeout> for Duration {
+    fn from(t: Timeout) -> Self {
+        t.as_duration()
+    }
+}
+
+impl fmt::Display for Timeout {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}s", self.0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn from_secs() {
+        assert_eq!(Timeout::from_secs(0).unwrap().as_secs(), 0);
+        assert_eq!(Timeout::from_secs(60).unwrap(), Timeout::DEFAULT);
+        assert_eq!(Timeout::from_secs(32767).unwrap(), Timeout::MAX);
+        assert_eq!(Timeout::MAX.as_secs(), 32767);
+        assert!(Timeout::from_secs(32768).is_err());
+        assert!(Timeout::from_secs(u16::MAX).is_err());
+        assert!(Timeout::try_from(32768u16).is_err());
+        assert_eq!(Timeout::DEFAULT.to_string(), "60s");
+    }
+
+    #[test]
+    fn from_duration() {
+        let t = |d| Timeout::try_from(d).map(|t| t.as_secs()).ok();
+        assert_eq!(t(Duration::ZERO), Some(0));
+        assert_eq!(t(Duration::from_nanos(1)), Some(1));
+        assert_eq!(t(Duration::from_millis(1500)), Some(2));
+        assert_eq!(t(Duration::from_secs(2)), Some(2));
+        assert_eq!(t(Duration::from_secs(32767)), Some(32767));
+        assert_eq!(t(Duration::new(32766, 1)), Some(32767));
+        assert_eq!(t(Duration::new(32767, 1)), None);
+        assert_eq!(t(Duration::from_secs(65536)), None);
+        assert_eq!(t(Duration::MAX), None);
+
+        assert_eq!(Duration::from(Timeout::DEFAULT), Duration::from_secs(60));
+    }
+
+    #[test]
+    fn saturating() {
+        assert_eq!(Timeout::saturating(Duration::from_millis(100)).as_secs(), 1);
+        assert_eq!(Timeout::saturating(Duration::from_secs(300)).as_secs(), 300);
+        assert_eq!(Timeout::saturating(Duration::new(32767, 1)), Timeout::MAX);
+        assert_eq!(Timeout::saturating(Duration::MAX), Timeout::MAX);
+    }
+}
@@ -0,0 +1,305 @@
+/*
+ * Copyright 2025 Jason King
+ */
+
+//! Command tracing, enabled by the `tracing` feature.
+//!
+//! Every command a [`Device`](crate::Device) submits gets a DEBUG level
+//! `scsi_command` span carrying the decoded opcode, CDB, direction, length
+//! and timeout, with the duration, status, residuals and any sense data
+//! recorded once it completes. At TRACE level the data-out and data-in
+//! payloads (up to 512 bytes of each) are logged as events.
+
+use crate::{Command, CommandResult, ScsiError, Transport};
+
+/// The most data bytes of a command logged at TRACE level.
+#[cfg(feature = "tracing")]
+const DUMP_MAX: usize = 512;
+
+/// Submit `cmd` to `transport`.
+#[cfg(not(feature = "tracing"))]
+#[inline]
+pub(crate) fn submit<T: Transport + ?Sized>(
+    transport: &mut T,
+    cmd: &mut Command<'_>,
+) -> Result<CommandResult, ScsiError> {
+    transport.submit(cmd)
+}
+
+/// Submit `cmd` to `transport` within a `scsi_command` span.
+#[cfg(feature = "tracing")]
+pub(crate) fn submit<T: Transport + ?Sized>(
+    transport: &mut T,
+    cmd: &mut Command<'_>,
+) -> Result<CommandResult, ScsiError> {
+    use crate::{cdb, Sense};
+    use std::time::Instant;
+    use tracing::field::{display, Empty};
+    use tracing::Level;
+
+    let span = tracing::debug_span!(
+        "scsi_command",
+        opcode = cdb::opcode_name(cmd.cdb).unwrap_or("unknown"),
+        cdb = %Hex(cmd.cdb),
+        direction = ?cmd.direction(),
+        len = cmd.data.len(),
+        timeout = cmd.timeout.as_secs(),
+        duration_us = Empty,
+        status = Empty,
+        resid = Empty,
+        rqresid = Empty,
+        sense = Empty,
+        error = Empty,
+    );
+    let _enter = span.enter();
+
+    if tracing::enabled!(Level::TRACE) {
+        let out = cmd.data.output_bytes(DUMP_MAX);
+        if !out.is_empty() {
+            tracing::trace!(data = %Hex(&out), "data-out");
+        }
+    }
+
+    let start = Instant::now();
+    let result = transport.submit(cmd);
+    span.record("duration_us", start.elapsed().as_micros() as u64);
+
+    match &result {
+        Ok(r) => {
+            span.record("status", display(r.status));
+            span.record("resid", r.resid);
+            span.record("rqresid", r.rqresid);
+            if !r.is_good() {
+                if let Some(sense) = cmd
+                    .sense
+                    .as_deref()
+                    .and_then(|buf| Sense::from_result(buf, r))
+                {
+                    span.record("sense", display(&sense));
+                }
+            }
+            tracing::debug!(status = %r.status, "command complete");
+
+            if tracing::enabled!(Level::TRACE) {
+                let len = cmd.data.input_len().saturating_sub(r.resid);
+                let data = cmd.data.input_bytes(len.min(DUMP_MAX));
+                if !data.is_empty() {
+                    tracing::trace!(data = %Hex(&data), "data-in");
+                }
+            }
+        }
+        Err(e) => {
+            span.record("error", display(e));
+            tracing::debug!(error = %e, "command failed");
+        }
+    }
+
+    result
+}
+
+/// Bytes formatted as space separated hex.
+#[cfg(feature = "tracing")]
+struct Hex<'a>(&'a [u8]);
+
+#[cfg(feature = "tracing")]
+impl std::fmt::Display for Hex<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for (i, b) in self.0.iter().enumerate() {
+            if i > 0 {
+                f.write_str(" ")?;
+            }
+            write!(f, "{b:02x}")?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(all(test, feature = "tracing"))]
+mod tests {
+    use super::*;
+    use crate::cdb::{Cdb, CdbSize, Inquiry, Read, Write};
+    use crate::{Device, Emulator, Flags, Timeout};
+    use std::collections::HashMap;
+    use std::fmt;
+    use std::sync::{Arc, Mutex};
+    use tracing::field::{Field, Visit};
+    use tracing::level_filters::LevelFilter;
+    use tracing::span::{Attributes, Id, Record};
+    use tracing::subscriber::Interest;
+    use tracing::{Event, Level, Metadata, Subscriber};
+
+    /// The fields recorded on a span or event, formatted.
+    #[derive(Debug, Default)]
+    struct Fields(HashMap<&'static str, String>);
+
+    impl Fields {
+        fn get(&self, name: &str) -> Option<&str> {
+            self.0.get(name).map(String::as_str)
+        }
+    }
+
+    impl Visit for Fields {
+        fn record_str(&mut self, field: &Field, value: &str) {
+            self.0.insert(field.name(), value.to_string());
+        }
+
+        fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+            self.0.insert(field.name(), format!("{value:?}"));
+        }
+    }
+
+    /// A subscriber recording every span and event up to `level`.
+    struct Capture {
+        level: Level,
+        spans: Mutex<Vec<Fields>>,
+        events: Mutex<Vec<(Level, Fields)>>,
+    }
+
+    impl Subscriber for Capture {
+        fn register_callsite(&self, _: &'static Metadata<'static>) -> Interest {
+            // Other tests may be running with a different level.
+            Interest::sometimes()
+        }
+
+        fn enabled(&self, meta: &Metadata<'_>) -> bool {
+            *meta.level() <= self.level
+        }
+
+        fn max_level_hint(&self) -> Option<LevelFilter> {
+            Some(LevelFilter::from_level(self.level))
+        }
+
+        fn new_span(&self, attrs: &Attributes<'_>) -> Id {
+            let mut fields = Fields::default();
+            attrs.record(&mut fields);
+            let mut spans = self.spans.lock().unwrap();
+            spans.push(fields);
+            Id::from_u64(spans.len() as u64)
+        }
+
+        fn record(&self, id: &Id, values: &Record<'_>) {
+            values.record(&mut self.spans.lock().unwrap()[id.into_u64() as usize - 1]);
+        }
+
+        fn record_follows_from(&self, _: &Id, _: &Id) {}
+
+        fn event(&self, event: &Event<'_>) {
+            let mut fields = Fields::default();
+            event.record(&mut fields);
+            let level = *event.metadata().level();
+            self.events.lock().unwrap().push((level, fields));
+        }
+
+        fn enter(&self, _: &Id) {}
+
+        fn exit(&self, _: &Id) {}
+    }
+
+    /// Run `f` against an emulated device, capturing its traces up to
+    /// `level`.
+    fn capture(level: Level, f: impl FnOnce(&mut Device<Emulator>)) -> Arc<Capture> {
+        let capture = Arc::new(Capture {
+            level,
+            spans: Mutex::new(Vec::new()),
+            events: Mutex::new(Vec::new()),
+        });
+        let mut dev = Device::new(Emulator::new(512, 64));
+        tracing::subscriber::with_default(capture.clone(), || f(&mut dev));
+        capture
+    }
+
+    /// The data dumped by the `message` events captured.
+    fn dumps(capture: &Capture, message: &str) -> Vec<String> {
+        let events = capture.events.lock().unwrap();
+        events
+            .iter()
+            .filter(|(_, fields)| fields.get("message") == Some(message))
+            .map(|(level, fields)| {
+                assert_eq!(*level, Level::TRACE);
+                fields.get("data").unwrap().to_string()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn span_fields() {
+        let capture = capture(Level::DEBUG, |dev| {
+            let read = Read {
+                min_size: CdbSize::Ten,
+                ..Read::new(3, 1, 512)
+            };
+            let mut buf = [0u8; 512];
+            dev.execute(&read, &mut buf, Timeout::DEFAULT).unwrap();
+        });
+
+        let spans = capture.spans.lock().unwrap();
+        assert_eq!(spans.len(), 1);
+        let span = &spans[0];
+        assert_eq!(span.get("opcode"), Some("READ(10)"));
+        assert_eq!(span.get("cdb"), Some("28 00 00 00 00 03 00 00 01 00"));
+        assert_eq!(span.get("direction"), Some("In"));
+        assert_eq!(span.get("len"), Some("512"));
+        assert_eq!(span.get("timeout"), Some("60"));
+        assert_eq!(span.get("status"), Some("GOOD"));
+        assert_eq!(span.get("resid"), Some("0"));
+        // None of the sense buffer was used.
+        assert_eq!(span.get("rqresid"), Some("252"));
+        assert!(span.get("duration_us").is_some());
+        assert_eq!(span.get("sense"), None);
+        assert_eq!(span.get("error"), None);
+
+        // No data at DEBUG level.
+        let events = capture.events.lock().unwrap();
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].0, Level::DEBUG);
+        assert_eq!(events[0].1.get("message"), Some("command complete"));
+    }
+
+    #[test]
+    fn span_sense() {
+        let mut err = None;
+        let capture = capture(Level::DEBUG, |dev| {
+            let cdb = Read::new(64, 1, 512).to_bytes();
+            let mut buf = [0u8; 512];
+            err = dev
+                .read(&cdb, &mut buf, None, Flags::empty(), Timeout::DEFAULT)
+                .err();
+        });
+        let sense = err.as_ref().and_then(|e| e.sense()).unwrap();
+
+        let spans = capture.spans.lock().unwrap();
+        assert_eq!(spans.len(), 1);
+        assert_eq!(spans[0].get("status"), Some("CHECK CONDITION"));
+        assert_eq!(spans[0].get("sense"), Some(sense.to_string().as_str()));
+    }
+
+    #[test]
+    fn data_dumps() {
+        let data: Vec<u8> = (0..1024).map(|i| (i * 7) as u8).collect();
+        let mut inquiry = [0u8; 36];
+        let capture = capture(Level::TRACE, |dev| {
+            let timeout = Timeout::DEFAULT;
+            dev.execute_out(&Write::new(0, 2, 512), &data, timeout)
+                .unwrap();
+            let mut buf = [0u8; 1024];
+            dev.execute(&Read::new(0, 2, 512), &mut buf, timeout)
+                .unwrap();
+            dev.execute(&Inquiry::standard(36), &mut inquiry, Timeout::DEFAULT)
+                .unwrap();
+        });
+
+        // Dumps are capped at DUMP_MAX bytes.
+        let capped = Hex(&data[..DUMP_MAX]).to_string();
+        assert_eq!(dumps(&capture, "data-out"), std::slice::from_ref(&capped));
+        assert_eq!(
+            dumps(&capture, "data-in"),
+            [capped, Hex(&inquiry).to_string()]
+        );
+    }
+
+    #[test]
+    fn hex() {
+        assert_eq!(Hex(&[]).to_string(), "");
+        assert_eq!(Hex(&[0x00, 0x1f, 0xff]).to_string(), "00 1f ff");
+    }
+}
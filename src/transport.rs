@@ -0,0 +1,567 @@
+/*
+ * Copyright 2025 Jason King
+ */
+
+use crate::aligned::AlignedBuf;
+use crate::{CommandResult, Flags, ScsiError, TagQueue, Timeout};
+use std::fmt;
+use std::io::{IoSlice, IoSliceMut};
+use std::marker::PhantomData;
+use std::ptr::NonNull;
+
+/// The direction of a command's data transfer, relative to the host.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum DataDirection {
+    None,
+    /// Data is transferred from the device (data-in).
+    In,
+    /// Data is transferred to the device (data-out).
+    Out,
+    /// Data is transferred in both directions (e.g. XDWRITEREAD).
+    Bidirectional,
+}
+
+/// The data buffers of a [`Command`].
+#[derive(Debug, Default)]
+pub enum DataBuffer<'a> {
+    #[default]
+    None,
+    /// Filled in by the device.
+    In(&'a mut [u8]),
+    /// Sent to the device.
+    Out(&'a [u8]),
+    /// `data_out` is sent to the device, and `data_in` filled in by it.
+    Bidirectional {
+        data_out: &'a [u8],
+        data_in: &'a mut [u8],
+    },
+    /// Filled in by the device, in order.
+    InVectored(IoVecMut<'a>),
+    /// Gathered in order and sent to the device.
+    OutVectored(&'a [IoSlice<'a>]),
+}
+
+impl DataBuffer<'_> {
+    pub fn direction(&self) -> DataDirection {
+        match self {
+            DataBuffer::None => DataDirection::None,
+            DataBuffer::In(_) | DataBuffer::InVectored(_) => DataDirection::In,
+            DataBuffer::Out(_) | DataBuffer::OutVectored(_) => DataDirection::Out,
+            DataBuffer::Bidirectional { .. } => DataDirection::Bidirectional,
+        }
+    }
+
+    /// The contiguous buffer the device fills in, if any.
+    pub fn input(&mut self) -> Option<&mut [u8]> {
+        match self {
+            DataBuffer::In(data) | DataBuffer::Bidirectional { data_in: data, .. } => Some(data),
+            _ => None,
+        }
+    }
+
+    /// The contiguous buffer sent to the device, if any.
+    pub fn output(&self) -> Option<&[u8]> {
+        match self {
+            DataBuffer::Out(data) | DataBuffer::Bidirectional { data_out: data, .. } => Some(data),
+            _ => None,
+        }
+    }
+
+    /// The total number of bytes to transfer, in both directions.
+    pub fn len(&self) -> usize {
+        match self {
+            DataBuffer::None => 0,
+            DataBuffer::In(data) => data.len(),
+            DataBuffer::Out(data) => data.len(),
+            DataBuffer::Bidirectional { data_out, data_in } => data_out.len() + data_in.len(),
+            DataBuffer::InVectored(iov) => iov.total_len(),
+            DataBuffer::OutVectored(iov) => iov.iter().map(|v| v.len()).sum(),
+        }
+    }
+
+    pub fn is_vectored(&self) -> bool {
+        matches!(self, DataBuffer::InVectored(_) | DataBuffer::OutVectored(_))
+    }
+
+    /// The length of the data-in buffer, in bytes.
+    pub(crate) fn input_len(&self) -> usize {
+        match self {
+            DataBuffer::In(data) => data.len(),
+            DataBuffer::Bidirectional { data_in, .. } => data_in.len(),
+            DataBuffer::InVectored(iov) => iov.total_len(),
+            _ => 0,
+        }
+    }
+
+    /// A copy of up to `max` bytes of the data-out, gathered from all its
+    /// segments.
+    pub(crate) fn output_bytes(&self, max: usize) -> Vec<u8> {
+        let mut out = Vec::new();
+        let mut add = |seg: &[u8]| {
+            let n = seg.len().min(max - out.len());
+            out.extend_from_slice(&seg[..n]);
+        };
+
+        match self {
+            DataBuffer::OutVectored(iov) => iov.iter().for_each(|seg| add(seg)),
+            data => data.output().into_iter().for_each(add),
+        }
+        out
+    }
+
+    /// A copy of up to `max` bytes of the data-in, gathered from all its
+    /// segments.
+    pub(crate) fn input_bytes(&mut self, max: usize) -> Vec<u8> {
+        let mut out = Vec::new();
+        let mut add = |seg: &[u8]| {
+            let n = seg.len().min(max - out.len());
+            out.extend_from_slice(&seg[..n]);
+        };
+
+        match self {
+            DataBuffer::InVectored(iov) => iov.iter_mut().for_each(|seg| add(seg)),
+            data => data.input().into_iter().for_each(|seg| add(seg)),
+        }
+        out
+    }
+
+    /// Copy `src` into the data-in, scattering it across the segments in
+    /// order. Returns the number of bytes copied.
+    pub(crate) fn fill_input(&mut self, src: &[u8]) -> usize {
+        let mut left = src;
+        let mut copy = |seg: &mut [u8]| {
+            let n = seg.len().min(left.len());
+            seg[..n].copy_from_slice(&left[..n]);
+            left = &left[n..];
+        };
+
+        match self {
+            DataBuffer::InVectored(iov) => iov.iter_mut().for_each(&mut copy),
+            data => data.input().into_iter().for_each(&mut copy),
+        }
+        src.len() - left.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+/// The segments of a vectored data-in buffer, borrowed from a slice of
+/// [`IoSliceMut`].
+///
+/// Unlike `&mut [IoSliceMut]`, only the bytes of the segments can be
+/// written through this, not the segments themselves, so a [`Command`]
+/// holding one can still be reborrowed for a shorter lifetime.
+pub struct IoVecMut<'a> {
+    ptr: NonNull<IoSliceMut<'a>>,
+    len: usize,
+    _marker: PhantomData<&'a mut [u8]>,
+}
+
+impl<'a> IoVecMut<'a> {
+    pub fn new(iov: &'a mut [IoSliceMut<'_>]) -> Self {
+        Self {
+            ptr: NonNull::from(&mut *iov).cast(),
+            len: iov.len(),
+            _marker: PhantomData,
+        }
+    }
+
+    /// The number of segments.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// The total length of the segments in bytes.
+    pub fn total_len(&self) -> usize {
+        self.segments().iter().map(|v| v.len()).sum()
+    }
+
+    pub fn iter_mut(&mut self) -> impl Iterator<Item = &mut [u8]> {
+        // SAFETY: ptr and len come from a slice uniquely borrowed for 'a,
+        // and only the segments' contents are handed out.
+        let iov = unsafe { std::slice::from_raw_parts_mut(self.ptr.as_ptr(), self.len) };
+        iov.iter_mut().map(|v| &mut **v)
+    }
+
+    /// The segments, which have the same layout as `struct iovec`.
+    pub fn as_ptr(&self) -> *const IoSliceMut<'a> {
+        self.ptr.as_ptr()
+    }
+
+    fn segments(&self) -> &[IoSliceMut<'a>] {
+        // SAFETY: As for iter_mut().
+        unsafe { std::slice::from_raw_parts(self.ptr.as_ptr(), self.len) }
+    }
+}
+
+impl fmt::Debug for IoVecMut<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_list()
+            .entries(self.segments().iter().map(|v| v.len()))
+            .finish()
+    }
+}
+
+/// A single SCSI command to be submitted to a [`Transport`].
+///
+/// The direction of any data transfer is taken from `data`; transports set
+/// or clear [`Flags::READ`] to match, whatever `flags` contains.
+#[derive(Debug)]
+pub struct Command<'a> {
+    pub cdb: &'a [u8],
+    pub data: DataBuffer<'a>,
+    pub sense: Option<&'a mut [u8]>,
+    pub flags: Flags,
+    pub timeout: Timeout,
+    /// Send the command down a specific multipath path (uscsi only).
+    pub path_instance: Option<u64>,
+}
+
+impl<'a> Command<'a> {
+    /// A command that transfers no data.
+    pub fn new(cdb: &'a [u8]) -> Self {
+        Self {
+            cdb,
+            data: DataBuffer::None,
+            sense: None,
+            flags: Flags::empty(),
+            timeout: Timeout::default(),
+            path_instance: None,
+        }
+    }
+
+    pub fn data_in(mut self, data: &'a mut [u8]) -> Self {
+        self.data = DataBuffer::In(data);
+        self
+    }
+
+    pub fn data_out(mut self, data: &'a [u8]) -> Self {
+        self.data = DataBuffer::Out(data);
+        self
+    }
+
+    pub fn data_in_vectored(mut self, data: &'a mut [IoSliceMut<'_>]) -> Self {
+        self.data = DataBuffer::InVectored(IoVecMut::new(data));
+        self
+    }
+
+    pub fn data_out_vectored(mut self, data: &'a [IoSlice<'a>]) -> Self {
+        self.data = DataBuffer::OutVectored(data);
+        self
+    }
+
+    pub fn bidirectional(mut self, data_out: &'a [u8], data_in: &'a mut [u8]) -> Self {
+        self.data = DataBuffer::Bidirectional { data_out, data_in };
+        self
+    }
+
+    pub fn sense(mut self, sense: &'a mut [u8]) -> Self {
+        self.sense = Some(sense);
+        self
+    }
+
+    pub fn flags(mut self, flags: Flags) -> Self {
+        self.flags |= flags;
+        self
+    }
+
+    pub fn tag(mut self, tag: TagQueue) -> Self {
+        self.flags = tag.apply(self.flags);
+        self
+    }
+
+    pub fn path_instance(mut self, path_instance: u64) -> Self {
+        self.path_instance = Some(path_instance);
+        self
+    }
+
+    pub fn timeout(mut self, timeout: Timeout) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Returns true if the device is expected to fill in `data`.
+    pub fn is_read(&self) -> bool {
+        self.direction() == DataDirection::In
+    }
+
+    pub fn direction(&self) -> DataDirection {
+        self.data.direction()
+    }
+
+    /// Call `submit` with a vectored data buffer replaced by a contiguous,
+    /// page-aligned bounce buffer: data-out segments are gathered into it
+    /// first, and the data-in that was transferred is scattered back into
+    /// the segments afterwards. Other commands are passed through as is.
+    ///
+    /// This lets a transport that only handles contiguous buffers accept
+    /// vectored commands.
+    pub fn with_contiguous_data<F>(&mut self, submit: F) -> Result<CommandResult, ScsiError>
+    where
+        F: FnOnce(&mut Command<'_>) -> Result<CommandResult, ScsiError>,
+    {
+        if !self.data.is_vectored() {
+            return submit(self);
+        }
+
+        let mut bounce = AlignedBuf::page_aligned(self.data.len());
+        let Command {
+            cdb,
+            data,
+            sense,
+            flags,
+            timeout,
+            path_instance,
+        } = self;
+        let mut cmd = Command {
+            cdb,
+            data: DataBuffer::None,
+            sense: sense.as_deref_mut(),
+            flags: *flags,
+            timeout: *timeout,
+            path_instance: *path_instance,
+        };
+
+        match data {
+            DataBuffer::OutVectored(iov) => {
+                let mut off = 0;
+                for v in iov.iter() {
+                    bounce[off..off + v.len()].copy_from_slice(v);
+                    off += v.len();
+                }
+                cmd.data = DataBuffer::Out(&bounce);
+                submit(&mut cmd)
+            }
+            DataBuffer::InVectored(_) => {
+                cmd.data = DataBuffer::In(&mut bounce);
+                let result = submit(&mut cmd)?;
+
+                let len = bounce.len().saturating_sub(result.resid);
+                data.fill_input(&bounce[..len]);
+                Ok(result)
+            }
+            _ => unreachable!("data is vectored"),
+        }
+    }
+}
+
+/// A backend capable of delivering SCSI commands to a device.
+///
+/// The illumos `USCSICMD` ioctl ([`crate::Uscsi`]) is one implementation;
+/// code written against this trait (or [`crate::Device`]) works unchanged
+/// with any other.
+pub trait Transport {
+    /// Submit `cmd` and wait for it to complete.
+    ///
+    /// A transport that cannot carry bidirectional transfers fails them
+    /// with [`ScsiError::Unsupported`].
+    fn submit(&mut self, cmd: &mut Command<'_>) -> Result<CommandResult, ScsiError>;
+
+    /// Reset the target.
+    fn reset(&mut self) -> Result<(), ScsiError> {
+        Err(ScsiError::Os(std::io::ErrorKind::Unsupported.into()))
+    }
+
+    /// The maximum transfer size (in bytes) supported for a single command.
+    fn max_xfer(&mut self) -> Result<usize, ScsiError> {
+        Err(ScsiError::Os(std::io::ErrorKind::Unsupported.into()))
+    }
+}
+
+impl<T: Transport + ?Sized> Transport for &mut T {
+    fn submit(&mut self, cmd: &mut Command<'_>) -> Result<CommandResult, ScsiError> {
+        (**self).submit(cmd)
+    }
+
+    fn reset(&mut self) -> Result<(), ScsiError> {
+        (**self).reset()
+    }
+
+    fn max_xfer(&mut self) -> Result<usize, ScsiError> {
+        (**self).max_xfer()
+    }
+}
+
+impl<T: Transport + ?Sized> Transport for Box<T> {
+    fn submit(&mut self, cmd: &mut Command<'_>) -> Result<CommandResult, ScsiError> {
+        (**self).submit(cmd)
+    }
+
+    fn reset(&mut self) -> Result<(), ScsiError> {
+        (**self).reset()
+    }
+
+    fn max_xfer(&mut self) -> Result<usize, ScsiError> {
+        (**self).max_xfer()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::aligned::page_size;
+    use crate::cdb::{Cdb, Read, TestUnitReady, Write};
+    use crate::Emulator;
+
+    const BS: u32 = 512;
+
+    fn pattern(len: usize) -> Vec<u8> {
+        (0..len)
+            .map(|i| (i as u8).wrapping_mul(29) ^ 0x5a)
+            .collect()
+    }
+
+    /// An emulator with `pattern()` in its first blocks.
+    fn emulator() -> Emulator {
+        let mut emu = Emulator::new(BS, 64);
+        let data = pattern(8 * BS as usize);
+        emu.data_mut()[..data.len()].copy_from_slice(&data);
+        emu
+    }
+
+    #[test]
+    fn io_vec_mut() {
+        let (mut a, mut b, mut c) = ([0u8; 3], [0u8; 0], [0u8; 5]);
+        let mut iov = [
+            IoSliceMut::new(&mut a),
+            IoSliceMut::new(&mut b),
+            IoSliceMut::new(&mut c),
+        ];
+        let mut vec = IoVecMut::new(&mut iov);
+        assert_eq!(vec.len(), 3);
+        assert_eq!(vec.total_len(), 8);
+        assert_eq!(format!("{vec:?}"), "[3, 0, 5]");
+        for (i, seg) in vec.iter_mut().enumerate() {
+            seg.fill(i as u8 + 1);
+        }
+        assert_eq!((a, c), ([1; 3], [3; 5]));
+
+        assert!(IoVecMut::new(&mut []).is_empty());
+    }
+
+    #[test]
+    fn scatter_read() {
+        let mut emu = emulator();
+        let cdb = Read::new(1, 3, BS).to_bytes();
+
+        let (mut a, mut b, mut c, mut d) = ([0u8; 1], [0u8; 700], [0u8; 0], [0u8; 835]);
+        let mut iov = [
+            IoSliceMut::new(&mut a),
+            IoSliceMut::new(&mut b),
+            IoSliceMut::new(&mut c),
+            IoSliceMut::new(&mut d),
+        ];
+        let mut cmd = Command::new(&cdb).data_in_vectored(&mut iov);
+        assert_eq!(cmd.data.len(), 3 * BS as usize);
+        let result = emu.submit(&mut cmd).unwrap();
+        assert_eq!(result.resid, 0);
+
+        let want = &pattern(4 * BS as usize)[BS as usize..];
+        assert_eq!([&a[..], &b[..], &d[..]].concat(), want);
+    }
+
+    #[test]
+    fn gather_write() {
+        let mut emu = Emulator::new(BS, 64);
+        let data = pattern(2 * BS as usize);
+        let (a, rest) = data.split_at(17);
+        let (b, c) = rest.split_at(512);
+        let iov = [
+            IoSlice::new(a),
+            IoSlice::new(&[]),
+            IoSlice::new(b),
+            IoSlice::new(c),
+        ];
+
+        let cdb = Write::new(4, 2, BS).to_bytes();
+        let result = emu
+            .submit(&mut Command::new(&cdb).data_out_vectored(&iov))
+            .unwrap();
+        assert_eq!(result.resid, 0);
+        let start = 4 * BS as usize;
+        assert_eq!(&emu.data()[start..start + data.len()], &data[..]);
+    }
+
+    #[test]
+    fn copy_back_honours_resid() {
+        let mut emu = emulator();
+        let want = pattern(2 * BS as usize);
+
+        // A buffer longer than the transfer: only what was read is copied
+        // back, the tail of the last segment is left alone.
+        let cdb = Read::new(0, 2, BS).to_bytes();
+        let (mut a, mut b) = ([0xeeu8; 600], [0xeeu8; 500]);
+        let mut iov = [IoSliceMut::new(&mut a), IoSliceMut::new(&mut b)];
+        let result = emu
+            .submit(&mut Command::new(&cdb).data_in_vectored(&mut iov))
+            .unwrap();
+        assert_eq!(result.resid, 76);
+        assert_eq!(a[..], want[..600]);
+        assert_eq!(b[..424], want[600..]);
+        assert!(b[424..].iter().all(|&x| x == 0xee));
+
+        // A transport reporting a residual the data was not all copied
+        // back for, and one reporting more than the whole buffer.
+        for (extra, copied) in [(BS as usize + 100, 412), (usize::MAX, 0)] {
+            let (mut a, mut b) = ([0xeeu8; 300], [0xeeu8; 724]);
+            let mut iov = [IoSliceMut::new(&mut a), IoSliceMut::new(&mut b)];
+            let mut cmd = Command::new(&cdb).data_in_vectored(&mut iov);
+            let result = cmd
+                .with_contiguous_data(|cmd| {
+                    let mut result = emu.submit(cmd)?;
+                    result.resid = result.resid.saturating_add(extra);
+                    Ok(result)
+                })
+                .unwrap();
+            assert_eq!(result.resid, extra);
+
+            let got = [&a[..], &b[..]].concat();
+            assert_eq!(got[..copied], want[..copied]);
+            assert!(got[copied..].iter().all(|&x| x == 0xee), "{extra}");
+        }
+    }
+
+    #[test]
+    fn bounce_buffer() {
+        let cdb = Read::new(0, 1, BS).to_bytes();
+        let (mut a, mut b) = ([0u8; 100], [0u8; 412]);
+        let mut iov = [IoSliceMut::new(&mut a), IoSliceMut::new(&mut b)];
+        let mut cmd = Command::new(&cdb)
+            .data_in_vectored(&mut iov)
+            .timeout(Timeout::from_secs(7).unwrap());
+        cmd.with_contiguous_data(|cmd| {
+            assert_eq!(cmd.timeout, Timeout::from_secs(7).unwrap());
+            let buf = cmd.data.input().expect("contiguous data-in");
+            assert_eq!(buf.len(), 512);
+            assert_eq!(buf.as_ptr() as usize % page_size(), 0);
+            buf.fill(1);
+            Ok(CommandResult::default())
+        })
+        .unwrap();
+        assert_eq!((a, b), ([1; 100], [1; 412]));
+
+        // Contiguous and data-less commands are passed through as they are.
+        let mut buf = [0u8; 512];
+        let ptr = buf.as_ptr();
+        let mut cmd = Command::new(&cdb).data_in(&mut buf);
+        cmd.with_contiguous_data(|cmd| {
+            assert_eq!(cmd.data.input().unwrap().as_ptr(), ptr);
+            Ok(CommandResult::default())
+        })
+        .unwrap();
+
+        let cdb = TestUnitReady.to_bytes();
+        let mut cmd = Command::new(&cdb);
+        let result = cmd.with_contiguous_data(|cmd| {
+            assert_eq!(cmd.direction(), DataDirection::None);
+            Err(ScsiError::Timeout)
+        });
+        assert!(matches!(result, Err(ScsiError::Timeout)));
+    }
+}
@@ -0,0 +1,80 @@
+/*
+ * Copyright 2025 Jason King
+ */
+
+use crate::{Command, CommandResult, ScsiError, Transport};
+use std::fs::OpenOptions;
+use std::os::fd::{AsFd, AsRawFd, BorrowedFd, IntoRawFd, OwnedFd, RawFd};
+use std::os::unix::fs::OpenOptionsExt;
+use std::path::Path;
+
+/// The illumos `USCSICMD` ioctl transport.
+#[derive(Debug)]
+pub struct Uscsi {
+    fd: OwnedFd,
+}
+
+impl Uscsi {
+    /// Open a raw character device (e.g. `/dev/rdsk/c0t0d0s2`).
+    ///
+    /// The device is opened read/write with `O_NDELAY` so that devices
+    /// with no media present (or that are otherwise not ready) can still
+    /// be opened and sent commands.
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self, std::io::Error> {
+        let file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .custom_flags(libc::O_NDELAY)
+            .open(path)?;
+
+        Ok(Self { fd: file.into() })
+    }
+}
+
+impl Transport for Uscsi {
+    fn submit(&mut self, cmd: &mut Command<'_>) -> Result<CommandResult, ScsiError> {
+        // SAFETY: The descriptor is owned by us and remains open for the
+        // duration of the call, and the CDB, data and sense buffers are
+        // all borrowed from `cmd` until the ioctl returns.
+        unsafe { crate::common(self.fd.as_raw_fd(), cmd) }
+    }
+
+    fn reset(&mut self) -> Result<(), ScsiError> {
+        // SAFETY: The descriptor is owned by us and remains open.
+        unsafe { crate::reset(self.fd.as_raw_fd()) }
+    }
+
+    fn max_xfer(&mut self) -> Result<usize, ScsiError> {
+        crate::max_xfer(self.fd.as_raw_fd())
+    }
+}
+
+impl From<OwnedFd> for Uscsi {
+    fn from(fd: OwnedFd) -> Self {
+        Self { fd }
+    }
+}
+
+impl From<Uscsi> for OwnedFd {
+    fn from(dev: Uscsi) -> Self {
+        dev.fd
+    }
+}
+
+impl AsFd for Uscsi {
+    fn as_fd(&self) -> BorrowedFd<'_> {
+        self.fd.as_fd()
+    }
+}
+
+impl AsRawFd for Uscsi {
+    fn as_raw_fd(&self) -> RawFd {
+        self.fd.as_raw_fd()
+    }
+}
+
+impl IntoRawFd for Uscsi {
+    fn into_raw_fd(self) -> RawFd {
+        self.fd.into_raw_fd()
+    }
+}
@@ -0,0 +1,1227 @@
+/*
+ * Copyright 2025 Jason King
+ */
+
+//! Decoders for the vital product data pages returned by INQUIRY with the
+//! EVPD bit set.
+
+use std::fmt;
+
+pub const SUPPORTED_PAGES: u8 = 0x00;
+pub const UNIT_SERIAL_NUMBER: u8 = 0x80;
+pub const DEVICE_IDENTIFICATION: u8 = 0x83;
+pub const EXTENDED_INQUIRY: u8 = 0x86;
+pub const ATA_INFORMATION: u8 = 0x89;
+pub const BLOCK_LIMITS: u8 = 0xb0;
+pub const BLOCK_DEVICE_CHARACTERISTICS: u8 = 0xb1;
+pub const LOGICAL_BLOCK_PROVISIONING: u8 = 0xb2;
+pub const ZONED_BLOCK_DEVICE_CHARACTERISTICS: u8 = 0xb6;
+
+/// Check `buf` holds VPD page `page` and return it, truncated to the page
+/// length it reports.
+fn page(buf: &[u8], page: u8) -> Option<&[u8]> {
+    if buf.len() < 4 || buf[1] != page {
+        return None;
+    }
+    let len = 4 + be16(&buf[2..4]) as usize;
+    Some(&buf[..len.min(buf.len())])
+}
+
+fn be16(b: &[u8]) -> u16 {
+    u16::from_be_bytes([b[0], b[1]])
+}
+
+fn be32(b: &[u8]) -> u32 {
+    u32::from_be_bytes([b[0], b[1], b[2], b[3]])
+}
+
+/// Fetch big endian fields which may lie beyond the end of a short page
+/// (devices often return an older, shorter version of a page); missing
+/// bytes read as zero.
+struct Fields<'a>(&'a [u8]);
+
+impl Fields<'_> {
+    fn u8(&self, off: usize) -> u8 {
+        self.0.get(off).copied().unwrap_or(0)
+    }
+
+    fn bit(&self, off: usize, bit: u8) -> bool {
+        self.u8(off) & (1 << bit) != 0
+    }
+
+    fn be(&self, off: usize, len: usize) -> u64 {
+        (off..off + len).fold(0, |v, i| v << 8 | self.u8(i) as u64)
+    }
+}
+
+fn ascii(b: &[u8]) -> String {
+    String::from_utf8_lossy(b)
+        .trim_matches(|c: char| c == ' ' || c == '\0')
+        .to_string()
+}
+
+/// Supported VPD pages (0x00).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SupportedPages {
+    pub pages: Vec<u8>,
+}
+
+impl SupportedPages {
+    pub fn parse(buf: &[u8]) -> Option<Self> {
+        let buf = page(buf, SUPPORTED_PAGES)?;
+        Some(Self {
+            pages: buf[4..].to_vec(),
+        })
+    }
+
+    pub fn contains(&self, page: u8) -> bool {
+        self.pages.contains(&page)
+    }
+}
+
+/// Unit serial number (0x80).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UnitSerialNumber {
+    pub serial: String,
+}
+
+impl UnitSerialNumber {
+    pub fn parse(buf: &[u8]) -> Option<Self> {
+        let buf = page(buf, UNIT_SERIAL_NUMBER)?;
+        Some(Self {
+            serial: ascii(&buf[4..]),
+        })
+    }
+}
+
+/// The code set of a designator.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum CodeSet {
+    Binary,
+    Ascii,
+    Utf8,
+    Reserved(u8),
+}
+
+impl From<u8> for CodeSet {
+    fn from(val: u8) -> Self {
+        match val & 0x0f {
+            1 => CodeSet::Binary,
+            2 => CodeSet::Ascii,
+            3 => CodeSet::Utf8,
+            v => CodeSet::Reserved(v),
+        }
+    }
+}
+
+/// The entity a designator is associated with.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Association {
+    LogicalUnit,
+    TargetPort,
+    TargetDevice,
+    Reserved,
+}
+
+impl From<u8> for Association {
+    fn from(val: u8) -> Self {
+        match val & 0x03 {
+            0 => Association::LogicalUnit,
+            1 => Association::TargetPort,
+            2 => Association::TargetDevice,
+            _ => Association::Reserved,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum DesignatorType {
+    VendorSpecific,
+    T10VendorId,
+    Eui64,
+    Naa,
+    RelativeTargetPort,
+    TargetPortGroup,
+    LogicalUnitGroup,
+    Md5LogicalUnit,
+    ScsiNameString,
+    ProtocolSpecificPort,
+    Uuid,
+    Reserved(u8),
+}
+
+impl From<u8> for DesignatorType {
+    fn from(val: u8) -> Self {
+        match val & 0x0f {
+            0x0 => DesignatorType::VendorSpecific,
+            0x1 => DesignatorType::T10VendorId,
+            0x2 => DesignatorType::Eui64,
+            0x3 => DesignatorType::Naa,
+            0x4 => DesignatorType::RelativeTargetPort,
+            0x5 => DesignatorType::TargetPortGroup,
+            0x6 => DesignatorType::LogicalUnitGroup,
+            0x7 => DesignatorType::Md5LogicalUnit,
+            0x8 => DesignatorType::ScsiNameString,
+            0x9 => DesignatorType::ProtocolSpecificPort,
+            0xa => DesignatorType::Uuid,
+            v => DesignatorType::Reserved(v),
+        }
+    }
+}
+
+/// A single designation descriptor from the device identification page.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Designator {
+    /// The protocol identifier, if PIV is set (and so the value is
+    /// meaningful).
+    pub protocol_identifier: Option<u8>,
+    pub code_set: CodeSet,
+    pub association: Association,
+    pub designator_type: DesignatorType,
+    pub value: Vec<u8>,
+}
+
+/// The decoded value of a [`Designator`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DesignatorValue<'a> {
+    VendorSpecific(&'a [u8]),
+    T10VendorId {
+        vendor: &'a str,
+        vendor_specific: &'a [u8],
+    },
+    /// An EUI-64 based designator (8, 12 or 16 bytes).
+    Eui64(&'a [u8]),
+    Naa {
+        naa: u8,
+        /// The IEEE company ID, for the formats that carry one.
+        ieee_company_id: Option<u32>,
+        value: &'a [u8],
+    },
+    RelativeTargetPort(u16),
+    TargetPortGroup(u16),
+    LogicalUnitGroup(u16),
+    Md5LogicalUnit(&'a [u8]),
+    ScsiNameString(&'a str),
+    ProtocolSpecificPort(&'a [u8]),
+    Uuid(&'a [u8]),
+    /// A reserved type, or a value too short for its type.
+    Other(&'a [u8]),
+}
+
+impl Designator {
+    pub fn decode(&self) -> DesignatorValue<'_> {
+        let v = &self.value[..];
+        fn text(b: &[u8]) -> &str {
+            let end = b.iter().position(|&c| c == 0).unwrap_or(b.len());
+            std::str::from_utf8(&b[..end]).unwrap_or_default()
+        }
+
+        match self.designator_type {
+            DesignatorType::VendorSpecific => DesignatorValue::VendorSpecific(v),
+            DesignatorType::T10VendorId if v.len() >= 8 => DesignatorValue::T10VendorId {
+                vendor: text(&v[..8]).trim_end(),
+                vendor_specific: &v[8..],
+            },
+            DesignatorType::Eui64 => DesignatorValue::Eui64(v),
+            DesignatorType::Naa if !v.is_empty() => {
+                let naa = v[0] >> 4;
+                let ieee_company_id = match naa {
+                    2 if v.len() >= 6 => Some(be32(&v[2..6]) >> 8),
+                    5 | 6 if v.len() >= 4 => Some(be32(&v[0..4]) >> 4 & 0xff_ffff),
+                    _ => None,
+                };
+                DesignatorValue::Naa {
+                    naa,
+                    ieee_company_id,
+                    value: v,
+                }
+            }
+            DesignatorType::RelativeTargetPort if v.len() >= 4 => {
+                DesignatorValue::RelativeTargetPort(be16(&v[2..4]))
+            }
+            DesignatorType::TargetPortGroup if v.len() >= 4 => {
+                DesignatorValue::TargetPortGroup(be16(&v[2..4]))
+            }
+            DesignatorType::LogicalUnitGroup if v.len() >= 4 => {
+                DesignatorValue::LogicalUnitGroup(be16(&v[2..4]))
+            }
+            DesignatorType::Md5LogicalUnit => DesignatorValue::Md5LogicalUnit(v),
+            DesignatorType::ScsiNameString => DesignatorValue::ScsiNameString(text(v)),
+            DesignatorType::ProtocolSpecificPort => DesignatorValue::ProtocolSpecificPort(v),
+            DesignatorType::Uuid if v.len() >= 18 => DesignatorValue::Uuid(&v[2..18]),
+            _ => DesignatorValue::Other(v),
+        }
+    }
+}
+
+fn hex(f: &mut fmt::Formatter<'_>, b: &[u8]) -> fmt::Result {
+    b.iter().try_for_each(|b| write!(f, "{b:02x}"))
+}
+
+impl fmt::Display for Designator {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.decode() {
+            DesignatorValue::T10VendorId {
+                vendor,
+                vendor_specific,
+            } => write!(
+                f,
+                "t10.{vendor} {}",
+                String::from_utf8_lossy(vendor_specific).trim_end()
+            ),
+            DesignatorValue::Eui64(v) => {
+                f.write_str("eui.")?;
+                hex(f, v)
+            }
+            DesignatorValue::Naa { value, .. } => {
+                f.write_str("naa.")?;
+                hex(f, value)
+            }
+            DesignatorValue::RelativeTargetPort(p) => write!(f, "relative target port {p}"),
+            DesignatorValue::TargetPortGroup(g) => write!(f, "target port group {g}"),
+            DesignatorValue::LogicalUnitGroup(g) => write!(f, "logical unit group {g}"),
+            DesignatorValue::ScsiNameString(s) => f.write_str(s),
+            DesignatorValue::Uuid(u) => {
+                hex(f, &u[0..4])?;
+                for r in [4..6, 6..8, 8..10, 10..16] {
+                    f.write_str("-")?;
+                    hex(f, &u[r])?;
+                }
+                Ok(())
+            }
+            DesignatorValue::VendorSpecific(v)
+            | DesignatorValue::Md5LogicalUnit(v)
+            | DesignatorValue::ProtocolSpecificPort(v)
+            | DesignatorValue::Other(v) => hex(f, v),
+        }
+    }
+}
+
+/// Device identification (0x83).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DeviceIdentification {
+    pub designators: Vec<Designator>,
+}
+
+impl DeviceIdentification {
+    pub fn parse(buf: &[u8]) -> Option<Self> {
+        let buf = page(buf, DEVICE_IDENTIFICATION)?;
+        let mut designators = Vec::new();
+        let mut off = 4;
+
+        while off + 4 <= buf.len() {
+            let d = &buf[off..];
+            let len = d[3] as usize;
+            let Some(value) = d.get(4..4 + len) else {
+                break;
+            };
+
+            designators.push(Designator {
+                protocol_identifier: match d[1] & 0x80 {
+                    0 => None,
+                    _ => Some(d[0] >> 4),
+                },
+                code_set: CodeSet::from(d[0]),
+                association: Association::from(d[1] >> 4),
+                designator_type: DesignatorType::from(d[1]),
+                value: value.to_vec(),
+            });
+            off += 4 + len;
+        }
+
+        Some(Self { designators })
+    }
+
+    /// The designators associated with the logical unit itself.
+    pub fn logical_unit(&self) -> impl Iterator<Item = &Designator> {
+        self.designators
+            .iter()
+            .filter(|d| d.association == Association::LogicalUnit)
+    }
+}
+
+/// Extended INQUIRY data (0x86).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ExtendedInquiry {
+    pub activate_microcode: u8,
+    /// Supported protection types.
+    pub spt: u8,
+    pub grd_chk: bool,
+    pub app_chk: bool,
+    pub ref_chk: bool,
+    pub uask_sup: bool,
+    pub group_sup: bool,
+    pub prior_sup: bool,
+    pub headsup: bool,
+    pub ordsup: bool,
+    pub simpsup: bool,
+    pub wu_sup: bool,
+    pub crd_sup: bool,
+    pub nv_sup: bool,
+    pub v_sup: bool,
+    pub no_pi_chk: bool,
+    pub p_i_i_sup: bool,
+    pub luiclr: bool,
+    pub lu_coll_type: u8,
+    pub r_sup: bool,
+    pub hssrelef: bool,
+    pub cbcs: bool,
+    pub multi_it_nexus_microcode_download: u8,
+    pub extended_self_test_completion_minutes: u16,
+    pub poa_sup: bool,
+    pub hra_sup: bool,
+    pub vsa_sup: bool,
+    pub maximum_supported_sense_data_length: u8,
+}
+
+impl ExtendedInquiry {
+    pub fn parse(buf: &[u8]) -> Option<Self> {
+        let b = Fields(page(buf, EXTENDED_INQUIRY)?);
+        Some(Self {
+            activate_microcode: b.u8(4) >> 6,
+            spt: (b.u8(4) >> 3) & 0x07,
+            grd_chk: b.bit(4, 2),
+            app_chk: b.bit(4, 1),
+            ref_chk: b.bit(4, 0),
+            uask_sup: b.bit(5, 5),
+            group_sup: b.bit(5, 4),
+            prior_sup: b.bit(5, 3),
+            headsup: b.bit(5, 2),
+            ordsup: b.bit(5, 1),
+            simpsup: b.bit(5, 0),
+            wu_sup: b.bit(6, 3),
+            crd_sup: b.bit(6, 2),
+            nv_sup: b.bit(6, 1),
+            v_sup: b.bit(6, 0),
+            no_pi_chk: b.bit(7, 5),
+            p_i_i_sup: b.bit(7, 4),
+            luiclr: b.bit(7, 0),
+            lu_coll_type: b.u8(8) >> 5,
+            r_sup: b.bit(8, 4),
+            hssrelef: b.bit(8, 1),
+            cbcs: b.bit(8, 0),
+            multi_it_nexus_microcode_download: b.u8(9) & 0x0f,
+            extended_self_test_completion_minutes: b.be(10, 2) as u16,
+            poa_sup: b.bit(12, 7),
+            hra_sup: b.bit(12, 6),
+            vsa_sup: b.bit(12, 5),
+            maximum_supported_sense_data_length: b.u8(13),
+        })
+    }
+}
+
+/// ATA information (0x89), returned by SCSI to ATA translation layers.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AtaInformation {
+    pub sat_vendor: String,
+    pub sat_product: String,
+    pub sat_revision: String,
+    /// The ATA device signature (a register device to host FIS).
+    pub device_signature: Vec<u8>,
+    /// The command used to fetch `identify` (ECh IDENTIFY DEVICE or A1h
+    /// IDENTIFY PACKET DEVICE).
+    pub command_code: u8,
+    /// The 512 byte IDENTIFY (PACKET) DEVICE data.
+    pub identify: Vec<u8>,
+}
+
+impl AtaInformation {
+    pub fn parse(buf: &[u8]) -> Option<Self> {
+        let buf = page(buf, ATA_INFORMATION)?;
+        if buf.len() < 60 {
+            return None;
+        }
+
+        Some(Self {
+            sat_vendor: ascii(&buf[8..16]),
+            sat_product: ascii(&buf[16..32]),
+            sat_revision: ascii(&buf[32..36]),
+            device_signature: buf[36..56].to_vec(),
+            command_code: buf[56],
+            identify: buf[60..].to_vec(),
+        })
+    }
+
+    /// An IDENTIFY DEVICE string field spanning `words`. ATA strings hold
+    /// two characters per word, most significant byte first.
+    fn identify_string(&self, words: std::ops::Range<usize>) -> Option<String> {
+        let raw = self.identify.get(words.start * 2..words.end * 2)?;
+        let swapped: Vec<u8> = raw.chunks_exact(2).flat_map(|w| [w[1], w[0]]).collect();
+        Some(ascii(&swapped))
+    }
+
+    pub fn serial(&self) -> Option<String> {
+        self.identify_string(10..20)
+    }
+
+    pub fn firmware(&self) -> Option<String> {
+        self.identify_string(23..27)
+    }
+
+    pub fn model(&self) -> Option<String> {
+        self.identify_string(27..47)
+    }
+}
+
+/// Block limits (0xB0). Fields the device did not return (older devices
+/// return a shorter page) are zero.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct BlockLimits {
+    /// WRITE SAME with a block count of zero is not supported.
+    pub wsnz: bool,
+    pub maximum_compare_and_write_length: u8,
+    pub optimal_transfer_length_granularity: u16,
+    /// In logical blocks; zero means not reported.
+    pub maximum_transfer_length: u32,
+    pub optimal_transfer_length: u32,
+    pub maximum_prefetch_length: u32,
+    pub maximum_unmap_lba_count: u32,
+    pub maximum_unmap_block_descriptor_count: u32,
+    pub optimal_unmap_granularity: u32,
+    /// The unmap granularity alignment, if UGAVALID is set.
+    pub unmap_granularity_alignment: Option<u32>,
+    pub maximum_write_same_length: u64,
+    pub maximum_atomic_transfer_length: u32,
+    pub atomic_alignment: u32,
+    pub atomic_transfer_length_granularity: u32,
+    pub maximum_atomic_transfer_length_with_atomic_boundary: u32,
+    pub maximum_atomic_boundary_size: u32,
+}
+
+impl BlockLimits {
+    pub fn parse(buf: &[u8]) -> Option<Self> {
+        let b = Fields(page(buf, BLOCK_LIMITS)?);
+        Some(Self {
+            wsnz: b.bit(4, 0),
+            maximum_compare_and_write_length: b.u8(5),
+            optimal_transfer_length_granularity: b.be(6, 2) as u16,
+            maximum_transfer_length: b.be(8, 4) as u32,
+            optimal_transfer_length: b.be(12, 4) as u32,
+            maximum_prefetch_length: b.be(16, 4) as u32,
+            maximum_unmap_lba_count: b.be(20, 4) as u32,
+            maximum_unmap_block_descriptor_count: b.be(24, 4) as u32,
+            optimal_unmap_granularity: b.be(28, 4) as u32,
+            unmap_granularity_alignment: match b.bit(32, 7) {
+                true => Some(b.be(32, 4) as u32 & 0x7fff_ffff),
+                false => None,
+            },
+            maximum_write_same_length: b.be(36, 8),
+            maximum_atomic_transfer_length: b.be(44, 4) as u32,
+            atomic_alignment: b.be(48, 4) as u32,
+            atomic_transfer_length_granularity: b.be(52, 4) as u32,
+            maximum_atomic_transfer_length_with_atomic_boundary: b.be(56, 4) as u32,
+            maximum_atomic_boundary_size: b.be(60, 4) as u32,
+        })
+    }
+}
+
+/// The medium rotation rate from the block device characteristics page.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum RotationRate {
+    NotReported,
+    /// A solid state (non-rotating) medium.
+    NonRotating,
+    Rpm(u16),
+    Reserved(u16),
+}
+
+impl From<u16> for RotationRate {
+    fn from(val: u16) -> Self {
+        match val {
+            0x0000 => RotationRate::NotReported,
+            0x0001 => RotationRate::NonRotating,
+            0x0401..=0xfffe => RotationRate::Rpm(val),
+            v => RotationRate::Reserved(v),
+        }
+    }
+}
+
+impl fmt::Display for RotationRate {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RotationRate::NotReported => f.write_str("not reported"),
+            RotationRate::NonRotating => f.write_str("non-rotating"),
+            RotationRate::Rpm(rpm) => write!(f, "{rpm} rpm"),
+            RotationRate::Reserved(v) => write!(f, "reserved ({v:#06x})"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum FormFactor {
+    NotReported,
+    Inch5_25,
+    Inch3_5,
+    Inch2_5,
+    Inch1_8,
+    LessThan1_8,
+    Reserved(u8),
+}
+
+impl From<u8> for FormFactor {
+    fn from(val: u8) -> Self {
+        match val & 0x0f {
+            0 => FormFactor::NotReported,
+            1 => FormFactor::Inch5_25,
+            2 => FormFactor::Inch3_5,
+            3 => FormFactor::Inch2_5,
+            4 => FormFactor::Inch1_8,
+            5 => FormFactor::LessThan1_8,
+            v => FormFactor::Reserved(v),
+        }
+    }
+}
+
+impl fmt::Display for FormFactor {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FormFactor::NotReported => f.write_str("not reported"),
+            FormFactor::Inch5_25 => f.write_str("5.25 inch"),
+            FormFactor::Inch3_5 => f.write_str("3.5 inch"),
+            FormFactor::Inch2_5 => f.write_str("2.5 inch"),
+            FormFactor::Inch1_8 => f.write_str("1.8 inch"),
+            FormFactor::LessThan1_8 => f.write_str("less than 1.8 inch"),
+            FormFactor::Reserved(v) => write!(f, "reserved ({v:#x})"),
+        }
+    }
+}
+
+/// Block device characteristics (0xB1).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BlockDeviceCharacteristics {
+    pub medium_rotation_rate: RotationRate,
+    pub product_type: u8,
+    /// Write after block erase required.
+    pub wabereq: u8,
+    /// Write after cryptographic erase required.
+    pub wacereq: u8,
+    pub nominal_form_factor: FormFactor,
+    /// 0: not reported, 1: host aware, 2: device managed.
+    pub zoned: u8,
+    /// Force unit access behaviour.
+    pub fuab: bool,
+    /// Verify byte check unmapped LBA supported.
+    pub vbuls: bool,
+    /// Depopulation time, in seconds.
+    pub depopulation_time: u32,
+}
+
+impl BlockDeviceCharacteristics {
+    pub fn parse(buf: &[u8]) -> Option<Self> {
+        let b = Fields(page(buf, BLOCK_DEVICE_CHARACTERISTICS)?);
+        Some(Self {
+            medium_rotation_rate: RotationRate::from(b.be(4, 2) as u16),
+            product_type: b.u8(6),
+            wabereq: b.u8(7) >> 6,
+            wacereq: (b.u8(7) >> 4) & 0x03,
+            nominal_form_factor: FormFactor::from(b.u8(7)),
+            zoned: (b.u8(8) >> 4) & 0x03,
+            fuab: b.bit(8, 1),
+            vbuls: b.bit(8, 0),
+            depopulation_time: b.be(12, 4) as u32,
+        })
+    }
+}
+
+/// Logical block provisioning (0xB2).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LogicalBlockProvisioning {
+    pub threshold_exponent: u8,
+    /// UNMAP supported.
+    pub lbpu: bool,
+    /// WRITE SAME(16) with UNMAP supported.
+    pub lbpws: bool,
+    /// WRITE SAME(10) with UNMAP supported.
+    pub lbpws10: bool,
+    /// What unmapped blocks read back as (0: unspecified, 1: zeros).
+    pub lbprz: u8,
+    /// ANCHOR supported.
+    pub anc_sup: bool,
+    /// A provisioning group descriptor is present.
+    pub dp: bool,
+    pub minimum_percentage: u8,
+    /// 0: not reported/fully provisioned, 1: resource provisioned,
+    /// 2: thin provisioned.
+    pub provisioning_type: u8,
+    pub threshold_percentage: u8,
+}
+
+impl LogicalBlockProvisioning {
+    pub fn parse(buf: &[u8]) -> Option<Self> {
+        let b = Fields(page(buf, LOGICAL_BLOCK_PROVISIONING)?);
+        Some(Self {
+            threshold_exponent: b.u8(4),
+            lbpu: b.bit(5, 7),
+            lbpws: b.bit(5, 6),
+            lbpws10: b.bit(5, 5),
+            lbprz: (b.u8(5) >> 2) & 0x07,
+            anc_sup: b.bit(5, 1),
+            dp: b.bit(5, 0),
+            minimum_percentage: b.u8(6) >> 3,
+            provisioning_type: b.u8(6) & 0x07,
+            threshold_percentage: b.u8(7),
+        })
+    }
+}
+
+/// Zoned block device characteristics (0xB6).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ZonedBlockDeviceCharacteristics {
+    /// Unrestricted read in sequential write required zone.
+    pub urswrz: bool,
+    pub optimal_open_sequential_write_preferred_zones: u32,
+    pub optimal_non_sequentially_written_sequential_write_preferred_zones: u32,
+    /// Zero means unlimited.
+    pub maximum_open_sequential_write_required_zones: u32,
+}
+
+impl ZonedBlockDeviceCharacteristics {
+    pub fn parse(buf: &[u8]) -> Option<Self> {
+        let b = Fields(page(buf, ZONED_BLOCK_DEVICE_CHARACTERISTICS)?);
+        Some(Self {
+            urswrz: b.bit(4, 0),
+            optimal_open_sequential_write_preferred_zones: b.be(8, 4) as u32,
+            optimal_non_sequentially_written_sequential_write_preferred_zones: b.be(12, 4) as u32,
+            maximum_open_sequential_write_required_zones: b.be(16, 4) as u32,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A VPD page holding `body`.
+    fn vpd(page: u8, body: &[u8]) -> Vec<u8> {
+        let mut buf = vec![0, page];
+        buf.extend_from_slice(&(body.len() as u16).to_be_bytes());
+        buf.extend_from_slice(body);
+        buf
+    }
+
+    /// A designation descriptor. `piv_assoc_type` is byte 1 as sent.
+    fn descriptor(proto_code_set: u8, piv_assoc_type: u8, value: &[u8]) -> Vec<u8> {
+        let mut d = vec![proto_code_set, piv_assoc_type, 0, value.len() as u8];
+        d.extend_from_slice(value);
+        d
+    }
+
+    fn designator(d: &[u8]) -> Designator {
+        let page = vpd(DEVICE_IDENTIFICATION, d);
+        let mut id = DeviceIdentification::parse(&page).unwrap();
+        assert_eq!(id.designators.len(), 1);
+        id.designators.remove(0)
+    }
+
+    #[test]
+    fn rejects_wrong_page_or_short_buffer() {
+        type Parses = fn(&[u8]) -> bool;
+        let parsers: [(u8, Parses); 9] = [
+            (SUPPORTED_PAGES, |b| SupportedPages::parse(b).is_some()),
+            (UNIT_SERIAL_NUMBER, |b| UnitSerialNumber::parse(b).is_some()),
+            (DEVICE_IDENTIFICATION, |b| {
+                DeviceIdentification::parse(b).is_some()
+            }),
+            (EXTENDED_INQUIRY, |b| ExtendedInquiry::parse(b).is_some()),
+            (ATA_INFORMATION, |b| AtaInformation::parse(b).is_some()),
+            (BLOCK_LIMITS, |b| BlockLimits::parse(b).is_some()),
+            (BLOCK_DEVICE_CHARACTERISTICS, |b| {
+                BlockDeviceCharacteristics::parse(b).is_some()
+            }),
+            (LOGICAL_BLOCK_PROVISIONING, |b| {
+                LogicalBlockProvisioning::parse(b).is_some()
+            }),
+            (ZONED_BLOCK_DEVICE_CHARACTERISTICS, |b| {
+                ZonedBlockDeviceCharacteristics::parse(b).is_some()
+            }),
+        ];
+
+        for (code, parse) in parsers {
+            let page = vpd(code, &[0; 600]);
+            assert!(parse(&page), "{code:#x}");
+            // A page claiming more than was returned is cut to the buffer.
+            assert!(parse(&page[..page.len() - 100]), "{code:#x}");
+
+            let mut other = page.clone();
+            other[1] ^= 0x01;
+            assert!(!parse(&other), "{code:#x}");
+            for len in 0..4 {
+                assert!(!parse(&page[..len]), "{code:#x}");
+            }
+        }
+
+        // Pages which need their fixed fields.
+        assert_eq!(AtaInformation::parse(&vpd(ATA_INFORMATION, &[0; 55])), None);
+    }
+
+    #[test]
+    fn supported_pages() {
+        let page = vpd(SUPPORTED_PAGES, &[0x00, 0x80, 0x83, 0xb0, 0xb1]);
+        let pages = SupportedPages::parse(&page).unwrap();
+        assert_eq!(pages.pages, [0x00, 0x80, 0x83, 0xb0, 0xb1]);
+        assert!(pages.contains(BLOCK_LIMITS));
+        assert!(!pages.contains(LOGICAL_BLOCK_PROVISIONING));
+
+        // Bytes past the page length are not part of the page.
+        let mut page = page;
+        page[3] = 2;
+        assert_eq!(SupportedPages::parse(&page).unwrap().pages, [0x00, 0x80]);
+    }
+
+    #[test]
+    fn unit_serial_number() {
+        let page = vpd(UNIT_SERIAL_NUMBER, b"    S3Z9NB0K123456\0\0");
+        assert_eq!(
+            UnitSerialNumber::parse(&page).unwrap().serial,
+            "S3Z9NB0K123456"
+        );
+        let page = vpd(UNIT_SERIAL_NUMBER, b"");
+        assert_eq!(UnitSerialNumber::parse(&page).unwrap().serial, "");
+    }
+
+    #[test]
+    fn designator_types() {
+        let uuid = [
+            0x10, 0, 0x12, 0x34, 0x56, 0x78, 0x9a, 0xbc, 0xde, 0xf0, 0x01, 0x23, 0x45, 0x67, 0x89,
+            0xab, 0xcd, 0xef,
+        ];
+        // (descriptor, decoded value, display)
+        let cases: &[(Vec<u8>, DesignatorValue<'_>, &str)] = &[
+            (
+                descriptor(0x02, 0x01, b"ATA     Samsung SSD 860  "),
+                DesignatorValue::T10VendorId {
+                    vendor: "ATA",
+                    vendor_specific: b"Samsung SSD 860  ",
+                },
+                "t10.ATA Samsung SSD 860",
+            ),
+            (
+                descriptor(
+                    0x01,
+                    0x02,
+                    &[0x00, 0x11, 0x22, 0x33, 0x44, 0x55, 0x66, 0x77],
+                ),
+                DesignatorValue::Eui64(&[0x00, 0x11, 0x22, 0x33, 0x44, 0x55, 0x66, 0x77]),
+                "eui.0011223344556677",
+            ),
+            (
+                descriptor(
+                    0x01,
+                    0x03,
+                    &[0x20, 0x00, 0x00, 0x11, 0x22, 0x33, 0x44, 0x55],
+                ),
+                DesignatorValue::Naa {
+                    naa: 2,
+                    ieee_company_id: Some(0x00_1122),
+                    value: &[0x20, 0x00, 0x00, 0x11, 0x22, 0x33, 0x44, 0x55],
+                },
+                "naa.2000001122334455",
+            ),
+            (
+                descriptor(0x01, 0x03, &[0x3f, 1, 2, 3, 4, 5, 6, 7]),
+                DesignatorValue::Naa {
+                    naa: 3,
+                    ieee_company_id: None,
+                    value: &[0x3f, 1, 2, 3, 4, 5, 6, 7],
+                },
+                "naa.3f01020304050607",
+            ),
+            (
+                descriptor(
+                    0x01,
+                    0x03,
+                    &[0x50, 0x01, 0x43, 0x80, 0x12, 0x34, 0x56, 0x78],
+                ),
+                DesignatorValue::Naa {
+                    naa: 5,
+                    ieee_company_id: Some(0x00_1438),
+                    value: &[0x50, 0x01, 0x43, 0x80, 0x12, 0x34, 0x56, 0x78],
+                },
+                "naa.5001438012345678",
+            ),
+            (
+                descriptor(
+                    0x01,
+                    0x03,
+                    &[
+                        0x60, 0x0c, 0x0f, 0xf1, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0x2a,
+                    ],
+                ),
+                DesignatorValue::Naa {
+                    naa: 6,
+                    ieee_company_id: Some(0x00_c0ff),
+                    value: &[
+                        0x60, 0x0c, 0x0f, 0xf1, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0x2a,
+                    ],
+                },
+                "naa.600c0ff100000000000000000000002a",
+            ),
+            (
+                descriptor(0x61, 0x94, &[0, 0, 0, 2]),
+                DesignatorValue::RelativeTargetPort(2),
+                "relative target port 2",
+            ),
+            (
+                descriptor(0x01, 0x15, &[0, 0, 0x01, 0x00]),
+                DesignatorValue::TargetPortGroup(256),
+                "target port group 256",
+            ),
+            (
+                descriptor(0x01, 0x06, &[0, 0, 0, 7]),
+                DesignatorValue::LogicalUnitGroup(7),
+                "logical unit group 7",
+            ),
+            (
+                descriptor(0x01, 0x07, &[0xaa; 16]),
+                DesignatorValue::Md5LogicalUnit(&[0xaa; 16]),
+                "aaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaa",
+            ),
+            (
+                descriptor(0x03, 0x28, b"iqn.2001-04.com.example:disk\0\0\0\0"),
+                DesignatorValue::ScsiNameString("iqn.2001-04.com.example:disk"),
+                "iqn.2001-04.com.example:disk",
+            ),
+            (
+                descriptor(0x01, 0x19, &[0xde, 0xad]),
+                DesignatorValue::ProtocolSpecificPort(&[0xde, 0xad]),
+                "dead",
+            ),
+            (
+                descriptor(0x01, 0x0a, &uuid),
+                DesignatorValue::Uuid(&uuid[2..]),
+                "12345678-9abc-def0-0123-456789abcdef",
+            ),
+            (
+                descriptor(0x02, 0x00, b"xyz"),
+                DesignatorValue::VendorSpecific(b"xyz"),
+                "78797a",
+            ),
+            (
+                descriptor(0x01, 0x0b, &[1, 2]),
+                DesignatorValue::Other(&[1, 2]),
+                "0102",
+            ),
+            // Values too short for their type.
+            (
+                descriptor(0x02, 0x01, b"ATA"),
+                DesignatorValue::Other(b"ATA"),
+                "415441",
+            ),
+            (descriptor(0x01, 0x03, &[]), DesignatorValue::Other(&[]), ""),
+            (
+                descriptor(0x01, 0x04, &[0, 2]),
+                DesignatorValue::Other(&[0, 2]),
+                "0002",
+            ),
+            (
+                descriptor(0x01, 0x0a, &uuid[..17]),
+                DesignatorValue::Other(&uuid[..17]),
+                "1000123456789abcdef00123456789abcd",
+            ),
+        ];
+
+        for (d, value, display) in cases {
+            let designator = designator(d);
+            assert_eq!(designator.decode(), *value, "{d:02x?}");
+            assert_eq!(designator.to_string(), *display, "{d:02x?}");
+        }
+    }
+
+    #[test]
+    fn designator_header() {
+        let d = designator(&descriptor(0x61, 0x94, &[0, 0, 0, 2]));
+        assert_eq!(d.protocol_identifier, Some(6));
+        assert_eq!(d.code_set, CodeSet::Binary);
+        assert_eq!(d.association, Association::TargetPort);
+        assert_eq!(d.designator_type, DesignatorType::RelativeTargetPort);
+
+        // Without PIV the protocol identifier is not meaningful.
+        let d = designator(&descriptor(0x63, 0x28, b"name"));
+        assert_eq!(d.protocol_identifier, None);
+        assert_eq!(d.code_set, CodeSet::Utf8);
+        assert_eq!(d.association, Association::TargetDevice);
+        assert_eq!(d.designator_type, DesignatorType::ScsiNameString);
+
+        let d = designator(&descriptor(0x0f, 0x3f, &[]));
+        assert_eq!(d.code_set, CodeSet::Reserved(0x0f));
+        assert_eq!(d.association, Association::Reserved);
+        assert_eq!(d.designator_type, DesignatorType::Reserved(0x0f));
+    }
+
+    #[test]
+    fn device_identification() {
+        let naa = descriptor(
+            0x01,
+            0x03,
+            &[0x50, 0x01, 0x43, 0x80, 0x12, 0x34, 0x56, 0x78],
+        );
+        let port = descriptor(0x61, 0x94, &[0, 0, 0, 1]);
+        let t10 = descriptor(0x02, 0x01, b"LIO-ORG disk0");
+        let page = vpd(DEVICE_IDENTIFICATION, &[naa, port, t10].concat());
+
+        let id = DeviceIdentification::parse(&page).unwrap();
+        assert_eq!(id.designators.len(), 3);
+        let lu: Vec<_> = id.logical_unit().map(|d| d.to_string()).collect();
+        assert_eq!(lu, ["naa.5001438012345678", "t10.LIO-ORG disk0"]);
+
+        // A descriptor running past the end of the page, or a partial
+        // header, ends the list.
+        for cut in 1..=10 {
+            let id = DeviceIdentification::parse(&page[..page.len() - cut]).unwrap();
+            assert_eq!(id.designators.len(), 2, "{cut}");
+        }
+        let mut bogus = page.clone();
+        bogus[4 + 3] = 0xff;
+        assert!(DeviceIdentification::parse(&bogus)
+            .unwrap()
+            .designators
+            .is_empty());
+    }
+
+    #[test]
+    fn extended_inquiry() {
+        let mut body = [0u8; 60];
+        body[0] = 0x40 | 0x10 | 0x07;
+        body[1] = 0x3f;
+        body[2] = 0x0f;
+        body[3] = 0x31;
+        body[4] = 0xa0 | 0x13;
+        body[5] = 0x05;
+        body[6..8].copy_from_slice(&90u16.to_be_bytes());
+        body[8] = 0xe0;
+        body[9] = 252;
+
+        let e = ExtendedInquiry::parse(&vpd(EXTENDED_INQUIRY, &body)).unwrap();
+        assert_eq!(
+            e,
+            ExtendedInquiry {
+                activate_microcode: 1,
+                spt: 2,
+                grd_chk: true,
+                app_chk: true,
+                ref_chk: true,
+                uask_sup: true,
+                group_sup: true,
+                prior_sup: true,
+                headsup: true,
+                ordsup: true,
+                simpsup: true,
+                wu_sup: true,
+                crd_sup: true,
+                nv_sup: true,
+                v_sup: true,
+                no_pi_chk: true,
+                p_i_i_sup: true,
+                luiclr: true,
+                lu_coll_type: 5,
+                r_sup: true,
+                hssrelef: true,
+                cbcs: true,
+                multi_it_nexus_microcode_download: 5,
+                extended_self_test_completion_minutes: 90,
+                poa_sup: true,
+                hra_sup: true,
+                vsa_sup: true,
+                maximum_supported_sense_data_length: 252,
+            }
+        );
+
+        // Fields beyond a short page read as zero.
+        let e = ExtendedInquiry::parse(&vpd(EXTENDED_INQUIRY, &body[..2])).unwrap();
+        assert_eq!(e.spt, 2);
+        assert!(e.simpsup && !e.v_sup && !e.cbcs);
+        assert_eq!(e.maximum_supported_sense_data_length, 0);
+    }
+
+    #[test]
+    fn ata_information() {
+        fn ata_string(s: &[u8]) -> Vec<u8> {
+            s.chunks_exact(2).flat_map(|w| [w[1], w[0]]).collect()
+        }
+
+        let mut identify = [0u8; 512];
+        identify[20..40].copy_from_slice(&ata_string(b"S3Z9NB0K123456      "));
+        identify[46..54].copy_from_slice(&ata_string(b"RVT04B6Q"));
+        identify[54..94].copy_from_slice(&ata_string(b"Samsung SSD 860 EVO 500GB               "));
+
+        let mut body = vec![0u8; 4];
+        body.extend_from_slice(b"linux   ");
+        body.extend_from_slice(b"libata          ");
+        body.extend_from_slice(b"3.00");
+        body.extend_from_slice(&[0x34, 0, 0x50, 0x01, 0x01, 0, 0, 0, 0, 0]);
+        body.extend_from_slice(&[0; 10]);
+        body.extend_from_slice(&[0xec, 0, 0, 0]);
+        body.extend_from_slice(&identify);
+
+        let ata = AtaInformation::parse(&vpd(ATA_INFORMATION, &body)).unwrap();
+        assert_eq!(ata.sat_vendor, "linux");
+        assert_eq!(ata.sat_product, "libata");
+        assert_eq!(ata.sat_revision, "3.00");
+        assert_eq!(ata.device_signature.len(), 20);
+        assert_eq!(ata.device_signature[0], 0x34);
+        assert_eq!(ata.command_code, 0xec);
+        assert_eq!(ata.identify.len(), 512);
+        assert_eq!(ata.serial().as_deref(), Some("S3Z9NB0K123456"));
+        assert_eq!(ata.firmware().as_deref(), Some("RVT04B6Q"));
+        assert_eq!(ata.model().as_deref(), Some("Samsung SSD 860 EVO 500GB"));
+
+        // IDENTIFY data cut short loses the fields it no longer holds.
+        let ata = AtaInformation::parse(&vpd(ATA_INFORMATION, &body[..56 + 50])).unwrap();
+        assert_eq!(ata.serial().as_deref(), Some("S3Z9NB0K123456"));
+        assert_eq!(ata.firmware(), None);
+        assert_eq!(ata.model(), None);
+    }
+
+    #[test]
+    fn block_limits() {
+        let mut body = [0u8; 60];
+        body[0] = 0x01;
+        body[1] = 0x20;
+        body[2..4].copy_from_slice(&8u16.to_be_bytes());
+        body[4..8].copy_from_slice(&0x0000_4000u32.to_be_bytes());
+        body[8..12].copy_from_slice(&0x0000_0800u32.to_be_bytes());
+        body[12..16].copy_from_slice(&0x0001_0000u32.to_be_bytes());
+        body[16..20].copy_from_slice(&0xffff_ffffu32.to_be_bytes());
+        body[20..24].copy_from_slice(&256u32.to_be_bytes());
+        body[24..28].copy_from_slice(&8u32.to_be_bytes());
+        body[28..32].copy_from_slice(&0x8000_0003u32.to_be_bytes());
+        body[32..40].copy_from_slice(&0x1_0000_0000u64.to_be_bytes());
+        body[40..44].copy_from_slice(&64u32.to_be_bytes());
+        body[44..48].copy_from_slice(&8u32.to_be_bytes());
+        body[48..52].copy_from_slice(&2u32.to_be_bytes());
+        body[52..56].copy_from_slice(&128u32.to_be_bytes());
+        body[56..60].copy_from_slice(&16u32.to_be_bytes());
+
+        let limits = BlockLimits::parse(&vpd(BLOCK_LIMITS, &body)).unwrap();
+        assert_eq!(
+            limits,
+            BlockLimits {
+                wsnz: true,
+                maximum_compare_and_write_length: 0x20,
+                optimal_transfer_length_granularity: 8,
+                maximum_transfer_length: 0x4000,
+                optimal_transfer_length: 0x800,
+                maximum_prefetch_length: 0x1_0000,
+                maximum_unmap_lba_count: 0xffff_ffff,
+                maximum_unmap_block_descriptor_count: 256,
+                optimal_unmap_granularity: 8,
+                unmap_granularity_alignment: Some(3),
+                maximum_write_same_length: 0x1_0000_0000,
+                maximum_atomic_transfer_length: 64,
+                atomic_alignment: 8,
+                atomic_transfer_length_granularity: 2,
+                maximum_atomic_transfer_length_with_atomic_boundary: 128,
+                maximum_atomic_boundary_size: 16,
+            }
+        );
+
+        // Without UGAVALID there is no alignment; an old 0x0c byte page
+        // stops after the optimal transfer length.
+        body[28] = 0;
+        let limits = BlockLimits::parse(&vpd(BLOCK_LIMITS, &body)).unwrap();
+        assert_eq!(limits.unmap_granularity_alignment, None);
+        let limits = BlockLimits::parse(&vpd(BLOCK_LIMITS, &body[..12])).unwrap();
+        assert_eq!(limits.optimal_transfer_length, 0x800);
+        assert_eq!(limits.maximum_prefetch_length, 0);
+        assert_eq!(limits.maximum_write_same_length, 0);
+    }
+
+    #[test]
+    fn block_device_characteristics() {
+        let mut body = [0u8; 60];
+        body[0..2].copy_from_slice(&7200u16.to_be_bytes());
+        body[2] = 0x01;
+        body[3] = 0x80 | 0x20 | 0x03;
+        body[4] = 0x10 | 0x02 | 0x01;
+        body[8..12].copy_from_slice(&3600u32.to_be_bytes());
+
+        let b =
+            BlockDeviceCharacteristics::parse(&vpd(BLOCK_DEVICE_CHARACTERISTICS, &body)).unwrap();
+        assert_eq!(
+            b,
+            BlockDeviceCharacteristics {
+                medium_rotation_rate: RotationRate::Rpm(7200),
+                product_type: 1,
+                wabereq: 2,
+                wacereq: 2,
+                nominal_form_factor: FormFactor::Inch2_5,
+                zoned: 1,
+                fuab: true,
+                vbuls: true,
+                depopulation_time: 3600,
+            }
+        );
+        assert_eq!(b.medium_rotation_rate.to_string(), "7200 rpm");
+        assert_eq!(b.nominal_form_factor.to_string(), "2.5 inch");
+
+        for (raw, rate, display) in [
+            (0x0000, RotationRate::NotReported, "not reported"),
+            (0x0001, RotationRate::NonRotating, "non-rotating"),
+            (0x0400, RotationRate::Reserved(0x0400), "reserved (0x0400)"),
+            (0x0401, RotationRate::Rpm(0x0401), "1025 rpm"),
+            (0xfffe, RotationRate::Rpm(0xfffe), "65534 rpm"),
+            (0xffff, RotationRate::Reserved(0xffff), "reserved (0xffff)"),
+        ] {
+            assert_eq!(RotationRate::from(raw), rate);
+            assert_eq!(rate.to_string(), display);
+        }
+        for (raw, display) in [
+            (0, "not reported"),
+            (1, "5.25 inch"),
+            (2, "3.5 inch"),
+            (4, "1.8 inch"),
+            (5, "less than 1.8 inch"),
+            (0xf6, "reserved (0x6)"),
+        ] {
+            assert_eq!(FormFactor::from(raw).to_string(), display);
+        }
+    }
+
+    #[test]
+    fn logical_block_provisioning() {
+        let body = [
+            0x0a,
+            0x80 | 0x40 | 0x20 | 0x04 | 0x02 | 0x01,
+            0x50 | 0x02,
+            75,
+        ];
+        let p = LogicalBlockProvisioning::parse(&vpd(LOGICAL_BLOCK_PROVISIONING, &body)).unwrap();
+        assert_eq!(
+            p,
+            LogicalBlockProvisioning {
+                threshold_exponent: 0x0a,
+                lbpu: true,
+                lbpws: true,
+                lbpws10: true,
+                lbprz: 1,
+                anc_sup: true,
+                dp: true,
+                minimum_percentage: 10,
+                provisioning_type: 2,
+                threshold_percentage: 75,
+            }
+        );
+    }
+
+    #[test]
+    fn zoned_block_device_characteristics() {
+        let mut body = [0u8; 60];
+        body[0] = 0x01;
+        body[4..8].copy_from_slice(&128u32.to_be_bytes());
+        body[8..12].copy_from_slice(&64u32.to_be_bytes());
+        body[12..16].copy_from_slice(&0xffff_ffffu32.to_be_bytes());
+
+        let z =
+            ZonedBlockDeviceCharacteristics::parse(&vpd(ZONED_BLOCK_DEVICE_CHARACTERISTICS, &body))
+                .unwrap();
+        assert_eq!(
+            z,
+            ZonedBlockDeviceCharacteristics {
+                urswrz: true,
+                optimal_open_sequential_write_preferred_zones: 128,
+                optimal_non_sequentially_written_sequential_write_preferred_zones: 64,
+                maximum_open_sequential_write_required_zones: 0xffff_ffff,
+            }
+        );
+    }
+}
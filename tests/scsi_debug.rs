@@ -0,0 +1,109 @@
+/*
+ * Copyright 2025 Jason King
+ */
+
+//! Tests of the `SG_IO` transport against a Linux `scsi_debug` device.
+//!
+//! These need root and a `scsi_debug` logical unit, so they only run when
+//! `USCSI_SCSI_DEBUG` names its sg node, e.g.:
+//!
+//! ```text
+//! # modprobe scsi_debug dev_size_mb=16
+//! # USCSI_SCSI_DEBUG=/dev/sg1 cargo test --test scsi_debug
+//! ```
+//!
+//! The READ/WRITE test writes to the first blocks of the device and
+//! restores them afterwards.
+
+#![cfg(target_os = "linux")]
+
+use std::io::{IoSlice, IoSliceMut};
+use uscsi::cdb::{Cdb, Read, ReadCapacity16, TestUnitReady, Write};
+use uscsi::{Capacity, Command, Device, Flags, ScsiStatus, SgIo, Timeout, Transport};
+
+const ENV: &str = "USCSI_SCSI_DEBUG";
+
+/// Open the scsi_debug device, or `None` (skipping the test) if
+/// `USCSI_SCSI_DEBUG` is not set.
+fn device() -> Option<Device<SgIo>> {
+    let Some(path) = std::env::var_os(ENV) else {
+        eprintln!("{ENV} is not set; skipping");
+        return None;
+    };
+    let transport =
+        SgIo::open(&path).unwrap_or_else(|e| panic!("opening {}: {e}", path.to_string_lossy()));
+    Some(Device::new(transport))
+}
+
+#[test]
+fn inquiry() {
+    let Some(mut dev) = device() else { return };
+
+    let inq = dev.inquiry().unwrap();
+    assert_eq!(inq.device_type, 0);
+    assert_eq!(inq.vendor, "Linux");
+    assert_eq!(inq.product, "scsi_debug");
+    assert!(!inq.revision.is_empty());
+
+    let cdb = TestUnitReady.to_bytes();
+    let result = dev.submit(&mut Command::new(&cdb)).unwrap();
+    assert_eq!(result.status, ScsiStatus::Good);
+}
+
+#[test]
+fn read_capacity() {
+    let Some(mut dev) = device() else { return };
+
+    let cap = dev.capacity().unwrap();
+    assert_eq!(cap.block_size, 512);
+    assert!(cap.blocks() >= 64, "{cap:?}");
+
+    // The same through the transport directly.
+    let mut buf = [0u8; ReadCapacity16::DATA_LEN];
+    let cdb = ReadCapacity16::default().to_bytes();
+    let result = dev
+        .transport_mut()
+        .submit(&mut Command::new(&cdb).data_in(&mut buf))
+        .unwrap();
+    assert_eq!(result.status, ScsiStatus::Good);
+    assert_eq!(result.resid, 0);
+    assert_eq!(Capacity::parse16(&buf), Some(cap));
+}
+
+#[test]
+fn read_write_round_trip() {
+    let Some(mut dev) = device() else { return };
+    let bs = dev.capacity().unwrap().block_size as usize;
+    let blocks = 8;
+    let timeout = Timeout::DEFAULT;
+
+    let mut saved = vec![0u8; blocks * bs];
+    dev.read_blocks(0, &mut saved).unwrap();
+
+    let data: Vec<u8> = (0..blocks * bs).map(|i| (i * 7 % 251) as u8).collect();
+    let result = dev.write_blocks(0, &data).unwrap();
+    assert_eq!(result.resid, 0);
+    let mut buf = vec![0u8; data.len()];
+    dev.read_blocks(0, &mut buf).unwrap();
+    assert_eq!(buf, data);
+
+    // Gather a WRITE and scatter a READ across uneven segments.
+    let cdb = Write::new(0, blocks as u32, bs as u32).to_bytes();
+    let rev: Vec<u8> = data.iter().rev().copied().collect();
+    let (a, rest) = rev.split_at(100);
+    let (b, c) = rest.split_at(bs * 3);
+    let iov = [IoSlice::new(a), IoSlice::new(b), IoSlice::new(c)];
+    dev.write_vectored(&cdb, &iov, None, Flags::empty(), timeout)
+        .unwrap();
+
+    let cdb = Read::new(0, blocks as u32, bs as u32).to_bytes();
+    let (mut a, mut b) = (vec![0u8; 1000], vec![0u8; blocks * bs - 1000]);
+    let mut iov = [IoSliceMut::new(&mut a), IoSliceMut::new(&mut b)];
+    let result = dev
+        .read_vectored(&cdb, &mut iov, None, Flags::empty(), timeout)
+        .unwrap();
+    assert_eq!(result.resid, 0);
+    assert_eq!([a, b].concat(), rev);
+
+    dev.write_blocks(0, &saved).unwrap();
+}